@@ -35,6 +35,10 @@ pub enum Subcommand {
 	/// Revert the chain to a previous state.
 	Revert(sc_cli::RevertCmd),
 
+	/// Export every kitty to a JSON or CSV file.
+	#[structopt(name = "export-kitties", about = "Export the kitty collection at a block.")]
+	ExportKitties(crate::export::ExportKittiesCmd),
+
 	/// The custom benchmark subcommmand benchmarking runtime pallets.
 	#[structopt(name = "benchmark", about = "Benchmark runtime pallets.")]
 	Benchmark(frame_benchmarking_cli::BenchmarkCmd),
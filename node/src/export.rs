@@ -0,0 +1,148 @@
+//! The `export-kitties` subcommand, writing the whole kitty collection to a JSON or CSV file.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use node_template_runtime::{opaque::Block, AccountId, Balance, BlockNumber};
+use pallet_kitties_runtime_api::KittiesApi;
+use sc_cli::{CliConfiguration, DatabaseParams, PruningParams, SharedParams};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_core::hexdisplay::HexDisplay;
+use sp_runtime::generic::BlockId;
+use structopt::StructOpt;
+
+/// File format of an export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+	/// A JSON array with one object per kitty.
+	Json,
+	/// A header line, then one `id,owner,gender,dna` line per kitty.
+	Csv,
+}
+
+impl FromStr for ExportFormat {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"json" => Ok(ExportFormat::Json),
+			"csv" => Ok(ExportFormat::Csv),
+			_ => Err(format!("Unknown export format `{}`, expected `json` or `csv`", s)),
+		}
+	}
+}
+
+/// The `export-kitties` command used to write the kitty collection to a file.
+#[derive(Debug, StructOpt)]
+pub struct ExportKittiesCmd {
+	/// Output file name.
+	#[structopt(parse(from_os_str))]
+	pub output: PathBuf,
+
+	/// Block to read the collection at.
+	///
+	/// Default is best block.
+	#[structopt(long = "at", value_name = "BLOCK")]
+	pub at: Option<BlockNumber>,
+
+	/// Write `json` or `csv`.
+	#[structopt(long, default_value = "json", possible_values = &["json", "csv"])]
+	pub format: ExportFormat,
+
+	/// Kitty ids read from the runtime per call.
+	#[structopt(long, default_value = "1000")]
+	pub page_size: u32,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub pruning_params: PruningParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub database_params: DatabaseParams,
+}
+
+impl ExportKittiesCmd {
+	/// Run the export-kitties command, writing kitties to the output a page at a time.
+	pub fn run<C>(&self, client: Arc<C>) -> sc_cli::Result<()>
+	where
+		C: ProvideRuntimeApi<Block> + HeaderBackend<Block>,
+		C::Api: KittiesApi<Block, AccountId, u32, Balance>,
+	{
+		if self.page_size == 0 {
+			return Err("Page size must be at least 1".into());
+		}
+		let at = match self.at {
+			Some(number) => {
+				client.hash(number)
+					.map_err(|e| format!("Failed to look up block {}: {:?}", number, e))?
+					.ok_or_else(|| format!("Block {} not found", number))?;
+				BlockId::Number(number)
+			},
+			None => BlockId::Hash(client.info().best_hash),
+		};
+
+		let mut output = BufWriter::new(File::create(&self.output)?);
+		match self.format {
+			ExportFormat::Json => write!(output, "[")?,
+			ExportFormat::Csv => writeln!(output, "id,owner,gender,dna")?,
+		}
+
+		let api = client.runtime_api();
+		let mut start = 0;
+		let mut exported = 0u64;
+		loop {
+			let (kitties, next) = api.kitties_page(&at, start, self.page_size)
+				.map_err(|e| format!("Failed to read kitties from {}: {:?}", start, e))?;
+			for (kitty_id, owner, kitty) in kitties {
+				let gender = format!("{:?}", kitty.gender());
+				let dna = HexDisplay::from(&kitty.dna());
+				match self.format {
+					ExportFormat::Json => write!(
+						output,
+						"{}\n  {{\"id\": {}, \"owner\": \"{}\", \"gender\": \"{}\", \"dna\": \"0x{}\"}}",
+						if exported == 0 { "" } else { "," },
+						kitty_id, owner, gender, dna,
+					)?,
+					ExportFormat::Csv => writeln!(
+						output, "{},{},{},0x{}", kitty_id, owner, gender, dna,
+					)?,
+				}
+				exported += 1;
+			}
+			match next {
+				Some(next) => start = next,
+				None => break,
+			}
+		}
+
+		if self.format == ExportFormat::Json {
+			writeln!(output, "\n]")?;
+		}
+		output.flush()?;
+		println!("Exported {} kitties to {}", exported, self.output.display());
+		Ok(())
+	}
+}
+
+impl CliConfiguration for ExportKittiesCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+
+	fn pruning_params(&self) -> Option<&PruningParams> {
+		Some(&self.pruning_params)
+	}
+
+	fn database_params(&self) -> Option<&DatabaseParams> {
+		Some(&self.database_params)
+	}
+}
@@ -0,0 +1,86 @@
+//! Runtime API definition for the kitties pallet.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::Codec;
+use sp_core::H256;
+use sp_runtime::DispatchError;
+use sp_std::vec::Vec;
+
+pub use pallet_kitties::{
+    CollectionBid, CollectionMetadata, Denomination, Kitty, OwnershipProof, TaxReport,
+};
+
+sp_api::decl_runtime_apis! {
+    pub trait KittiesApi<AccountId, KittyIndex, Balance> where
+        AccountId: Codec,
+        KittyIndex: Codec,
+        Balance: Codec,
+    {
+        /// Preview the offspring of two kitties using `seed` in place of on-chain randomness.
+        /// Runs the same checks as `breed_kitty` (ownership aside) without touching state.
+        fn simulate_breed(first: KittyIndex, second: KittyIndex, seed: H256) -> Result<Kitty, DispatchError>;
+
+        /// Whether two kitties can breed right now, running every check of `breed_kitty`.
+        /// Ownership by `owner` is only checked if it is given.
+        fn can_breed(owner: Option<AccountId>, first: KittyIndex, second: KittyIndex) -> Result<(), DispatchError>;
+
+        /// Whether a kitty's dna was derived from `seed_preimage`, the SCALE encoded seed whose
+        /// hash is in its `KittyCreated` or `KittyBred` event.
+        fn verify_dna(kitty_id: KittyIndex, seed_preimage: Vec<u8>) -> bool;
+
+        /// Resolve a kitty and its current owner from its id alone.
+        fn kitty_of(kitty_id: KittyIndex) -> Option<(AccountId, Kitty)>;
+
+        /// Up to `limit` kitties whose trait `trait_id` falls in bucket `value`.
+        fn kitties_with_trait(trait_id: u8, value: u8, limit: u32) -> Vec<KittyIndex>;
+
+        /// Up to `limit` kitties within `max_distance` dna bits of `kitty_id`, closest first,
+        /// with their distance. Only kitties sharing a trait bucket with it are compared.
+        fn similar_kitties(kitty_id: KittyIndex, max_distance: u32, limit: u32) -> Vec<(KittyIndex, u32)>;
+
+        /// Value of a kitty from the recent sale prices of kitties sharing its traits, or `None`
+        /// if no such kitty has sold.
+        fn appraise(kitty_id: KittyIndex) -> Option<Balance>;
+
+        /// Id of the kitty most recently created, bred or imported for `owner`.
+        fn last_minted_by(owner: AccountId) -> Option<KittyIndex>;
+
+        /// Describe the kitties error with `code`, the index in `DispatchError::Module`, as
+        /// `Name: documentation`. Empty if there is no such error.
+        fn describe_error(code: u8) -> Vec<u8>;
+
+        /// SVG portrait of a kitty drawn from its dna, or nothing if it does not exist.
+        fn render_kitty(kitty_id: KittyIndex) -> Vec<u8>;
+
+        /// ERC-721 metadata JSON of a kitty, with its portrait as the image and its traits as
+        /// attributes, or nothing if it does not exist.
+        fn token_metadata_json(kitty_id: KittyIndex) -> Vec<u8>;
+
+        /// Up to `limit` kitties waiting in the shelter for adoption.
+        fn sheltered_kitties(limit: u32) -> Vec<KittyIndex>;
+
+        /// The kitties with ids from `start` up to `start + limit` with their owners, and the id
+        /// the next page starts from, or `None` after the last page. Burned ids are skipped.
+        fn kitties_page(start: KittyIndex, limit: u32) -> (Vec<(KittyIndex, AccountId, Kitty)>, Option<KittyIndex>);
+
+        /// Merkle proof of a kitty's owner against the latest owner snapshot, if the kitty is in
+        /// it.
+        fn ownership_proof(kitty_id: KittyIndex) -> Option<OwnershipProof<AccountId>>;
+
+        /// Name, description and banner URI of the kitty collection, if they have been set.
+        fn class_metadata() -> Option<CollectionMetadata>;
+
+        /// Up to `limit` listings as `(kitty, seller, price, denomination)` and up to `limit`
+        /// collection bids by id. Native listings come cheapest first, then reference priced
+        /// ones by kitty id; bids come best first, then oldest first.
+        fn order_book(limit: u32) -> (
+            Vec<(KittyIndex, AccountId, Balance, Denomination)>,
+            Vec<(u32, CollectionBid<AccountId, Balance>)>,
+        );
+
+        /// Proceeds, cost basis and purchases of `account`'s kitty trades in tax era `era`, the
+        /// eras being `TaxEraLength` blocks long. Zero once the era is older than `TaxHistory`.
+        fn tax_report(account: AccountId, era: u32) -> TaxReport<Balance>;
+    }
+}
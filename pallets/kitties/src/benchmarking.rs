@@ -1,24 +1,176 @@
-//! Benchmarking setup for pallet-template
+//! Benchmarking setup for pallet-kitties
 
 use super::*;
 
 #[allow(unused)]
-use crate::Pallet as Template;
+use crate::Pallet as Kitties;
 
-use frame_benchmarking::{benchmarks, impl_benchmark_test_suite, whitelisted_caller};
+use frame_benchmarking::{account, benchmarks, impl_benchmark_test_suite, whitelisted_caller};
+use frame_support::traits::{Currency, EnsureOrigin, Get, Hooks};
 use frame_system::RawOrigin;
+use sp_runtime::traits::Bounded;
 use sp_std::{boxed::Box, vec, vec::Vec};
 
-#[allow(unused)]
-use crate::Kitty;
+const SEED: u32 = 0;
+
+/// Most pending sales expiring in one block that `on_initialize` is benchmarked with. Nothing
+/// bounds them on chain, so this should stay above what a block of purchases can create.
+const MAX_EXPIRIES: u32 = 100;
+
+/// `whitelisted_caller` with plenty of funds.
+fn funded_caller<T: Config>() -> T::AccountId {
+    let caller = whitelisted_caller();
+    T::Currency::make_free_balance_be(&caller, BalanceOf::<T>::max_value() / 2u32.into());
+    caller
+}
+
+/// A named account with plenty of funds.
+fn funded_account<T: Config>(name: &'static str, index: u32) -> T::AccountId {
+    let who = account(name, index, SEED);
+    T::Currency::make_free_balance_be(&who, BalanceOf::<T>::max_value() / 4u32.into());
+    who
+}
+
+/// The id of a fresh kitty minted to `owner`. Each one is minted as the next extrinsic, so
+/// many kitties can be minted in one block without their dna colliding.
+fn new_kitty<T: Config>(owner: &T::AccountId) -> KittyIndexOf<T> {
+    let index = frame_system::Module::<T>::extrinsic_index().unwrap_or_default();
+    frame_system::Module::<T>::set_extrinsic_index(index + 1);
+    Kitties::<T>::create_kitty(RawOrigin::Signed(owner.clone()).into())
+        .expect("Creating a kitty works");
+    Kitties::<T>::last_minted_by(owner).expect("Kitty was just minted")
+}
+
+/// The id of a fresh kitty minted to `seller` and listed at `price`.
+fn listed_kitty<T: Config>(seller: &T::AccountId, price: BalanceOf<T>) -> KittyIndexOf<T> {
+    let kitty_id = new_kitty::<T>(seller);
+    Kitties::<T>::set_price(
+        RawOrigin::Signed(seller.clone()).into(),
+        kitty_id,
+        Some(price),
+    )
+    .expect("Listing a fresh kitty works");
+    kitty_id
+}
+
+/// Open `count` quests on `goal` that never end, so every action counting towards `goal`
+/// reads and writes all of them.
+fn open_quests<T: Config>(count: u32, goal: QuestGoal) -> Result<(), &'static str> {
+    for _ in 0..count {
+        Kitties::<T>::create_quest(
+            T::ForceOrigin::successful_origin(),
+            goal,
+            1,
+            0u32.into(),
+            T::BlockNumber::max_value(),
+        )?;
+    }
+    Ok(())
+}
 
 benchmarks! {
     create_kitty {
-        let caller: T::AccountId = whitelisted_caller();
-    }: _(RawOrigin::Signed(caller))
+        let caller = funded_caller::<T>();
+    }: _(RawOrigin::Signed(caller.clone()))
+    verify {
+        assert!(Kitties::<T>::last_minted_by(&caller).is_some());
+    }
+
+    transfer_kitty {
+        let caller = funded_caller::<T>();
+        let kitty_id = new_kitty::<T>(&caller);
+        let recipient: T::AccountId = account("recipient", 0, SEED);
+    }: _(RawOrigin::Signed(caller), recipient.clone(), kitty_id)
+    verify {
+        assert_eq!(Kitties::<T>::kitty_of(kitty_id).map(|(owner, _)| owner), Some(recipient));
+    }
+
+    set_price {
+        let caller = funded_caller::<T>();
+        let kitty_id = new_kitty::<T>(&caller);
+        let price = BalanceOf::<T>::from(100u32);
+    }: _(RawOrigin::Signed(caller), kitty_id, Some(price))
+    verify {
+        assert!(Kitties::<T>::kitty_exchange(kitty_id).is_some());
+    }
+
+    buy_kitty {
+        let q in 0 .. T::MaxQuests::get();
+        open_quests::<T>(q, QuestGoal::Buy)?;
+        let seller = funded_account::<T>("seller", 0);
+        let price = T::Currency::minimum_balance() * 100u32.into();
+        let kitty_id = listed_kitty::<T>(&seller, price);
+        let caller = funded_caller::<T>();
+    }: _(RawOrigin::Signed(caller.clone()), kitty_id, None)
+    verify {
+        assert_eq!(Kitties::<T>::kitty_of(kitty_id).map(|(owner, _)| owner), Some(caller));
+    }
+
+    buy_floor {
+        let c in 1 .. T::MaxFloorBuy::get();
+        open_quests::<T>(T::MaxQuests::get(), QuestGoal::Buy)?;
+        let caller = funded_caller::<T>();
+        let seller = funded_account::<T>("seller", 0);
+        let price = T::Currency::minimum_balance() * 100u32.into();
+        // The caller's own listings are the cheapest, so the sweep skips `c` of them first
+        for _ in 0..c {
+            listed_kitty::<T>(&caller, price);
+            listed_kitty::<T>(&seller, price * 2u32.into());
+        }
+    }: _(RawOrigin::Signed(caller.clone()), c, BalanceOf::<T>::max_value())
+    verify {
+        assert_eq!(OwnerListings::<T>::iter_prefix(&seller).count(), 0);
+        assert_eq!(OwnerListings::<T>::iter_prefix(&caller).count() as u32, c);
+    }
+
+    on_initialize {
+        let e in 0 .. MAX_EXPIRIES;
+        let s in 0 .. T::MaxScheduledPerBlock::get();
+        let seller = funded_account::<T>("seller", 0);
+        let price = T::Currency::minimum_balance() * 100u32.into();
+        Kitties::<T>::set_high_value_threshold(
+            T::ForceOrigin::successful_origin(),
+            Some(T::Currency::minimum_balance()),
+        )?;
+        let n = frame_system::Module::<T>::block_number() + T::ConfirmationPeriod::get();
+
+        // Every purchase is held for confirmation and reverted in block `n`
+        for i in 0..e {
+            let kitty_id = listed_kitty::<T>(&seller, price);
+            let buyer = funded_account::<T>("buyer", i);
+            Kitties::<T>::buy_kitty(RawOrigin::Signed(buyer).into(), kitty_id, None)?;
+        }
+        for _ in 0..s {
+            let kitty_id = new_kitty::<T>(&seller);
+            Kitties::<T>::schedule_listing(
+                RawOrigin::Signed(seller.clone()).into(),
+                kitty_id,
+                price,
+                n,
+            )?;
+        }
+    }: {
+        Kitties::<T>::on_initialize(n);
+    }
+    verify {
+        assert_eq!(PendingSales::<T>::iter().count(), 0);
+        assert_eq!(OwnerListings::<T>::iter_prefix(&seller).count() as u32, s);
+    }
+
+    delist_all {
+        let caller = funded_caller::<T>();
+        for _ in 0..T::MaxDelistAll::get() {
+            let kitty_id = new_kitty::<T>(&caller);
+            Kitties::<T>::set_price(
+                RawOrigin::Signed(caller.clone()).into(),
+                kitty_id,
+                Some(100u32.into()),
+            )?;
+        }
+    }: _(RawOrigin::Signed(caller.clone()))
     verify {
-        assert_eq!(true, true);
+        assert_eq!(OwnerListings::<T>::iter_prefix(&caller).count(), 0);
     }
 }
 
-impl_benchmark_test_suite!(Template, crate::mock::new_test_ext(), crate::mock::Test,);
+impl_benchmark_test_suite!(Kitties, crate::mock::new_test_ext(), crate::mock::Test,);
@@ -9,33 +9,369 @@ mod tests;
 #[cfg(feature = "runtime-benchmarks")]
 mod benchmarking;
 
+pub mod merkle;
+pub mod migrations;
+
+#[cfg(feature = "render")]
+pub mod render;
+
 pub use pallet::*;
 
+// `#[pallet::event]` expands to a `From<Event<T>> for ()` impl returning `-> () { () }`
+#[allow(clippy::unused_unit)]
 #[frame_support::pallet]
 pub mod pallet {
     use frame_support::{
-        dispatch::DispatchResultWithPostInfo,
+        dispatch::{DispatchResult, DispatchResultWithPostInfo},
+        error::{DecodeDifferent, ModuleErrorMetadata},
         pallet_prelude::*,
-        traits::{Currency, ExistenceRequirement, Randomness},
+        traits::{
+            BalanceStatus, Currency, ExistenceRequirement, OnUnbalanced, Randomness,
+            ReservableCurrency, WithdrawReasons,
+        },
     };
     use frame_system::pallet_prelude::*;
     use orml_utilities::with_transaction_result;
     #[cfg(feature = "std")]
     use serde::{Deserialize, Serialize};
     use sp_core::H256;
-    use sp_io::hashing::blake2_128;
+    use sp_io::hashing::{blake2_128, blake2_256};
+    use sp_runtime::{
+        offchain::{http, storage::StorageValueRef, Duration},
+        traits::{
+            AccountIdConversion, CheckedDiv, Convert, Hash, IdentifyAccount, One, Saturating,
+            UniqueSaturatedInto, Verify, Zero,
+        },
+        ModuleId, Permill,
+    };
+    use sp_std::{collections::btree_set::BTreeSet, convert::TryInto, vec, vec::Vec};
 
     use orml_nft::Pallet as NftModule;
 
+    /// Source of the exchange rate between a reference unit (e.g. USD) and the native currency.
+    pub trait PriceOracle<Balance, BlockNumber> {
+        /// Native balance per reference unit, and the block the rate was last updated at.
+        fn latest_rate() -> Option<(Balance, BlockNumber)>;
+    }
+
+    impl<Balance, BlockNumber> PriceOracle<Balance, BlockNumber> for () {
+        fn latest_rate() -> Option<(Balance, BlockNumber)> {
+            None
+        }
+    }
+
+    /// Two letter code of the jurisdiction an account is taxed in, e.g. `*b"DE"`.
+    pub type JurisdictionTag = [u8; 2];
+
+    /// Tax charged on kitty sales.
+    pub trait TaxHandler<AccountId, Balance> {
+        /// The tax on a sale at `price` between accounts tagged with the given jurisdictions,
+        /// and the account it is paid to, or `None` if the sale is not taxed.
+        fn sales_tax(
+            seller: Option<JurisdictionTag>,
+            buyer: Option<JurisdictionTag>,
+            price: Balance,
+        ) -> Option<(AccountId, Balance)>;
+    }
+
+    impl<AccountId, Balance> TaxHandler<AccountId, Balance> for () {
+        fn sales_tax(
+            _seller: Option<JurisdictionTag>,
+            _buyer: Option<JurisdictionTag>,
+            _price: Balance,
+        ) -> Option<(AccountId, Balance)> {
+            None
+        }
+    }
+
+    /// Genetics engine deciding the dna of new kitties.
+    pub trait DnaProvider {
+        /// Dna of `length` bytes for a kitty created from scratch, derived from `seed`.
+        fn random_dna(seed: &[u8], length: usize) -> Vec<u8>;
+
+        /// Dna of `length` bytes for the offspring of parents with dna `first` and `second`,
+        /// derived from `seed`.
+        fn offspring_dna(first: &[u8], second: &[u8], seed: &[u8], length: usize) -> Vec<u8>;
+    }
+
+    /// Dna made of chained blake2 hashes of the seed, and of the parents' dna when breeding.
+    pub struct Blake2Dna;
+
+    impl DnaProvider for Blake2Dna {
+        fn random_dna(seed: &[u8], length: usize) -> Vec<u8> {
+            let mut dna = Vec::with_capacity(length);
+            let mut chunk = blake2_128(seed);
+            while dna.len() < length {
+                dna.extend_from_slice(&chunk);
+                chunk = blake2_128(&chunk);
+            }
+            dna.truncate(length);
+            dna
+        }
+
+        fn offspring_dna(first: &[u8], second: &[u8], seed: &[u8], length: usize) -> Vec<u8> {
+            let mut payload = (first, second).encode();
+            payload.extend_from_slice(seed);
+            Self::random_dna(&payload, length)
+        }
+    }
+
     /// Configure the pallet by specifying the parameters and types on which it depends.
     #[pallet::config]
     pub trait Config:
         orml_nft::Config<TokenData = Kitty, ClassData = ()> + frame_system::Config
     {
         /// Because this pallet emits events, it depends on the runtime's definition of an event.
-        type Event: From<Event<Self>> + IsType<<Self as frame_system::Config>::Event>;
+        type Event: From<Event<Self>>
+            + IsType<<Self as frame_system::Config>::Event>
+            + TryInto<Event<Self>>;
         type RandomnessSource: Randomness<H256>;
-        type Currency: Currency<Self::AccountId>;
+        /// Genetics engine deciding the dna of created and bred kitties.
+        type DnaProvider: DnaProvider;
+        type Currency: ReservableCurrency<Self::AccountId>;
+
+        /// The pallet's id, used for deriving the account that collects marketplace fees.
+        #[pallet::constant]
+        type ModuleId: Get<ModuleId>;
+
+        /// Share of each sale price taken as marketplace fee.
+        #[pallet::constant]
+        type MarketplaceFee: Get<Permill>;
+
+        /// Marketplace fee on reselling a kitty in the block it was bought. It decays linearly
+        /// to `MarketplaceFee` over `FlipFeeDecay` blocks.
+        #[pallet::constant]
+        type FlipFee: Get<Permill>;
+
+        /// Number of blocks after a purchase until a resale pays the plain `MarketplaceFee`.
+        #[pallet::constant]
+        type FlipFeeDecay: Get<Self::BlockNumber>;
+
+        /// Share of the marketplace fee paid out to the referrer of a purchase.
+        #[pallet::constant]
+        type ReferralShare: Get<Permill>;
+
+        /// Fee charged for breeding, given the highest generation among the parents.
+        type BreedingFee: Convert<u32, BalanceOf<Self>>;
+
+        /// Price of creating a kitty, unless governance has set another with `set_mint_price`.
+        #[pallet::constant]
+        type MintPrice: Get<BalanceOf<Self>>;
+
+        /// Where mint payments go.
+        type MintPriceDestination: OnUnbalanced<NegativeImbalanceOf<Self>>;
+
+        /// Fee for adopting a kitty from the shelter.
+        #[pallet::constant]
+        type AdoptionFee: Get<BalanceOf<Self>>;
+
+        /// Where adoption fees go.
+        type AdoptionFeeDestination: OnUnbalanced<NegativeImbalanceOf<Self>>;
+
+        /// Fee for cloning an original kitty. It doubles with every clone of a clone.
+        #[pallet::constant]
+        type CloneFee: Get<BalanceOf<Self>>;
+
+        /// Highest generation that may be bred, if any.
+        #[pallet::constant]
+        type MaxGeneration: Get<Option<u32>>;
+
+        /// Chance that a breeding produces twins.
+        #[pallet::constant]
+        type TwinProbability: Get<Permill>;
+
+        /// Whether breeding between parents, children and full siblings is refused.
+        #[pallet::constant]
+        type ForbidIncest: Get<bool>;
+
+        /// Whether `create_kitty` favours whichever gender is behind among recent mints.
+        #[pallet::constant]
+        type BalanceMintGenders: Get<bool>;
+
+        /// Exchange rate used for listings priced in the reference unit.
+        type PriceOracle: PriceOracle<BalanceOf<Self>, Self::BlockNumber>;
+
+        /// Oldest oracle rate, in blocks, that purchases will accept.
+        #[pallet::constant]
+        type MaxOracleAge: Get<Self::BlockNumber>;
+
+        /// Number of blocks a bought kitty must wait before it can be listed again.
+        #[pallet::constant]
+        type ResaleCooldown: Get<Self::BlockNumber>;
+
+        /// Number of blocks a seller has to confirm a sale above the high value threshold
+        /// before it is reverted.
+        #[pallet::constant]
+        type ConfirmationPeriod: Get<Self::BlockNumber>;
+
+        /// Number of blocks a layaway buyer has to make each installment.
+        #[pallet::constant]
+        type InstallmentPeriod: Get<Self::BlockNumber>;
+
+        /// Most installments a layaway purchase can be split into.
+        #[pallet::constant]
+        type MaxInstallments: Get<u32>;
+
+        /// Part of the price a layaway buyer forfeits to the seller on a missed installment,
+        /// at most what they have paid.
+        #[pallet::constant]
+        type LayawayPenalty: Get<Permill>;
+
+        /// Trades between two accounts in both directions within this many blocks are flagged as
+        /// possible wash trading.
+        #[pallet::constant]
+        type WashTradeWindow: Get<Self::BlockNumber>;
+
+        /// Signature over an encoded `KittyExport`, checked against the import authority.
+        type ImportSignature: Verify<Signer = Self::ImportSigner> + Parameter;
+        /// Key type that produces `ImportSignature`s.
+        type ImportSigner: IdentifyAccount<AccountId = Self::AccountId>;
+
+        /// Signature over the `trade_payload` of `TradeTerms`, checked against the counterparty.
+        type TradeSignature: Verify<Signer = Self::TradeSigner> + Parameter;
+        /// Key type that produces `TradeSignature`s.
+        type TradeSigner: IdentifyAccount<AccountId = Self::AccountId>;
+
+        /// Number of dna bytes generated for new kitties, and the most an imported genome may
+        /// carry. Only the first 256 bytes are indexed as traits.
+        #[pallet::constant]
+        type DnaLength: Get<u32>;
+
+        /// Every this many kitties minted, the minter is rewarded, if any.
+        #[pallet::constant]
+        type MilestoneInterval: Get<Option<u64>>;
+
+        /// Amount paid from the pallet account to the minter of a milestone kitty.
+        #[pallet::constant]
+        type MilestoneReward: Get<BalanceOf<Self>>;
+
+        /// Most quests that can exist at once.
+        #[pallet::constant]
+        type MaxQuests: Get<u32>;
+
+        /// Most accounts' progress a single `remove_quest` call deletes.
+        #[pallet::constant]
+        type MaxQuestProgressRemovals: Get<u32>;
+
+        /// Most kitties that can enter a single race.
+        #[pallet::constant]
+        type MaxRaceEntrants: Get<u32>;
+
+        /// Length in blocks of the periods that spending limits apply to.
+        #[pallet::constant]
+        type SpendingPeriod: Get<Self::BlockNumber>;
+
+        /// Length in blocks of the periods sale prices are aggregated over.
+        #[pallet::constant]
+        type StatsPeriod: Get<Self::BlockNumber>;
+
+        /// Number of most recent periods whose price statistics are kept.
+        #[pallet::constant]
+        type StatsHistory: Get<u32>;
+
+        /// Number of most recent trades kept in each account's trade history.
+        #[pallet::constant]
+        type TradeHistoryLength: Get<u32>;
+
+        /// Most kitties `buy_floor` buys in one call.
+        #[pallet::constant]
+        type MaxFloorBuy: Get<u32>;
+
+        /// Most listings and delistings that can be scheduled for the same block.
+        #[pallet::constant]
+        type MaxScheduledPerBlock: Get<u32>;
+
+        /// Most listings `delist_all` removes in one call.
+        #[pallet::constant]
+        type MaxDelistAll: Get<u32>;
+
+        /// Most kitties that can be created, bred, cloned or imported in one block.
+        #[pallet::constant]
+        type MaxMintsPerBlock: Get<u32>;
+
+        /// Most kitties `claim_inheritance` moves to the heir in one call.
+        #[pallet::constant]
+        type MaxInheritedKitties: Get<u32>;
+
+        /// Most kitties a single community grant can mint.
+        #[pallet::constant]
+        type MaxGrantKitties: Get<u32>;
+
+        /// Blocks between snapshots of the merkle root of kitty owners, or zero for none.
+        #[pallet::constant]
+        type OwnerSnapshotPeriod: Get<Self::BlockNumber>;
+
+        /// Most kitties an owner snapshot reads per block.
+        #[pallet::constant]
+        type MaxSnapshotKittiesPerBlock: Get<u32>;
+
+        /// Age in blocks a kitty needs for each evolution stage: stage `n` is reached at `n`
+        /// times this age.
+        #[pallet::constant]
+        type EvolutionInterval: Get<Self::BlockNumber>;
+
+        /// Blocks a listing's price must stand before it can be changed. Delisting is always
+        /// allowed.
+        #[pallet::constant]
+        type MinListingStability: Get<Self::BlockNumber>;
+
+        /// Longest name, description or banner URI the collection metadata can have, in bytes.
+        #[pallet::constant]
+        type MaxMetadataLength: Get<u32>;
+
+        /// Blocks a buyer has to return a kitty bought from a listing that accepts returns.
+        #[pallet::constant]
+        type ReturnPeriod: Get<Self::BlockNumber>;
+
+        /// Part of the price a seller keeps when a kitty is returned, on top of the fees.
+        #[pallet::constant]
+        type RestockingFee: Get<Permill>;
+
+        /// Most kitties an airdrop pays the owners of per block.
+        #[pallet::constant]
+        type MaxAirdropsPerBlock: Get<u32>;
+
+        /// Most `ForceOrigin` actions kept in the audit log. The oldest are dropped first.
+        #[pallet::constant]
+        type MaxAuditLogLength: Get<u32>;
+
+        /// Length in blocks of the eras sales are reported in for tax purposes.
+        #[pallet::constant]
+        type TaxEraLength: Get<Self::BlockNumber>;
+
+        /// Number of eras tax reports are kept for, the current one included.
+        #[pallet::constant]
+        type TaxHistory: Get<u32>;
+
+        /// Most tax reports of eras past `TaxHistory` removed per block.
+        #[pallet::constant]
+        type MaxTaxPrunesPerBlock: Get<u32>;
+
+        /// Most breeding seasons that can be scheduled at once.
+        #[pallet::constant]
+        type MaxBreedingSeasons: Get<u32>;
+
+        /// Generations of ancestors covered by pedigree certificates.
+        #[pallet::constant]
+        type PedigreeDepth: Get<u32>;
+
+        /// Whether buying a kitty may spend the buyer's whole balance and reap their account.
+        type BuyerExistenceRequirement: Get<ExistenceRequirement>;
+
+        /// Tax taken out of the seller's proceeds on every sale.
+        type TaxHandler: TaxHandler<Self::AccountId, BalanceOf<Self>>;
+
+        /// Share of the pallet's cut of marketplace fees rebated to the buyer who paid it.
+        #[pallet::constant]
+        type FeeRebate: Get<Permill>;
+
+        /// Length in blocks of the periods fee rebates vest over. Rebates can be claimed once
+        /// the period they were earned in has ended.
+        #[pallet::constant]
+        type RebatePeriod: Get<Self::BlockNumber>;
+
+        /// Origin allowed to perform administrative actions on the pallet.
+        type ForceOrigin: EnsureOrigin<Self::Origin>;
     }
 
     #[pallet::genesis_config]
@@ -49,6 +385,12 @@ pub mod pallet {
             let class_id = NftModule::<T>::create_class(&Default::default(), Vec::new(), ())
                 .expect("Cannot fail or invalid chain spec");
             ClassId::<T>::put(class_id);
+            StorageVersion::<T>::put(Releases::V9);
+
+            // Create the fee accounts so that fees below the existential deposit can be collected
+            for account in [Pallet::<T>::account_id(), Pallet::<T>::rebate_account_id()].iter() {
+                let _ = T::Currency::make_free_balance_be(account, T::Currency::minimum_balance());
+            }
         }
     }
 
@@ -56,284 +398,5810 @@ pub mod pallet {
     #[pallet::generate_store(pub(super) trait Store)]
     pub struct Pallet<T>(_);
 
-    type BalanceOf<T> =
+    pub(crate) type BalanceOf<T> =
         <<T as Config>::Currency as Currency<<T as frame_system::Config>::AccountId>>::Balance;
-    type KittyIndexOf<T> = <T as orml_nft::Config>::TokenId;
+    pub(crate) type KittyIndexOf<T> = <T as orml_nft::Config>::TokenId;
+    type NegativeImbalanceOf<T> = <<T as Config>::Currency as Currency<
+        <T as frame_system::Config>::AccountId,
+    >>::NegativeImbalance;
 
     #[derive(Encode, Decode, Clone, PartialEq, Debug)]
-    pub struct Listing<T: Config>(T::AccountId, BalanceOf<T>);
-
-    #[pallet::storage]
-    #[pallet::getter(fn kitty_exchange)]
-    pub(super) type KittyExchange<T: Config> =
-        StorageMap<_, Blake2_128Concat, KittyIndexOf<T>, Option<Listing<T>>, ValueQuery>;
+    pub struct Listing<T: Config>(
+        pub(crate) T::AccountId,
+        pub(crate) BalanceOf<T>,
+        pub(crate) Denomination,
+    );
 
-    #[pallet::storage]
-    #[pallet::getter(fn class_id)]
-    pub(super) type ClassId<T: Config> = StorageValue<_, T::ClassId, ValueQuery>;
+    /// Everything needed to recreate an exported kitty on another chain.
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+    pub struct KittyExport<AccountId, KittyIndex> {
+        /// Account the kitty is minted to on import.
+        pub owner: AccountId,
+        pub dna: Kitty,
+        /// Parents on the exporting chain, for the record only.
+        pub parents: Option<(KittyIndex, KittyIndex)>,
+        pub generation: u32,
+        /// Export counter of the exporting chain.
+        pub nonce: u64,
+    }
 
-    #[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
-    #[derive(Encode, Decode, Clone, PartialEq, Debug, Eq, Copy)]
-    pub struct Kitty(pub [u8; 16]);
+    type KittyExportOf<T> = KittyExport<<T as frame_system::Config>::AccountId, KittyIndexOf<T>>;
 
-    #[derive(Encode, Decode, Clone, PartialEq, Debug)]
-    pub enum Gender {
-        Male,
-        Female,
+    /// A direct trade agreed off-chain, signed by `counterparty` and submitted by `submitter`.
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+    pub struct TradeTerms<AccountId, KittyIndex, Balance, BlockNumber> {
+        pub submitter: AccountId,
+        pub counterparty: AccountId,
+        /// Kitties the submitter gives the counterparty.
+        pub give: Vec<KittyIndex>,
+        /// Kitties the counterparty gives the submitter.
+        pub take: Vec<KittyIndex>,
+        pub submitter_pays: Balance,
+        pub counterparty_pays: Balance,
+        /// Last block the trade can be executed in.
+        pub expires: BlockNumber,
+        /// `TradeNonces` entry of the counterparty when it signed. Executing any trade of the
+        /// counterparty moves it on, so each signature is used at most once.
+        pub nonce: u32,
     }
 
-    impl Kitty {
-        fn new<T: Config>(owner: T::AccountId) -> Result<Kitty, Error<T>> {
-            // Collect sources for random hash
-            let payload = (
-                owner.clone(),
-                T::RandomnessSource::random(&owner.encode()[..]),
-                frame_system::Module::<T>::extrinsic_index(),
-            );
+    type TradeTermsOf<T> = TradeTerms<
+        <T as frame_system::Config>::AccountId,
+        KittyIndexOf<T>,
+        BalanceOf<T>,
+        <T as frame_system::Config>::BlockNumber,
+    >;
 
-            // Generate random dna source
-            let dna = payload.using_encoded(blake2_128);
+    /// What a coupon takes off a purchase.
+    #[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, Debug)]
+    pub enum CouponKind {
+        /// Refunds a share of the marketplace fee from the pallet account.
+        FeeDiscount,
+        /// Refunds a share of the price from the promo pot.
+        PriceSubsidy,
+    }
 
-            return Ok(Kitty(dna));
-        }
+    /// A one-time discount issued by the admin.
+    #[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, Debug)]
+    pub struct Coupon {
+        pub kind: CouponKind,
+        pub discount: Permill,
+    }
 
-        fn get_gender_from_dna(dna: [u8; 16]) -> Gender {
-            let total = dna.iter().max();
-            match total {
-                Some(total) => {
-                    if total % 2 == 0 {
-                        Gender::Male
-                    } else {
-                        Gender::Female
-                    }
-                }
-                None => Gender::Male,
-            }
-        }
+    /// A proposal that the pallet account pays to mint `count` promotional kitties for
+    /// `beneficiary`.
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+    pub struct GrantProposal<AccountId> {
+        pub proposer: AccountId,
+        pub beneficiary: AccountId,
+        pub count: u32,
+    }
 
-        fn ensure_different_kitty<T: Config>(
-            first: &Kitty,
-            second: &Kitty,
-        ) -> Result<(), Error<T>> {
-            match *first != *second {
-                true => Ok(()),
-                false => Err(Error::<T>::KittyPartnerMissing),
-            }
-        }
+    /// Kind of action a quest counts.
+    #[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, Debug)]
+    pub enum QuestGoal {
+        Create,
+        Breed,
+        Buy,
+        Sell,
+    }
 
-        fn ensure_different_gender<T: Config>(
-            first: &Kitty,
-            second: &Kitty,
-        ) -> Result<(), Error<T>> {
-            match first.gender() != second.gender() {
-                true => Ok(()),
-                false => Err(Error::<T>::KittyGendersNotCompatible),
-            }
-        }
+    /// A task set by the admin: perform `goal` `target` times before `ends_at` to claim `reward`.
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+    pub struct Quest<BlockNumber, Balance> {
+        pub goal: QuestGoal,
+        pub target: u32,
+        /// Paid from the pallet account on claim, next to the quest badge.
+        pub reward: Balance,
+        pub ends_at: BlockNumber,
+    }
 
-        pub fn gender(&self) -> Gender {
-            Kitty::get_gender_from_dna(self.0)
-        }
+    type QuestOf<T> = Quest<<T as frame_system::Config>::BlockNumber, BalanceOf<T>>;
 
-        fn breed<T: Config>(first: Kitty, second: Kitty) -> Result<Kitty, Error<T>> {
-            // Ensure parents are not the same
-            Kitty::ensure_different_kitty(&first, &second)?;
-            // Ensure parents have opposite genders
-            Kitty::ensure_different_gender(&first, &second)?;
-            // Combine parent DNAs as seed
-            let payload = (
-                first.0,
-                second.0,
-                T::RandomnessSource::random_seed(),
-                frame_system::Module::<T>::extrinsic_index(),
-            );
+    /// A race opened by the admin. Owners pay `entry_fee` per kitty into the prize pool, which
+    /// the `winners` fastest kitties split once the race closes.
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+    pub struct Race<AccountId, KittyIndex, Balance, BlockNumber> {
+        pub entry_fee: Balance,
+        pub closes_at: BlockNumber,
+        pub winners: u32,
+        /// Entered kitties and the accounts that entered them, in entry order.
+        pub entrants: Vec<(KittyIndex, AccountId)>,
+    }
 
-            // Generate dna
-            let dna = payload.using_encoded(blake2_128);
+    /// A purchase above the high value threshold waiting for the seller's confirmation. The
+    /// kitty is escrowed and the price stays reserved with the buyer until then.
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+    pub struct PendingSale<AccountId, KittyIndex, Balance, BlockNumber> {
+        pub kitty: KittyIndex,
+        pub seller: AccountId,
+        pub buyer: AccountId,
+        pub price: Balance,
+        pub referrer: Option<AccountId>,
+        pub expires_at: BlockNumber,
+    }
 
-            Ok(Kitty(dna))
-        }
+    /// A purchase paid in installments. The kitty is escrowed until the last one, while the
+    /// installments paid so far stay reserved with the buyer.
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+    pub struct Layaway<AccountId, KittyIndex, Balance, BlockNumber> {
+        pub kitty: KittyIndex,
+        pub seller: AccountId,
+        pub buyer: AccountId,
+        pub price: Balance,
+        pub installment: Balance,
+        pub payments_left: u32,
+        pub due: BlockNumber,
     }
 
-    // Pallets use events to inform users when important changes are made.
-    // https://substrate.dev/docs/en/knowledgebase/runtime/events
-    #[pallet::event]
-    #[pallet::metadata(T::AccountId = "AccountId")]
-    #[pallet::generate_deposit(pub(super) fn deposit_event)]
-    pub enum Event<T: Config> {
-        /// A Kitty has been generated for the owner with random dna.
-        /// [kitty, owner]
-        KittyCreated(Kitty, KittyIndexOf<T>, T::AccountId),
-        /// A Kitty has been bred.
-        /// [kitty, owner]
-        KittyBred(Kitty, KittyIndexOf<T>, T::AccountId),
-        /// A Kitty has been transfered.
-        /// [kitty, from, to]
-        KittyTransfer(KittyIndexOf<T>, T::AccountId, T::AccountId),
-        /// A Kitty has been sold.
-        /// [kitty, price, seller, buyer]
-        KittySold(KittyIndexOf<T>, BalanceOf<T>, T::AccountId, T::AccountId),
-        /// A Kitty's price has been updated
-        /// [kitty, price, owner]
-        KittyPriceUpdated(KittyIndexOf<T>, Option<BalanceOf<T>>, T::AccountId),
+    /// A sale the buyer can still undo with `return_kitty`. The refund stays reserved with the
+    /// seller until then.
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+    pub struct ReturnableSale<AccountId, KittyIndex, Balance, BlockNumber> {
+        pub kitty: KittyIndex,
+        pub seller: AccountId,
+        pub buyer: AccountId,
+        pub refund: Balance,
+        pub until: BlockNumber,
+        /// Tax era the sale was reported in.
+        pub era: BlockNumber,
+        /// What the sale added to the buyer's purchases and the seller's proceeds.
+        pub price: Balance,
+        pub proceeds: Balance,
+        /// Cost basis of the kitty before the sale, which a return restores.
+        pub cost_basis: Balance,
     }
 
-    // Errors inform users that something went wrong.
-    #[pallet::error]
-    pub enum Error<T> {
-        /// Error names should be descriptive.
-        NoneValue,
-        /// Errors should have helpful documentation associated with them.
-        StorageOverflow,
-        /// Kitty was generated with a DNA that already exists
-        DuplicateKitty,
-        /// Kitty not found
-        KittyNotFound,
-        /// Kitties need a valid (different from self) partner to breed
-        KittyPartnerMissing,
-        /// Kitties must have different genders to be able to breed
-        KittyGendersNotCompatible,
-        /// Kitty Id has overflowed KittyIndex
-        KittyIdOverflow,
-        /// Kitty is not listed on the exchange
-        KittyNotForSale,
-        /// Cannot buy own kitty
-        CannotBuyOwnKitty,
-        /// Could not create kitty
-        CouldNotCreateKitty,
+    /// An airdrop from the promo pot being paid out a few kitties per block, from kitty `next`
+    /// up to but not including `end`.
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+    pub struct Airdrop<KittyIndex, Balance> {
+        pub amount_per_kitty: Balance,
+        pub next: KittyIndex,
+        pub end: KittyIndex,
+        /// Kitties whose owners have been paid so far.
+        pub paid: u32,
     }
 
-    #[pallet::hooks]
-    impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {}
+    type AirdropOf<T> = Airdrop<KittyIndexOf<T>, BalanceOf<T>>;
 
-    impl<T: Config> Pallet<T> {
-        fn kitties(owner: &T::AccountId, kitty_id: KittyIndexOf<T>) -> Option<Kitty> {
-            NftModule::<T>::tokens(Self::class_id(), kitty_id).and_then(|x| {
-                if x.owner == *owner {
-                    Some(x.data)
-                } else {
-                    None
-                }
-            })
-        }
-    }
+    type ReturnableSaleOf<T> = ReturnableSale<
+        <T as frame_system::Config>::AccountId,
+        KittyIndexOf<T>,
+        BalanceOf<T>,
+        <T as frame_system::Config>::BlockNumber,
+    >;
 
-    // Dispatchable functions allows users to interact with the pallet and invoke state changes.
-    // These functions materialize as "extrinsics", which are often compared to transactions.
-    // Dispatchable functions must be annotated with a weight and must return a DispatchResult.
-    #[pallet::call]
-    impl<T: Config> Pallet<T> {
-        /// An example dispatchable that takes a singles value as a parameter, writes the value to
-        /// storage and emits an event. This function must be dispatched by a signed extrinsic.
-        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(1,2))]
-        pub fn create_kitty(origin: OriginFor<T>) -> DispatchResultWithPostInfo {
-            // Check that the extrinsic was signed and get the signer.
-            // This function will return an error if the extrinsic is not signed.
-            // https://substrate.dev/docs/en/knowledgebase/runtime/origin
-            let who = ensure_signed(origin)?;
-            let who_backup = who.clone();
-            // Insert the created kitty into storage
+    type LayawayOf<T> = Layaway<
+        <T as frame_system::Config>::AccountId,
+        KittyIndexOf<T>,
+        BalanceOf<T>,
+        <T as frame_system::Config>::BlockNumber,
+    >;
 
-            let kitty = Kitty::new::<T>(who_backup)?;
-            let current_id =
-                NftModule::<T>::mint(&who, Self::class_id(), Default::default(), kitty.clone())?;
+    type PendingSaleOf<T> = PendingSale<
+        <T as frame_system::Config>::AccountId,
+        KittyIndexOf<T>,
+        BalanceOf<T>,
+        <T as frame_system::Config>::BlockNumber,
+    >;
 
-            // Emit an event.
-            Self::deposit_event(Event::KittyCreated(kitty, current_id, who));
-            // Return a successful DispatchResultWithPostInfo
-            Ok(().into())
+    type RaceOf<T> = Race<
+        <T as frame_system::Config>::AccountId,
+        KittyIndexOf<T>,
+        BalanceOf<T>,
+        <T as frame_system::Config>::BlockNumber,
+    >;
+
+    /// An account's record on the marketplace.
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, Debug, Default)]
+    pub struct Reputation {
+        /// Kitties sold, through listings or into collection bids.
+        pub sales: u32,
+        /// Collection bids withdrawn before they were filled.
+        pub cancelled_bids: u32,
+    }
+
+    impl Reputation {
+        /// Successful sales less withdrawn bids.
+        pub fn score(&self) -> u32 {
+            self.sales.saturating_sub(self.cancelled_bids)
         }
+    }
 
-        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(3,2))]
-        pub fn breed_kitty(
-            origin: OriginFor<T>,
-            first_parent: KittyIndexOf<T>,
-            second_parent: KittyIndexOf<T>,
-        ) -> DispatchResultWithPostInfo {
-            // Ensure signed origin
-            let who = ensure_signed(origin)?;
+    /// A standing offer to buy up to `quantity` kitties at `max_price` each, optionally only
+    /// kitties with the `(trait, value)` in `trait_filter`. The funds for every remaining kitty
+    /// stay reserved with the bidder.
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+    pub struct CollectionBid<AccountId, Balance> {
+        pub bidder: AccountId,
+        pub max_price: Balance,
+        pub trait_filter: Option<(u8, u8)>,
+        pub quantity: u32,
+    }
 
-            // Ensure that kitties exist
-            let first_parent_struct =
-                Self::kitties(&who, first_parent).ok_or_else(|| Error::<T>::KittyNotFound)?;
-            let second_parent_struct =
-                Self::kitties(&who, second_parent).ok_or_else(|| Error::<T>::KittyNotFound)?;
+    pub(crate) type CollectionBidOf<T> =
+        CollectionBid<<T as frame_system::Config>::AccountId, BalanceOf<T>>;
 
-            // Insert the created kitty into storage
-            let kitty = Kitty::breed::<T>(first_parent_struct, second_parent_struct)?;
-            let current_id =
-                NftModule::<T>::mint(&who, Self::class_id(), Default::default(), kitty.clone())?;
+    /// A non-transferable record that `holder` owned `kitty` with the pedigree hashed in
+    /// `lineage` at block `issued_at`.
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+    pub struct PedigreeCertificate<AccountId, KittyIndex, BlockNumber> {
+        pub holder: AccountId,
+        pub kitty: KittyIndex,
+        pub lineage: [u8; 32],
+        pub issued_at: BlockNumber,
+    }
 
-            // Emit an event.
-            Self::deposit_event(Event::KittyBred(kitty, current_id, who));
-            // Return a successful DispatchResultWithPostInfo
-            Ok(().into())
-        }
+    /// The right for `holder` to breed with `kitty` `uses_left` more times, for sale at `price`
+    /// if it is set. The license is void once `kitty` no longer belongs to its `grantor`.
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+    pub struct BreedingLicense<AccountId, KittyIndex, Balance> {
+        pub kitty: KittyIndex,
+        pub grantor: AccountId,
+        pub holder: AccountId,
+        pub uses_left: u32,
+        pub price: Option<Balance>,
+    }
 
-        /// An example dispatchable that may throw a custom error.
-        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(1,2))]
-        pub fn transfer_kitty(
-            origin: OriginFor<T>,
-            receiver: T::AccountId,
-            kitty_id: KittyIndexOf<T>,
-        ) -> DispatchResultWithPostInfo {
-            // Ensure signed origin
-            let who = ensure_signed(origin)?;
+    pub(crate) type BreedingLicenseOf<T> =
+        BreedingLicense<<T as frame_system::Config>::AccountId, KittyIndexOf<T>, BalanceOf<T>>;
 
-            NftModule::<T>::transfer(&who, &receiver, (Self::class_id(), kitty_id))?;
+    type PedigreeCertificateOf<T> = PedigreeCertificate<
+        <T as frame_system::Config>::AccountId,
+        KittyIndexOf<T>,
+        <T as frame_system::Config>::BlockNumber,
+    >;
 
-            if who != receiver {
-                KittyExchange::<T>::remove(kitty_id);
-                Self::deposit_event(Event::KittyTransfer(kitty_id, who, receiver));
-            }
-            Ok(().into())
-        }
+    /// Kitties with their ids and owners, and the id the next page starts from.
+    type KittiesPageOf<T> = (
+        Vec<(
+            KittyIndexOf<T>,
+            <T as frame_system::Config>::AccountId,
+            Kitty,
+        )>,
+        Option<KittyIndexOf<T>>,
+    );
 
-        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(1,2))]
-        pub fn set_price(
-            origin: OriginFor<T>,
-            kitty_id: KittyIndexOf<T>,
-            new_price: Option<BalanceOf<T>>,
-        ) -> DispatchResultWithPostInfo {
-            let who = ensure_signed(origin)?;
+    /// Listings as `(kitty, seller, price, denomination)` and collection bids by id.
+    type OrderBookOf<T> = (
+        Vec<(
+            KittyIndexOf<T>,
+            <T as frame_system::Config>::AccountId,
+            BalanceOf<T>,
+            Denomination,
+        )>,
+        Vec<(u32, CollectionBidOf<T>)>,
+    );
 
-            ensure!(
-                orml_nft::TokensByOwner::<T>::contains_key(&who, (Self::class_id(), kitty_id)),
-                Error::<T>::KittyNotFound
-            );
+    /// A scheduled price change as `(kitty, owner, new price)`; `None` delists the kitty.
+    type ScheduledPriceChangeOf<T> = (
+        KittyIndexOf<T>,
+        <T as frame_system::Config>::AccountId,
+        Option<BalanceOf<T>>,
+    );
+
+    /// Weight of recording a `ForceOrigin` action in the audit log.
+    fn audit_weight<T: Config>() -> Weight {
+        T::DbWeight::get().reads_writes(1, 1)
+    }
+
+    /// Worst case weight of counting an action towards every quest.
+    fn quest_weight<T: Config>() -> Weight {
+        T::DbWeight::get().reads_writes(T::MaxQuests::get().into(), T::MaxQuests::get().into())
+    }
+
+    /// An account's kitty sales and purchases over one `TaxEraLength` era.
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, Debug, Default)]
+    pub struct TaxReport<Balance> {
+        /// What the account received for the kitties it sold, after fees and sales tax.
+        pub proceeds: Balance,
+        /// What the account paid for the kitties it sold, or zero for kitties it did not buy.
+        pub cost_basis: Balance,
+        /// What the account paid for the kitties it bought.
+        pub purchases: Balance,
+    }
+
+    /// Sale price statistics over one `StatsPeriod`.
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+    pub struct PriceCandle<Balance> {
+        pub open: Balance,
+        pub high: Balance,
+        pub low: Balance,
+        pub close: Balance,
+        pub volume: Balance,
+        pub sales: u32,
+    }
+
+    /// A `ForceOrigin` action in the audit log. `action` is the index of the event the action
+    /// deposited, which the pallet metadata names, and `payload_hash` the blake2_256 hash of
+    /// that whole event.
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+    pub struct AuditEntry<BlockNumber> {
+        pub block: BlockNumber,
+        pub action: u8,
+        pub payload_hash: [u8; 32],
+    }
+
+    /// Name and branding of the kitty collection, for explorers to show.
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, Debug, Default)]
+    pub struct CollectionMetadata {
+        pub name: Vec<u8>,
+        pub description: Vec<u8>,
+        pub banner_uri: Vec<u8>,
+    }
+
+    /// Merkle root of every kitty and its owner, and the number of kitties under it.
+    #[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, Debug, Default)]
+    pub struct OwnerSnapshot {
+        pub root: [u8; 32],
+        pub kitties: u32,
+    }
+
+    /// An owner snapshot being taken a few kitties per block, from kitty `next` up to but not
+    /// including `end`.
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+    pub struct OwnerSnapshotProgress<KittyIndex, BlockNumber> {
+        pub block: BlockNumber,
+        pub next: KittyIndex,
+        pub end: KittyIndex,
+        /// Kitties read so far.
+        pub kitties: u32,
+        /// Heights and roots of the complete subtrees of the leaves so far, for `merkle::fold`.
+        pub peaks: Vec<(u32, [u8; 32])>,
+    }
+
+    type OwnerSnapshotProgressOf<T> =
+        OwnerSnapshotProgress<KittyIndexOf<T>, <T as frame_system::Config>::BlockNumber>;
+
+    /// Proof that `owner` held a kitty in an owner snapshot, for `merkle::verify` with its leaf
+    /// from `Pallet::owner_leaf`.
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+    pub struct OwnershipProof<AccountId> {
+        pub owner: AccountId,
+        pub index: u32,
+        pub kitties: u32,
+        pub siblings: Vec<[u8; 32]>,
+    }
+
+    /// A settled sale, as kept in the trade history of both parties.
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+    pub struct TradeReceipt<AccountId, KittyIndex, Balance, BlockNumber> {
+        pub kitty: KittyIndex,
+        pub seller: AccountId,
+        pub buyer: AccountId,
+        pub price: Balance,
+        pub block: BlockNumber,
+    }
+
+    type TradeReceiptOf<T> = TradeReceipt<
+        <T as frame_system::Config>::AccountId,
+        KittyIndexOf<T>,
+        BalanceOf<T>,
+        <T as frame_system::Config>::BlockNumber,
+    >;
+
+    /// Weight of buying a single kitty.
+    fn buy_weight<T: Config>() -> Weight {
+        10_000
+            + T::DbWeight::get().reads_writes(13, 18)
+            + 2 * quest_weight::<T>()
+            + trait_prices_weight::<T>()
+    }
+
+    /// Weight of folding a sale into the price averages of the traits of the kitty sold.
+    fn trait_prices_weight<T: Config>() -> Weight {
+        let traits = T::DnaLength::get() as u64;
+        T::DbWeight::get().reads_writes(1 + traits, traits)
+    }
+
+    /// Storage layout versions of the pallet.
+    #[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, Debug, Default)]
+    pub enum Releases {
+        /// Kitties hold a raw 16 byte dna.
+        #[default]
+        V1,
+        /// Kitties hold a versioned `Genome`.
+        V2,
+        /// Listings are indexed by seller in `OwnerListings` and by price in `PriceIndex`.
+        V3,
+        /// Kitties left in the storage of the legacy template pallet have been re-minted.
+        V4,
+        /// Kitties are indexed by dna in `DnaIndex`.
+        V5,
+        /// Listings are keyed by kitty class and kitty in `KittyExchange`.
+        V6,
+        /// Funds reserved for collection bids and pending sales are tracked in `MarketHolds`.
+        V7,
+        /// Breeding licenses record the owner of their kitty who granted them.
+        V8,
+        /// Returnable sales record the tax report entries and cost basis a return undoes.
+        V9,
+    }
+
+    /// Unit a listing price is expressed in.
+    #[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, Debug)]
+    pub enum Denomination {
+        /// The native currency.
+        Native,
+        /// The `PriceOracle` reference unit, converted at purchase time.
+        Reference,
+    }
+
+    /// Listings by kitty class and kitty.
+    #[pallet::storage]
+    pub(super) type KittyExchange<T: Config> = StorageDoubleMap<
+        _,
+        Twox64Concat,
+        T::ClassId,
+        Blake2_128Concat,
+        KittyIndexOf<T>,
+        Option<Listing<T>>,
+        ValueQuery,
+    >;
+
+    /// Listed kitties by seller.
+    #[pallet::storage]
+    pub(super) type OwnerListings<T: Config> = StorageDoubleMap<
+        _,
+        Blake2_128Concat,
+        T::AccountId,
+        Blake2_128Concat,
+        KittyIndexOf<T>,
+        (),
+        OptionQuery,
+    >;
+
+    /// Native-priced listings by price. Prices are stored unhashed and big-endian, so iteration
+    /// starts from the cheapest listing.
+    #[pallet::storage]
+    pub(super) type PriceIndex<T: Config> =
+        StorageDoubleMap<_, Identity, [u8; 16], Twox64Concat, KittyIndexOf<T>, (), OptionQuery>;
+
+    /// Open collection bids by id.
+    #[pallet::storage]
+    #[pallet::getter(fn collection_bids)]
+    pub(super) type CollectionBids<T: Config> =
+        StorageMap<_, Twox64Concat, u32, CollectionBidOf<T>, OptionQuery>;
+
+    /// Id of the next collection bid to be placed.
+    #[pallet::storage]
+    pub(super) type NextCollectionBidId<T: Config> = StorageValue<_, u32, ValueQuery>;
+
+    #[pallet::storage]
+    #[pallet::getter(fn class_id)]
+    pub(super) type ClassId<T: Config> = StorageValue<_, T::ClassId, ValueQuery>;
+
+    /// Storage layout version, used to decide which migrations to run.
+    #[pallet::storage]
+    pub(super) type StorageVersion<T: Config> = StorageValue<_, Releases, ValueQuery>;
+
+    /// Generation of each kitty: 0 for created kitties, one more than the older parent for bred ones.
+    #[pallet::storage]
+    #[pallet::getter(fn kitty_generation)]
+    pub(super) type KittyGeneration<T: Config> =
+        StorageMap<_, Blake2_128Concat, KittyIndexOf<T>, u32, ValueQuery>;
+
+    /// Blake2 256 hash of the SCALE encoded seed each created or bred kitty's dna was derived
+    /// from.
+    #[pallet::storage]
+    #[pallet::getter(fn dna_seed)]
+    pub(super) type DnaSeeds<T: Config> =
+        StorageMap<_, Blake2_128Concat, KittyIndexOf<T>, [u8; 32], OptionQuery>;
+
+    /// Parents of each bred kitty.
+    #[pallet::storage]
+    #[pallet::getter(fn kitty_parents)]
+    pub(super) type KittyParents<T: Config> =
+        StorageMap<_, Blake2_128Concat, KittyIndexOf<T>, (KittyIndexOf<T>, KittyIndexOf<T>)>;
+
+    /// How many clones removed from an original each kitty is: 0 for originals.
+    #[pallet::storage]
+    #[pallet::getter(fn clone_depth)]
+    pub(super) type CloneDepth<T: Config> =
+        StorageMap<_, Blake2_128Concat, KittyIndexOf<T>, u8, ValueQuery>;
+
+    /// The original each clone descends from.
+    #[pallet::storage]
+    #[pallet::getter(fn clone_original)]
+    pub(super) type CloneOriginal<T: Config> =
+        StorageMap<_, Blake2_128Concat, KittyIndexOf<T>, KittyIndexOf<T>, OptionQuery>;
+
+    /// Number of clones descending from each original.
+    #[pallet::storage]
+    #[pallet::getter(fn clone_count)]
+    pub(super) type CloneCount<T: Config> =
+        StorageMap<_, Blake2_128Concat, KittyIndexOf<T>, u8, ValueQuery>;
+
+    /// Id of the kitty with each dna, keyed by the dna's blake2_256 hash.
+    #[pallet::storage]
+    pub(super) type DnaIndex<T: Config> =
+        StorageMap<_, Identity, [u8; 32], KittyIndexOf<T>, OptionQuery>;
+
+    /// Kitties by (trait id, bucketed trait value), so trait filters don't need full scans.
+    #[pallet::storage]
+    pub(super) type TraitIndex<T: Config> = StorageDoubleMap<
+        _,
+        Blake2_128Concat,
+        (u8, u8),
+        Blake2_128Concat,
+        KittyIndexOf<T>,
+        (),
+        OptionQuery,
+    >;
+
+    /// Number of `buy_kitty` calls on each kitty that failed because its listing was gone or
+    /// the buyer could not pay, a measure of contention for it.
+    #[pallet::storage]
+    #[pallet::getter(fn failed_purchases)]
+    pub(super) type FailedPurchases<T: Config> =
+        StorageMap<_, Blake2_128Concat, KittyIndexOf<T>, u32, ValueQuery>;
+
+    /// Number of offspring each kitty has had, twins included.
+    #[pallet::storage]
+    #[pallet::getter(fn offspring_count)]
+    pub(super) type OffspringCount<T: Config> =
+        StorageMap<_, Blake2_128Concat, KittyIndexOf<T>, u32, ValueQuery>;
+
+    /// Block from which a recently bought kitty may be listed again.
+    #[pallet::storage]
+    #[pallet::getter(fn resale_locked_until)]
+    pub(super) type ResaleLockedUntil<T: Config> =
+        StorageMap<_, Blake2_128Concat, KittyIndexOf<T>, T::BlockNumber>;
+
+    /// Block a kitty was minted in. Kitties minted before it was tracked count as minted in
+    /// block zero.
+    #[pallet::storage]
+    #[pallet::getter(fn minted_at)]
+    pub(super) type MintedAt<T: Config> =
+        StorageMap<_, Blake2_128Concat, KittyIndexOf<T>, T::BlockNumber, ValueQuery>;
+
+    /// Number of times a kitty has evolved.
+    #[pallet::storage]
+    #[pallet::getter(fn evolution_stage)]
+    pub(super) type EvolutionStage<T: Config> =
+        StorageMap<_, Blake2_128Concat, KittyIndexOf<T>, u8, ValueQuery>;
+
+    /// Block a kitty was last bought in, for the decaying flip fee on its next sale.
+    #[pallet::storage]
+    #[pallet::getter(fn purchased_at)]
+    pub(super) type PurchasedAt<T: Config> =
+        StorageMap<_, Blake2_128Concat, KittyIndexOf<T>, T::BlockNumber>;
+
+    /// Block a listed kitty's price was last set in.
+    #[pallet::storage]
+    #[pallet::getter(fn listed_at)]
+    pub(super) type ListedAt<T: Config> =
+        StorageMap<_, Blake2_128Concat, KittyIndexOf<T>, T::BlockNumber>;
+
+    /// Block from which a kitty received through `transfer_with_lock` may be moved or listed.
+    #[pallet::storage]
+    #[pallet::getter(fn transfer_lock)]
+    pub(super) type TransferLocks<T: Config> =
+        StorageMap<_, Blake2_128Concat, KittyIndexOf<T>, T::BlockNumber>;
+
+    /// Number of trades and block of the latest trade, by (buyer, seller).
+    #[pallet::storage]
+    #[pallet::getter(fn pair_trades)]
+    pub(super) type PairTrades<T: Config> = StorageDoubleMap<
+        _,
+        Blake2_128Concat,
+        T::AccountId,
+        Blake2_128Concat,
+        T::AccountId,
+        (u32, T::BlockNumber),
+        OptionQuery,
+    >;
+
+    /// Marketplace record of each account.
+    #[pallet::storage]
+    #[pallet::getter(fn reputation)]
+    pub(super) type Reputations<T: Config> =
+        StorageMap<_, Blake2_128Concat, T::AccountId, Reputation, ValueQuery>;
+
+    /// Listing price above which sellers need at least the given reputation score, if any.
+    #[pallet::storage]
+    #[pallet::getter(fn reputation_gate)]
+    pub(super) type ReputationGate<T: Config> = StorageValue<_, (BalanceOf<T>, u32), OptionQuery>;
+
+    /// Number of trades flagged as possible wash trading per account.
+    #[pallet::storage]
+    #[pallet::getter(fn suspicious_trades)]
+    pub(super) type SuspiciousTrades<T: Config> =
+        StorageMap<_, Blake2_128Concat, T::AccountId, u32, ValueQuery>;
+
+    /// Flagged trade count at which an account is barred from trading, if any.
+    #[pallet::storage]
+    #[pallet::getter(fn wash_trade_threshold)]
+    pub(super) type WashTradeThreshold<T: Config> = StorageValue<_, u32, OptionQuery>;
+
+    /// Lowest native price a kitty can be listed at, if any.
+    #[pallet::storage]
+    #[pallet::getter(fn min_listing_price)]
+    pub(super) type MinListingPrice<T: Config> = StorageValue<_, BalanceOf<T>, OptionQuery>;
+
+    /// Highest native price a kitty can be listed at, if any.
+    #[pallet::storage]
+    #[pallet::getter(fn max_listing_price)]
+    pub(super) type MaxListingPrice<T: Config> = StorageValue<_, BalanceOf<T>, OptionQuery>;
+
+    /// Accounts barred from buying and listing kitties.
+    #[pallet::storage]
+    #[pallet::getter(fn trading_restricted)]
+    pub(super) type TradingRestricted<T: Config> =
+        StorageMap<_, Blake2_128Concat, T::AccountId, (), OptionQuery>;
+
+    /// Whether the kitty collection is frozen, e.g. ahead of a runtime upgrade.
+    #[pallet::storage]
+    #[pallet::getter(fn collection_frozen)]
+    pub(super) type Frozen<T: Config> = StorageValue<_, bool, ValueQuery>;
+
+    /// Block range `[start, end)` during which the marketplace is closed.
+    #[pallet::storage]
+    #[pallet::getter(fn maintenance_window)]
+    pub(super) type MaintenanceWindow<T: Config> =
+        StorageValue<_, (T::BlockNumber, T::BlockNumber), OptionQuery>;
+
+    /// Pedigree certificates by id. They stay with their holder whatever happens to the kitty.
+    #[pallet::storage]
+    #[pallet::getter(fn pedigree_certificates)]
+    pub(super) type PedigreeCertificates<T: Config> =
+        StorageMap<_, Twox64Concat, u32, PedigreeCertificateOf<T>, OptionQuery>;
+
+    /// Id of the next pedigree certificate to be minted.
+    #[pallet::storage]
+    pub(super) type NextCertificateId<T: Config> = StorageValue<_, u32, ValueQuery>;
+
+    /// Breeding licenses by id. They are removed once used up.
+    #[pallet::storage]
+    #[pallet::getter(fn breeding_licenses)]
+    pub(super) type BreedingLicenses<T: Config> =
+        StorageMap<_, Twox64Concat, u32, BreedingLicenseOf<T>, OptionQuery>;
+
+    /// Id of the next breeding license to be minted.
+    #[pallet::storage]
+    pub(super) type NextLicenseId<T: Config> = StorageValue<_, u32, ValueQuery>;
+
+    /// Males and females among recent `create_kitty` mints. Both are halved once they add up
+    /// to `GENDER_WINDOW`, so older mints count for less.
+    #[pallet::storage]
+    #[pallet::getter(fn recent_mint_genders)]
+    pub(super) type RecentMintGenders<T: Config> = StorageValue<_, (u32, u32), ValueQuery>;
+
+    /// Kitties waiting in the shelter for adoption, and who surrendered them.
+    #[pallet::storage]
+    #[pallet::getter(fn shelter)]
+    pub(super) type Shelter<T: Config> =
+        StorageMap<_, Twox64Concat, KittyIndexOf<T>, T::AccountId, OptionQuery>;
+
+    /// Mint price set by governance in place of `MintPrice`.
+    #[pallet::storage]
+    pub(super) type MintPriceOverride<T: Config> = StorageValue<_, BalanceOf<T>, OptionQuery>;
+
+    /// Sorted, non-overlapping block ranges `[start, end)` during which kitties can breed.
+    /// Breeding is open at all times while the schedule is empty.
+    #[pallet::storage]
+    #[pallet::getter(fn breeding_seasons)]
+    pub(super) type BreedingSeasons<T: Config> =
+        StorageValue<_, Vec<(T::BlockNumber, T::BlockNumber)>, ValueQuery>;
+
+    /// Id of the kitty most recently minted to each account, by creation, breeding or import.
+    #[pallet::storage]
+    #[pallet::getter(fn last_minted_by)]
+    pub(super) type LastMintedBy<T: Config> =
+        StorageMap<_, Blake2_128Concat, T::AccountId, KittyIndexOf<T>, OptionQuery>;
+
+    /// Kitties minted, kitties sold and total sale volume in the current block.
+    #[pallet::storage]
+    pub(super) type BlockActivity<T: Config> =
+        StorageValue<_, (u32, u32, BalanceOf<T>), ValueQuery>;
+
+    /// Number of kitties ever minted, including those since burned.
+    #[pallet::storage]
+    #[pallet::getter(fn total_minted)]
+    pub(super) type TotalMinted<T: Config> = StorageValue<_, u64, ValueQuery>;
+
+    /// Kitties minted at a `MilestoneInterval`, with the mint count they reached.
+    #[pallet::storage]
+    #[pallet::getter(fn milestone_kitty)]
+    pub(super) type MilestoneKitties<T: Config> =
+        StorageMap<_, Blake2_128Concat, KittyIndexOf<T>, u64, OptionQuery>;
+
+    /// Quests that can currently be worked on or claimed.
+    #[pallet::storage]
+    #[pallet::getter(fn quests)]
+    pub(super) type Quests<T: Config> =
+        StorageMap<_, Blake2_128Concat, u32, QuestOf<T>, OptionQuery>;
+
+    /// Races that can be entered or are waiting to be finished.
+    #[pallet::storage]
+    #[pallet::getter(fn races)]
+    pub(super) type Races<T: Config> = StorageMap<_, Blake2_128Concat, u32, RaceOf<T>, OptionQuery>;
+
+    /// Id of the next race to be opened.
+    #[pallet::storage]
+    pub(super) type NextRaceId<T: Config> = StorageValue<_, u32, ValueQuery>;
+
+    /// Price above which sales wait for the seller's confirmation, if any.
+    #[pallet::storage]
+    #[pallet::getter(fn high_value_threshold)]
+    pub(super) type HighValueThreshold<T: Config> = StorageValue<_, BalanceOf<T>, OptionQuery>;
+
+    /// Funds each account has reserved for the marketplace: collection bids, pending purchases,
+    /// layaways and refunds of returnable sales.
+    #[pallet::storage]
+    #[pallet::getter(fn market_hold)]
+    pub(super) type MarketHolds<T: Config> =
+        StorageMap<_, Blake2_128Concat, T::AccountId, BalanceOf<T>, ValueQuery>;
+
+    /// Sales waiting for the seller's confirmation.
+    #[pallet::storage]
+    #[pallet::getter(fn pending_sales)]
+    pub(super) type PendingSales<T: Config> =
+        StorageMap<_, Blake2_128Concat, u32, PendingSaleOf<T>, OptionQuery>;
+
+    /// Pending sales by the block they are reverted at.
+    #[pallet::storage]
+    pub(super) type PendingSaleExpiries<T: Config> =
+        StorageMap<_, Twox64Concat, T::BlockNumber, Vec<u32>, ValueQuery>;
+
+    /// Id of the next pending sale.
+    #[pallet::storage]
+    pub(super) type NextPendingSaleId<T: Config> = StorageValue<_, u32, ValueQuery>;
+
+    /// Layaway purchases by id.
+    #[pallet::storage]
+    #[pallet::getter(fn layaways)]
+    pub(super) type Layaways<T: Config> =
+        StorageMap<_, Twox64Concat, u32, LayawayOf<T>, OptionQuery>;
+
+    /// Layaways by the block their next installment is due by. Ids of layaways that were paid
+    /// on time are left behind and skipped.
+    #[pallet::storage]
+    pub(super) type LayawayDeadlines<T: Config> =
+        StorageMap<_, Twox64Concat, T::BlockNumber, Vec<u32>, ValueQuery>;
+
+    /// Community grant proposals waiting for approval, by id.
+    #[pallet::storage]
+    #[pallet::getter(fn grant_proposals)]
+    pub(super) type GrantProposals<T: Config> =
+        StorageMap<_, Twox64Concat, u32, GrantProposal<T::AccountId>, OptionQuery>;
+
+    /// Id of the next community grant proposal.
+    #[pallet::storage]
+    pub(super) type NextGrantProposalId<T: Config> = StorageValue<_, u32, ValueQuery>;
+
+    /// Owner snapshots by the block whose end they were started from.
+    #[pallet::storage]
+    #[pallet::getter(fn owner_snapshots)]
+    pub(super) type OwnerSnapshots<T: Config> =
+        StorageMap<_, Twox64Concat, T::BlockNumber, OwnerSnapshot, OptionQuery>;
+
+    /// Block of the latest owner snapshot.
+    #[pallet::storage]
+    #[pallet::getter(fn latest_owner_snapshot)]
+    pub(super) type LatestOwnerSnapshot<T: Config> = StorageValue<_, T::BlockNumber, OptionQuery>;
+
+    /// The owner snapshot being taken, if any.
+    #[pallet::storage]
+    #[pallet::getter(fn pending_owner_snapshot)]
+    pub(super) type PendingOwnerSnapshot<T: Config> =
+        StorageValue<_, OwnerSnapshotProgressOf<T>, OptionQuery>;
+
+    /// Owner and leaf index of each kitty in an owner snapshot. Snapshots alternate between
+    /// two buffers so the latest stays whole while the next is taken.
+    #[pallet::storage]
+    pub(super) type SnapshotOwners<T: Config> = StorageDoubleMap<
+        _,
+        Twox64Concat,
+        u8,
+        Twox64Concat,
+        KittyIndexOf<T>,
+        (T::AccountId, u32),
+        OptionQuery,
+    >;
+
+    /// Buffer of `SnapshotOwners` holding the latest owner snapshot.
+    #[pallet::storage]
+    pub(super) type OwnerSnapshotBuffer<T: Config> = StorageValue<_, u8, ValueQuery>;
+
+    /// Metadata of the kitty class, if it has been set.
+    #[pallet::storage]
+    #[pallet::getter(fn class_metadata)]
+    pub(super) type ClassMetadata<T: Config> = StorageValue<_, CollectionMetadata, OptionQuery>;
+
+    /// Id of the next layaway.
+    #[pallet::storage]
+    pub(super) type NextLayawayId<T: Config> = StorageValue<_, u32, ValueQuery>;
+
+    /// Seller of each listed kitty whose buyer may return it. A fresh listing starts without.
+    #[pallet::storage]
+    #[pallet::getter(fn accepts_returns)]
+    pub(super) type AcceptsReturns<T: Config> =
+        StorageMap<_, Blake2_128Concat, KittyIndexOf<T>, T::AccountId, OptionQuery>;
+
+    /// Sales that can still be returned, by id.
+    #[pallet::storage]
+    #[pallet::getter(fn returnable_sales)]
+    pub(super) type ReturnableSales<T: Config> =
+        StorageMap<_, Twox64Concat, u32, ReturnableSaleOf<T>, OptionQuery>;
+
+    /// Returnable sales by the block their return period ends in.
+    #[pallet::storage]
+    pub(super) type ReturnDeadlines<T: Config> =
+        StorageMap<_, Twox64Concat, T::BlockNumber, Vec<u32>, ValueQuery>;
+
+    /// Id of the next returnable sale.
+    #[pallet::storage]
+    pub(super) type NextReturnableSaleId<T: Config> = StorageValue<_, u32, ValueQuery>;
+
+    /// The latest `ForceOrigin` actions, oldest first.
+    #[pallet::storage]
+    #[pallet::getter(fn audit_log)]
+    pub(super) type AuditLog<T: Config> =
+        StorageValue<_, Vec<AuditEntry<T::BlockNumber>>, ValueQuery>;
+
+    /// The airdrop being paid out, if any.
+    #[pallet::storage]
+    #[pallet::getter(fn active_airdrop)]
+    pub(super) type ActiveAirdrop<T: Config> = StorageValue<_, AirdropOf<T>, OptionQuery>;
+
+    /// Unredeemed coupons, keyed by the blake2_256 hash of their code.
+    #[pallet::storage]
+    #[pallet::getter(fn coupons)]
+    pub(super) type Coupons<T: Config> = StorageMap<_, Identity, [u8; 32], Coupon, OptionQuery>;
+
+    /// Hashes of redeemed coupon codes, which cannot be issued again.
+    #[pallet::storage]
+    #[pallet::getter(fn coupon_redeemed)]
+    pub(super) type RedeemedCoupons<T: Config> = StorageMap<_, Identity, [u8; 32], (), OptionQuery>;
+
+    /// Id of the next quest to be created.
+    #[pallet::storage]
+    pub(super) type NextQuestId<T: Config> = StorageValue<_, u32, ValueQuery>;
+
+    /// Progress of each account on each quest, and whether its reward has been claimed.
+    #[pallet::storage]
+    #[pallet::getter(fn quest_progress)]
+    pub(super) type QuestProgress<T: Config> = StorageDoubleMap<
+        _,
+        Blake2_128Concat,
+        u32,
+        Blake2_128Concat,
+        T::AccountId,
+        (u32, bool),
+        ValueQuery,
+    >;
+
+    /// Badges of the quests each account has completed and claimed.
+    #[pallet::storage]
+    #[pallet::getter(fn quest_badge)]
+    pub(super) type QuestBadges<T: Config> =
+        StorageDoubleMap<_, Blake2_128Concat, T::AccountId, Blake2_128Concat, u32, (), OptionQuery>;
+
+    /// Most each account has chosen to spend on kitties per `SpendingPeriod`.
+    #[pallet::storage]
+    #[pallet::getter(fn spending_limit)]
+    pub(super) type SpendingLimits<T: Config> =
+        StorageMap<_, Blake2_128Concat, T::AccountId, BalanceOf<T>, OptionQuery>;
+
+    /// Heir of each account that has chosen one, and how many blocks the account must be
+    /// inactive for before the heir can claim its kitties.
+    #[pallet::storage]
+    #[pallet::getter(fn heir)]
+    pub(super) type Heirs<T: Config> =
+        StorageMap<_, Blake2_128Concat, T::AccountId, (T::AccountId, T::BlockNumber), OptionQuery>;
+
+    /// Block of the last call made by each account that has an heir.
+    #[pallet::storage]
+    #[pallet::getter(fn last_active)]
+    pub(super) type LastActive<T: Config> =
+        StorageMap<_, Blake2_128Concat, T::AccountId, T::BlockNumber, ValueQuery>;
+
+    /// `GAME_*` permissions each owner has given a game server over its kitties, and the block
+    /// they expire at.
+    #[pallet::storage]
+    #[pallet::getter(fn game_server)]
+    pub(super) type GameServers<T: Config> = StorageDoubleMap<
+        _,
+        Blake2_128Concat,
+        T::AccountId,
+        Blake2_128Concat,
+        T::AccountId,
+        (u8, T::BlockNumber),
+        OptionQuery,
+    >;
+
+    /// Period index and amount spent in it by each account with a spending limit.
+    #[pallet::storage]
+    #[pallet::getter(fn spent)]
+    pub(super) type Spent<T: Config> =
+        StorageMap<_, Blake2_128Concat, T::AccountId, (T::BlockNumber, BalanceOf<T>), ValueQuery>;
+
+    /// Fee rebates of each account: the period index of its latest rebate, the amount earned
+    /// in that period and the amount that has vested since.
+    #[pallet::storage]
+    #[pallet::getter(fn fee_rebates)]
+    pub(super) type FeeRebates<T: Config> = StorageMap<
+        _,
+        Blake2_128Concat,
+        T::AccountId,
+        (T::BlockNumber, BalanceOf<T>, BalanceOf<T>),
+        ValueQuery,
+    >;
+
+    /// Sale price statistics by period index, for the last `StatsHistory` periods with sales.
+    #[pallet::storage]
+    #[pallet::getter(fn price_stats)]
+    pub(super) type PriceStats<T: Config> =
+        StorageMap<_, Twox64Concat, T::BlockNumber, PriceCandle<BalanceOf<T>>, OptionQuery>;
+
+    /// The last `TradeHistoryLength` trades of each account as buyer or seller, oldest first.
+    #[pallet::storage]
+    #[pallet::getter(fn trade_history)]
+    pub(super) type TradeHistory<T: Config> =
+        StorageMap<_, Blake2_128Concat, T::AccountId, Vec<TradeReceiptOf<T>>, ValueQuery>;
+
+    /// Number of trades executed with each account as the counterparty, which is the nonce
+    /// its next signed `TradeTerms` must carry.
+    #[pallet::storage]
+    #[pallet::getter(fn trade_nonce)]
+    pub(super) type TradeNonces<T: Config> =
+        StorageMap<_, Blake2_128Concat, T::AccountId, u32, ValueQuery>;
+
+    /// Moving average of the prices of sold kitties with a trait in a bucket, and how many such
+    /// sales there have been.
+    #[pallet::storage]
+    #[pallet::getter(fn trait_price)]
+    pub(super) type TraitPrices<T: Config> =
+        StorageMap<_, Twox64Concat, (u8, u8), (BalanceOf<T>, u32), OptionQuery>;
+
+    /// Price paid for each kitty when it was last bought.
+    #[pallet::storage]
+    #[pallet::getter(fn cost_basis)]
+    pub(super) type CostBasis<T: Config> =
+        StorageMap<_, Blake2_128Concat, KittyIndexOf<T>, BalanceOf<T>, ValueQuery>;
+
+    /// Tax reports by era index and account, for the last `TaxHistory` eras. Reports of older
+    /// eras are removed a few per block.
+    #[pallet::storage]
+    pub(super) type TaxReports<T: Config> = StorageDoubleMap<
+        _,
+        Twox64Concat,
+        T::BlockNumber,
+        Blake2_128Concat,
+        T::AccountId,
+        TaxReport<BalanceOf<T>>,
+        ValueQuery,
+    >;
+
+    /// Oldest tax era whose reports may not all have been removed yet.
+    #[pallet::storage]
+    pub(super) type NextTaxPruneEra<T: Config> = StorageValue<_, T::BlockNumber, ValueQuery>;
+
+    /// Indices of the periods in `PriceStats`, oldest first.
+    #[pallet::storage]
+    #[pallet::getter(fn stats_periods)]
+    pub(super) type StatsPeriods<T: Config> = StorageValue<_, Vec<T::BlockNumber>, ValueQuery>;
+
+    /// Number of kitties exported so far, used as the nonce of the next export.
+    #[pallet::storage]
+    #[pallet::getter(fn export_nonce)]
+    pub(super) type ExportNonce<T: Config> = StorageValue<_, u64, ValueQuery>;
+
+    /// Account whose signature `import_kitty` accepts on exports.
+    #[pallet::storage]
+    #[pallet::getter(fn import_authority)]
+    pub(super) type ImportAuthority<T: Config> = StorageValue<_, T::AccountId, OptionQuery>;
+
+    /// Hashes of exports that have already been imported.
+    #[pallet::storage]
+    pub(super) type ImportedExports<T: Config> = StorageMap<_, Identity, T::Hash, (), OptionQuery>;
+
+    /// Listings and delistings to apply at the start of each block.
+    #[pallet::storage]
+    #[pallet::getter(fn scheduled_price_changes)]
+    pub(super) type ScheduledPriceChanges<T: Config> =
+        StorageMap<_, Twox64Concat, T::BlockNumber, Vec<ScheduledPriceChangeOf<T>>, ValueQuery>;
+
+    /// Jurisdiction each account is taxed in.
+    #[pallet::storage]
+    #[pallet::getter(fn jurisdiction)]
+    pub(super) type Jurisdictions<T: Config> =
+        StorageMap<_, Blake2_128Concat, T::AccountId, JurisdictionTag, OptionQuery>;
+
+    /// Total amount each account has earned by referring purchases.
+    #[pallet::storage]
+    #[pallet::getter(fn referral_earnings)]
+    pub(super) type ReferralEarnings<T: Config> =
+        StorageMap<_, Blake2_128Concat, T::AccountId, BalanceOf<T>, ValueQuery>;
+
+    /// Kitty genetics. The version says how `dna` is to be read.
+    #[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+    #[derive(Encode, Decode, Clone, PartialEq, Debug, Eq)]
+    pub struct Genome {
+        pub version: u8,
+        pub dna: Vec<u8>,
+    }
+
+    #[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+    #[derive(Encode, Decode, Clone, PartialEq, Debug, Eq)]
+    pub struct Kitty(pub Genome);
+
+    impl From<[u8; 16]> for Kitty {
+        /// Wrap the dna of a kitty from before genomes were versioned.
+        fn from(dna: [u8; 16]) -> Self {
+            Kitty(Genome {
+                version: 1,
+                dna: dna.to_vec(),
+            })
+        }
+    }
+
+    /// Local offchain storage key holding the URL that sale notifications are POSTed to.
+    ///
+    /// Node operators opt in by setting it, e.g. through the `offchain_localStorageSet` RPC.
+    pub const WEBHOOK_URL_KEY: &[u8] = b"kitties::webhook-url";
+    /// How long the offchain worker waits for the webhook to respond.
+    const WEBHOOK_TIMEOUT_MS: u64 = 3_000;
+
+    /// Genome version of newly minted kitties: one trait per dna byte.
+    pub const GENOME_VERSION: u8 = 1;
+    /// Width of the buckets dna bytes are grouped into when indexing traits.
+    pub const TRAIT_BUCKET_SIZE: u8 = 16;
+    /// Dna byte a kitty's fertility is read from.
+    pub const FERTILITY_GENE: usize = 12;
+    /// Fewest offspring any kitty can have.
+    pub const MIN_FERTILITY: u32 = 2;
+    /// Dna byte holding a kitty's pattern intensity, which grows as it evolves.
+    pub const PATTERN_GENE: usize = 13;
+    /// Pattern intensity gained per evolution stage.
+    pub const EVOLUTION_STEP: u8 = 32;
+    /// Last evolution stage a kitty can reach.
+    pub const MAX_EVOLUTION_STAGE: u8 = 3;
+    /// Game server permission to enter an owner's kitties into races.
+    pub const GAME_ENTER_RACES: u8 = 1 << 0;
+    /// Game server permission to evolve an owner's kitties.
+    pub const GAME_EVOLVE: u8 = 1 << 1;
+    /// Every permission a game server can be given. None of them moves a kitty to another owner.
+    pub const GAME_PERMISSIONS: u8 = GAME_ENTER_RACES | GAME_EVOLVE;
+    /// Attempts at generating a dna no other kitty has before minting fails.
+    const DNA_ATTEMPTS: u32 = 8;
+    /// Mints counted in `RecentMintGenders` before both counters are halved.
+    const GENDER_WINDOW: u32 = 100;
+    /// Sales a trait's moving average price is smoothed over, and the most any one trait weighs
+    /// in an appraisal.
+    const APPRAISAL_SALES: u32 = 8;
+    /// Most kitties a direct trade can move, both ways together.
+    pub const MAX_TRADE_KITTIES: u32 = 8;
+    /// Prefix of the `trade_payload` a trade's counterparty signs.
+    pub const TRADE_SIGNING_CONTEXT: [u8; 13] = *b"kitties/trade";
+    /// Most kitties `similar_kitties` compares against, taken from the trait index.
+    const SIMILARITY_CANDIDATES: usize = 256;
+
+    /// A kitty and the hash of the seed its dna was derived from.
+    type SeededKitty = (Kitty, [u8; 32]);
+
+    #[derive(Encode, Decode, Clone, Copy, PartialEq, Debug)]
+    pub enum Gender {
+        Male,
+        Female,
+    }
+
+    impl Kitty {
+        /// A kitty with random dna for `owner`, and the hash of the seed it was derived from.
+        /// If a `gender` is given, seeds are retried like duplicates until one derives it, as
+        /// long as attempts are left.
+        fn new<T: Config>(
+            owner: T::AccountId,
+            gender: Option<Gender>,
+        ) -> Result<SeededKitty, Error<T>> {
+            // Collect sources for random hash
+            let payload = (
+                owner.clone(),
+                T::RandomnessSource::random(&owner.encode()[..]),
+                frame_system::Module::<T>::extrinsic_index(),
+            );
+
+            // Generate random dna source
+            Kitty::generate_unique::<T>(None, payload, gender)
+        }
+
+        /// Like `generate`, extending `seed` with a nonce until the dna is not taken by another
+        /// kitty. Also returns the hash of the encoded seed the dna was derived from.
+        ///
+        /// With a `gender`, the first unique kitty of that gender is preferred, falling back to
+        /// the first unique one once attempts run out.
+        fn generate_unique<T: Config>(
+            parents: Option<(&Kitty, &Kitty)>,
+            seed: impl Encode,
+            gender: Option<Gender>,
+        ) -> Result<SeededKitty, Error<T>> {
+            let mut fallback = None;
+            for nonce in 0..DNA_ATTEMPTS {
+                let seed = match nonce {
+                    0 => seed.encode(),
+                    _ => (&seed, nonce).encode(),
+                };
+                let kitty = Kitty::generate::<T>(parents, &seed);
+                if !DnaIndex::<T>::contains_key(blake2_256(kitty.dna())) {
+                    let seeded = (kitty, blake2_256(&seed));
+                    match gender {
+                        Some(gender) if seeded.0.gender() != gender => {
+                            fallback = fallback.or(Some(seeded))
+                        }
+                        _ => return Ok(seeded),
+                    }
+                }
+            }
+            fallback.ok_or(Error::<T>::DuplicateKitty)
+        }
+
+        /// Derive a current version genome of `DnaLength` bytes from the encoded `seed`, as
+        /// the offspring of `parents` if given.
+        fn generate<T: Config>(parents: Option<(&Kitty, &Kitty)>, seed: &[u8]) -> Kitty {
+            Kitty(Genome {
+                version: GENOME_VERSION,
+                dna: Kitty::derive_dna::<T>(parents, seed, T::DnaLength::get() as usize),
+            })
+        }
+
+        /// Derive `length` bytes of dna from the encoded `seed` with the `DnaProvider`.
+        fn derive_dna<T: Config>(
+            parents: Option<(&Kitty, &Kitty)>,
+            seed: &[u8],
+            length: usize,
+        ) -> Vec<u8> {
+            let mut dna = match parents {
+                Some((first, second)) => {
+                    T::DnaProvider::offspring_dna(first.dna(), second.dna(), seed, length)
+                }
+                None => T::DnaProvider::random_dna(seed, length),
+            };
+            // Traits are read by position, so never trust the provider with the length
+            dna.resize(length, 0);
+            dna
+        }
+
+        pub fn dna(&self) -> &[u8] {
+            &self.0.dna
+        }
+
+        fn get_gender_from_dna(dna: &[u8]) -> Gender {
+            let total = dna.iter().max();
+            match total {
+                Some(total) => {
+                    if total % 2 == 0 {
+                        Gender::Male
+                    } else {
+                        Gender::Female
+                    }
+                }
+                None => Gender::Male,
+            }
+        }
+
+        fn ensure_different_kitty<T: Config>(
+            first: &Kitty,
+            second: &Kitty,
+        ) -> Result<(), Error<T>> {
+            match *first != *second {
+                true => Ok(()),
+                false => Err(Error::<T>::KittyPartnerMissing),
+            }
+        }
+
+        fn ensure_different_gender<T: Config>(
+            first: &Kitty,
+            second: &Kitty,
+        ) -> Result<(), Error<T>> {
+            match first.gender() != second.gender() {
+                true => Ok(()),
+                false => Err(Error::<T>::KittyGendersNotCompatible),
+            }
+        }
+
+        pub fn gender(&self) -> Gender {
+            Kitty::get_gender_from_dna(self.dna())
+        }
+
+        /// The kitty one evolution stage on: its pattern intensity grows by `EVOLUTION_STEP`,
+        /// without ever changing which byte is highest or its parity, so the gender stays.
+        /// Kitties without a pattern gene do not change.
+        pub fn evolved(&self) -> Kitty {
+            let mut kitty = self.clone();
+            let max = self.dna().iter().copied().max().unwrap_or_default();
+            if let Some(pattern) = kitty.0.dna.get_mut(PATTERN_GENE) {
+                let raised = pattern.saturating_add(EVOLUTION_STEP);
+                *pattern = if *pattern == max {
+                    raised - (raised - *pattern) % 2
+                } else {
+                    raised.min(max)
+                };
+            }
+            kitty
+        }
+
+        /// Bucketed value of a trait, where trait `n` is read from the `n`th dna byte.
+        pub fn trait_value(&self, trait_id: u8) -> Option<u8> {
+            self.dna()
+                .get(trait_id as usize)
+                .map(|byte| byte / TRAIT_BUCKET_SIZE)
+        }
+
+        /// Number of dna bits that differ from `other`. Bytes past the end of the shorter dna
+        /// count as zero.
+        pub fn hamming_distance(&self, other: &Kitty) -> u32 {
+            let (dna, other) = (self.dna(), other.dna());
+            (0..dna.len().max(other.len()))
+                .map(|i| {
+                    let byte = |dna: &[u8]| dna.get(i).copied().unwrap_or_default();
+                    (byte(dna) ^ byte(other)).count_ones()
+                })
+                .sum()
+        }
+
+        /// Most offspring the kitty can have: `MIN_FERTILITY` plus up to 7 more, read from its
+        /// `FERTILITY_GENE`. Dna too short to have the gene gives the minimum.
+        pub fn fertility(&self) -> u32 {
+            let gene = self.dna().get(FERTILITY_GENE).copied().unwrap_or_default();
+            MIN_FERTILITY + gene as u32 / 32
+        }
+
+        /// Racing speed, the sum of the first four trait values.
+        pub fn speed(&self) -> u32 {
+            self.traits().take(4).map(|(_, value)| value as u32).sum()
+        }
+
+        /// Bucketed values of every trait, in trait id order.
+        pub fn traits(&self) -> impl Iterator<Item = (u8, u8)> + '_ {
+            (0..=u8::MAX)
+                .zip(self.dna())
+                .map(|(trait_id, byte)| (trait_id, byte / TRAIT_BUCKET_SIZE))
+        }
+
+        fn ensure_can_breed<T: Config>(first: &Kitty, second: &Kitty) -> Result<(), Error<T>> {
+            // Ensure parents are not the same
+            Kitty::ensure_different_kitty(first, second)?;
+            // Ensure parents have opposite genders
+            Kitty::ensure_different_gender(first, second)
+        }
+
+        /// Breed an offspring, and with `TwinProbability` a twin of it, each with the hash of
+        /// the seed it was derived from.
+        fn breed<T: Config>(
+            first: &Kitty,
+            second: &Kitty,
+        ) -> Result<(SeededKitty, Option<SeededKitty>), Error<T>> {
+            Kitty::ensure_can_breed(first, second)?;
+            let seed = (
+                T::RandomnessSource::random_seed(),
+                frame_system::Module::<T>::extrinsic_index(),
+            );
+
+            // Roll for twins from the parents and seed, so the outcome is as random as the dna
+            let roll = (b"twins", first.dna(), second.dna(), &seed).using_encoded(blake2_128);
+            let roll = u32::from_le_bytes([roll[0], roll[1], roll[2], roll[3]]);
+            let parents = Some((first, second));
+            let twin = if Permill::from_parts(roll % 1_000_000) < T::TwinProbability::get() {
+                Some(Kitty::generate_unique::<T>(parents, (&seed, 1u8), None)?)
+            } else {
+                None
+            };
+
+            // Generate dna
+            Ok((Kitty::generate_unique::<T>(parents, seed, None)?, twin))
+        }
+    }
+
+    // Pallets use events to inform users when important changes are made.
+    // https://substrate.dev/docs/en/knowledgebase/runtime/events
+    /// Events of the kitties pallet.
+    ///
+    /// Marketplace events are indexed under `kitty_topic` of their kitty and, where they have
+    /// one, `seller_topic` of the seller or listing owner.
+    ///
+    /// Fields are named, and listed in the same order in each variant's documentation since
+    /// metadata only carries their types. New events go at the end so that existing ones keep
+    /// their index.
+    #[pallet::event]
+    #[pallet::metadata(
+        T::AccountId = "AccountId",
+        T::BlockNumber = "BlockNumber",
+        BalanceOf<T> = "Balance",
+        KittyIndexOf<T> = "KittyIndex"
+    )]
+    pub enum Event<T: Config> {
+        /// A Kitty has been generated for the owner with random dna.
+        /// [dna, kitty, owner, seed_hash]
+        KittyCreated {
+            dna: Kitty,
+            kitty: KittyIndexOf<T>,
+            owner: T::AccountId,
+            seed_hash: [u8; 32],
+        },
+        /// A Kitty has been bred.
+        /// [dna, kitty, owner, seed_hash]
+        KittyBred {
+            dna: Kitty,
+            kitty: KittyIndexOf<T>,
+            owner: T::AccountId,
+            seed_hash: [u8; 32],
+        },
+        /// A Kitty has reached a mint milestone and its minter has been rewarded
+        /// [kitty, milestone, minter, reward]
+        MilestoneKitty {
+            kitty: KittyIndexOf<T>,
+            milestone: u64,
+            minter: T::AccountId,
+            reward: BalanceOf<T>,
+        },
+        /// Marketplace activity of the block, only deposited if there was any
+        /// [mints, sales, volume]
+        BlockMarketDigest {
+            mints: u32,
+            sales: u32,
+            volume: BalanceOf<T>,
+        },
+        /// A quest has been created
+        /// [quest]
+        QuestCreated { quest: u32 },
+        /// A quest has been removed, along with all progress on it once `cleared`
+        /// [quest, cleared]
+        QuestRemoved { quest: u32, cleared: bool },
+        /// A completed quest has been claimed
+        /// [quest, account, reward]
+        QuestRewardClaimed {
+            quest: u32,
+            account: T::AccountId,
+            reward: BalanceOf<T>,
+        },
+        /// A race has been opened
+        /// [race, entry_fee, closes_at]
+        RaceOpened {
+            race: u32,
+            entry_fee: BalanceOf<T>,
+            closes_at: T::BlockNumber,
+        },
+        /// A Kitty has entered a race
+        /// [race, kitty, owner]
+        RaceEntered {
+            race: u32,
+            kitty: KittyIndexOf<T>,
+            owner: T::AccountId,
+        },
+        /// A race has been run and its prize pool paid out
+        /// [race, winners as (kitty, owner, prize)]
+        RaceFinished {
+            race: u32,
+            winners: Vec<(KittyIndexOf<T>, T::AccountId, BalanceOf<T>)>,
+        },
+        /// The price above which sales need the seller's confirmation has changed
+        /// [threshold]
+        HighValueThresholdSet { threshold: Option<BalanceOf<T>> },
+        /// A high value purchase is waiting for the seller's confirmation
+        /// [sale, kitty, seller, buyer, price, expires_at]
+        SaleAwaitingConfirmation {
+            sale: u32,
+            kitty: KittyIndexOf<T>,
+            seller: T::AccountId,
+            buyer: T::AccountId,
+            price: BalanceOf<T>,
+            expires_at: T::BlockNumber,
+        },
+        /// A high value purchase was not confirmed in time and has been reverted
+        /// [sale, kitty]
+        PendingSaleExpired { sale: u32, kitty: KittyIndexOf<T> },
+        /// A coupon has been issued
+        /// [code_hash, coupon]
+        CouponIssued { code_hash: [u8; 32], coupon: Coupon },
+        /// A coupon has been redeemed on a purchase
+        /// [code_hash, kitty, buyer, discount]
+        CouponRedeemed {
+            code_hash: [u8; 32],
+            kitty: KittyIndexOf<T>,
+            buyer: T::AccountId,
+            discount: BalanceOf<T>,
+        },
+        /// Kitties can no longer be minted, moved or traded
+        CollectionFrozen,
+        /// Kitties can be minted, moved and traded again
+        CollectionThawed,
+        /// Vested fee rebates have been paid out
+        /// [account, amount]
+        FeeRebateClaimed {
+            account: T::AccountId,
+            amount: BalanceOf<T>,
+        },
+        /// The breeding season schedule has changed
+        /// [seasons]
+        BreedingSeasonsSet {
+            seasons: Vec<(T::BlockNumber, T::BlockNumber)>,
+        },
+        /// A breeding season has started
+        /// [start, end]
+        BreedingSeasonOpened {
+            start: T::BlockNumber,
+            end: T::BlockNumber,
+        },
+        /// A breeding season has ended
+        /// [start, end]
+        BreedingSeasonClosed {
+            start: T::BlockNumber,
+            end: T::BlockNumber,
+        },
+        /// The price of creating a kitty has changed
+        /// [price]
+        MintPriceSet { price: BalanceOf<T> },
+        /// A Kitty has been given up to the shelter
+        /// [kitty, owner]
+        KittySurrendered {
+            kitty: KittyIndexOf<T>,
+            owner: T::AccountId,
+        },
+        /// A Kitty has been adopted from the shelter
+        /// [kitty, adopter, fee]
+        KittyAdopted {
+            kitty: KittyIndexOf<T>,
+            adopter: T::AccountId,
+            fee: BalanceOf<T>,
+        },
+        /// A pedigree certificate has been minted
+        /// [certificate, kitty, holder, lineage]
+        PedigreeCertified {
+            certificate: u32,
+            kitty: KittyIndexOf<T>,
+            holder: T::AccountId,
+            lineage: [u8; 32],
+        },
+        /// A Kitty has been cloned
+        /// [original, clone, owner, fee]
+        KittyCloned {
+            original: KittyIndexOf<T>,
+            clone: KittyIndexOf<T>,
+            owner: T::AccountId,
+            fee: BalanceOf<T>,
+        },
+        /// An account has changed its spending limit
+        /// [account, limit]
+        SpendingLimitSet {
+            account: T::AccountId,
+            limit: Option<BalanceOf<T>>,
+        },
+        /// The cheapest listings have been bought in one go
+        /// [buyer, count, total]
+        FloorSwept {
+            buyer: T::AccountId,
+            count: u32,
+            total: BalanceOf<T>,
+        },
+        /// A breeding has produced twins, each also announced by `KittyBred`.
+        /// [kitty, twin, owner]
+        KittyTwinsBorn {
+            kitty: KittyIndexOf<T>,
+            twin: KittyIndexOf<T>,
+            owner: T::AccountId,
+        },
+        /// A Kitty has been transfered.
+        /// [kitty, from, to]
+        KittyTransfer {
+            kitty: KittyIndexOf<T>,
+            from: T::AccountId,
+            to: T::AccountId,
+        },
+        /// A Kitty has been sold.
+        /// [kitty, price, seller, buyer]
+        KittySold {
+            kitty: KittyIndexOf<T>,
+            price: BalanceOf<T>,
+            seller: T::AccountId,
+            buyer: T::AccountId,
+        },
+        /// A Kitty has been put up for sale
+        /// [kitty, price, owner]
+        KittyListed {
+            kitty: KittyIndexOf<T>,
+            price: BalanceOf<T>,
+            owner: T::AccountId,
+        },
+        /// A listed Kitty's price has changed
+        /// [kitty, old_price, new_price, owner]
+        KittyRepriced {
+            kitty: KittyIndexOf<T>,
+            old_price: BalanceOf<T>,
+            new_price: BalanceOf<T>,
+            owner: T::AccountId,
+        },
+        /// A Kitty has been taken off the market
+        /// [kitty, owner]
+        KittyDelisted {
+            kitty: KittyIndexOf<T>,
+            owner: T::AccountId,
+        },
+        /// A referrer has been paid a cut of the marketplace fee.
+        /// [kitty, amount, referrer]
+        ReferralPaid {
+            kitty: KittyIndexOf<T>,
+            amount: BalanceOf<T>,
+            referrer: T::AccountId,
+        },
+        /// Sales tax has been paid on a sale
+        /// [kitty, amount, collector]
+        SalesTaxPaid {
+            kitty: KittyIndexOf<T>,
+            amount: BalanceOf<T>,
+            collector: T::AccountId,
+        },
+        /// An account's tax jurisdiction has changed
+        /// [account, jurisdiction]
+        JurisdictionSet {
+            account: T::AccountId,
+            jurisdiction: Option<JurisdictionTag>,
+        },
+        /// A listing or delisting has been scheduled
+        /// [kitty, owner, price, block]
+        PriceChangeScheduled {
+            kitty: KittyIndexOf<T>,
+            owner: T::AccountId,
+            price: Option<BalanceOf<T>>,
+            block: T::BlockNumber,
+        },
+        /// A scheduled listing or delisting could not be applied
+        /// [kitty, owner, error]
+        ScheduledPriceChangeFailed {
+            kitty: KittyIndexOf<T>,
+            owner: T::AccountId,
+            error: DispatchError,
+        },
+        /// The reputation needed for high value listings has changed
+        /// [gate]
+        ReputationGateSet { gate: Option<(BalanceOf<T>, u32)> },
+        /// The marketplace maintenance window has been changed.
+        /// [window]
+        MaintenanceWindowSet {
+            window: Option<(T::BlockNumber, T::BlockNumber)>,
+        },
+        /// A Kitty has been listed at a price in the oracle's reference unit
+        /// [kitty, price, owner]
+        KittyReferencePriceUpdated {
+            kitty: KittyIndexOf<T>,
+            price: BalanceOf<T>,
+            owner: T::AccountId,
+        },
+        /// A Kitty has been transferred and locked with its receiver
+        /// [kitty, from, to, unlock_at]
+        KittyTransferLocked {
+            kitty: KittyIndexOf<T>,
+            from: T::AccountId,
+            to: T::AccountId,
+            unlock_at: T::BlockNumber,
+        },
+        /// A Kitty's resale cooldown has been lifted
+        /// [kitty]
+        ResaleCooldownCleared { kitty: KittyIndexOf<T> },
+        /// A sale reversed a recent trade between the same accounts
+        /// [kitty, seller, buyer]
+        SuspiciousTrade {
+            kitty: KittyIndexOf<T>,
+            seller: T::AccountId,
+            buyer: T::AccountId,
+        },
+        /// An account has been barred from trading
+        /// [account]
+        TradingRestricted { account: T::AccountId },
+        /// An account may trade again
+        /// [account]
+        TradingRestrictionLifted { account: T::AccountId },
+        /// The flagged trade count that bars accounts from trading has changed
+        /// [threshold]
+        WashTradeThresholdSet { threshold: Option<u32> },
+        /// The bounds on listing prices have changed
+        /// [min, max]
+        ListingPriceBoundsSet {
+            min: Option<BalanceOf<T>>,
+            max: Option<BalanceOf<T>>,
+        },
+        /// A Kitty has been burned for import on another chain
+        /// [kitty, export]
+        KittyExported {
+            kitty: KittyIndexOf<T>,
+            export: KittyExportOf<T>,
+        },
+        /// An exported Kitty has been minted on this chain
+        /// [dna, kitty, owner]
+        KittyImported {
+            dna: Kitty,
+            kitty: KittyIndexOf<T>,
+            owner: T::AccountId,
+        },
+        /// The account that signs kitty imports has changed
+        /// [authority]
+        ImportAuthoritySet { authority: Option<T::AccountId> },
+        /// A standing bid for any matching kitty has been placed
+        /// [bid, bidder, max_price, quantity]
+        CollectionBidPlaced {
+            bid: u32,
+            bidder: T::AccountId,
+            max_price: BalanceOf<T>,
+            quantity: u32,
+        },
+        /// A kitty has been sold into a collection bid
+        /// [bid, kitty, seller]
+        CollectionBidFilled {
+            bid: u32,
+            kitty: KittyIndexOf<T>,
+            seller: T::AccountId,
+        },
+        /// A collection bid has been withdrawn and its remaining funds released
+        /// [bid]
+        CollectionBidCancelled { bid: u32 },
+        /// An account has chosen an heir for its kitties
+        /// [owner, heir, inactivity_blocks]
+        HeirSet {
+            owner: T::AccountId,
+            heir: T::AccountId,
+            inactivity_blocks: T::BlockNumber,
+        },
+        /// An account no longer has an heir
+        /// [owner]
+        HeirRemoved { owner: T::AccountId },
+        /// An heir has received kitties of an inactive account
+        /// [owner, heir, count]
+        InheritanceClaimed {
+            owner: T::AccountId,
+            heir: T::AccountId,
+            count: u32,
+        },
+        /// A breeding license has been minted for a kitty
+        /// [license, kitty, holder, uses, price]
+        BreedingLicenseMinted {
+            license: u32,
+            kitty: KittyIndexOf<T>,
+            holder: T::AccountId,
+            uses: u32,
+            price: Option<BalanceOf<T>>,
+        },
+        /// A breeding license has been put up for sale or taken off the market
+        /// [license, holder, price]
+        BreedingLicensePriceSet {
+            license: u32,
+            holder: T::AccountId,
+            price: Option<BalanceOf<T>>,
+        },
+        /// A breeding license has been sold
+        /// [license, seller, buyer, price]
+        BreedingLicenseSold {
+            license: u32,
+            seller: T::AccountId,
+            buyer: T::AccountId,
+            price: BalanceOf<T>,
+        },
+        /// A breeding license has been used to breed with its kitty
+        /// [license, kitty, holder, uses_left]
+        BreedingLicenseUsed {
+            license: u32,
+            kitty: KittyIndexOf<T>,
+            holder: T::AccountId,
+            uses_left: u32,
+        },
+        /// A kitty has been bought in installments and escrowed until they are paid
+        /// [layaway, kitty, seller, buyer, price, payments]
+        LayawayStarted {
+            layaway: u32,
+            kitty: KittyIndexOf<T>,
+            seller: T::AccountId,
+            buyer: T::AccountId,
+            price: BalanceOf<T>,
+            payments: u32,
+        },
+        /// An installment of a layaway has been paid
+        /// [layaway, buyer, amount, payments_left]
+        InstallmentPaid {
+            layaway: u32,
+            buyer: T::AccountId,
+            amount: BalanceOf<T>,
+            payments_left: u32,
+        },
+        /// A layaway buyer missed an installment, so the kitty went back to the seller
+        /// [layaway, kitty, buyer, penalty]
+        LayawayDefaulted {
+            layaway: u32,
+            kitty: KittyIndexOf<T>,
+            buyer: T::AccountId,
+            penalty: BalanceOf<T>,
+        },
+        /// A community kitty grant has been proposed
+        /// [proposal, proposer, beneficiary, count]
+        GrantProposed {
+            proposal: u32,
+            proposer: T::AccountId,
+            beneficiary: T::AccountId,
+            count: u32,
+        },
+        /// A community kitty grant has been approved and its kitties minted
+        /// [proposal, beneficiary, count, cost]
+        GrantApproved {
+            proposal: u32,
+            beneficiary: T::AccountId,
+            count: u32,
+            cost: BalanceOf<T>,
+        },
+        /// A community kitty grant has been rejected
+        /// [proposal]
+        GrantRejected { proposal: u32 },
+        /// The owners of all kitties have been snapshotted, starting from the end of a block
+        /// [block, root, kitties]
+        OwnersSnapshotted {
+            block: T::BlockNumber,
+            root: [u8; 32],
+            kitties: u32,
+        },
+        /// A kitty has evolved to a new stage
+        /// [kitty, stage, dna]
+        KittyEvolved {
+            kitty: KittyIndexOf<T>,
+            stage: u8,
+            dna: Kitty,
+        },
+        /// An owner has let a game server act on its kitties
+        /// [owner, server, permissions, expires]
+        GameServerAuthorized {
+            owner: T::AccountId,
+            server: T::AccountId,
+            permissions: u8,
+            expires: T::BlockNumber,
+        },
+        /// An owner has revoked a game server's permissions
+        /// [owner, server]
+        GameServerRevoked {
+            owner: T::AccountId,
+            server: T::AccountId,
+        },
+        /// The collection's metadata has changed
+        /// [metadata]
+        ClassMetadataSet { metadata: CollectionMetadata },
+        /// A seller has changed whether a listed kitty can be returned
+        /// [kitty, accepted]
+        ReturnsAcceptedSet {
+            kitty: KittyIndexOf<T>,
+            accepted: bool,
+        },
+        /// A sold kitty can be returned until block `until` for `refund`
+        /// [sale, kitty, buyer, refund, until]
+        ReturnWindowOpened {
+            sale: u32,
+            kitty: KittyIndexOf<T>,
+            buyer: T::AccountId,
+            refund: BalanceOf<T>,
+            until: T::BlockNumber,
+        },
+        /// A buyer has returned a kitty to its seller
+        /// [sale, kitty, seller, buyer, refund]
+        KittyReturned {
+            sale: u32,
+            kitty: KittyIndexOf<T>,
+            seller: T::AccountId,
+            buyer: T::AccountId,
+            refund: BalanceOf<T>,
+        },
+        /// An airdrop to the owners of the kitties minted so far has started
+        /// [amount_per_kitty, kitties]
+        AirdropStarted {
+            amount_per_kitty: BalanceOf<T>,
+            kitties: KittyIndexOf<T>,
+        },
+        /// An airdrop has been paid out, or stopped early as the promo pot ran dry
+        /// [amount_per_kitty, paid]
+        AirdropFinished {
+            amount_per_kitty: BalanceOf<T>,
+            paid: u32,
+        },
+        /// Two accounts have swapped kitties and balance directly
+        /// [submitter, counterparty, given, taken]
+        TradeExecuted {
+            submitter: T::AccountId,
+            counterparty: T::AccountId,
+            given: Vec<KittyIndexOf<T>>,
+            taken: Vec<KittyIndexOf<T>>,
+        },
+    }
+
+    // Errors inform users that something went wrong.
+    // An error's code is its position here, so new errors must only be appended.
+    #[pallet::error]
+    pub enum Error<T> {
+        /// Error names should be descriptive.
+        NoneValue,
+        /// Errors should have helpful documentation associated with them.
+        StorageOverflow,
+        /// Kitty was generated with a DNA that already exists
+        DuplicateKitty,
+        /// Kitty not found
+        KittyNotFound,
+        /// Kitties need a valid (different from self) partner to breed
+        KittyPartnerMissing,
+        /// Kitties must have different genders to be able to breed
+        KittyGendersNotCompatible,
+        /// Kitty Id has overflowed KittyIndex
+        KittyIdOverflow,
+        /// Kitty is not listed on the exchange
+        KittyNotForSale,
+        /// Cannot buy own kitty
+        CannotBuyOwnKitty,
+        /// Could not create kitty
+        CouldNotCreateKitty,
+        /// Buyer and seller cannot refer their own purchase
+        InvalidReferrer,
+        /// Offspring would exceed the maximum generation
+        MaxGenerationReached,
+        /// The marketplace is closed for maintenance
+        TradingPaused,
+        /// Maintenance window must end after it starts
+        InvalidMaintenanceWindow,
+        /// Kitties cannot breed with their parents, children or full siblings
+        IncestuousBreeding,
+        /// The price oracle has no recent enough rate
+        StaleOraclePrice,
+        /// Kitty was bought too recently to be listed again
+        KittyInResaleCooldown,
+        /// Account has been barred from trading
+        AccountRestricted,
+        /// No import authority is configured
+        ImportsDisabled,
+        /// Export is not signed by the import authority
+        InvalidExportSignature,
+        /// Export has already been imported
+        ExportAlreadyImported,
+        /// Genome version is unknown to this runtime or its dna is longer than `DnaLength`
+        UnsupportedGenome,
+        /// Quests must have a target of at least one
+        InvalidQuest,
+        /// No more quests can be created until some are removed
+        TooManyQuests,
+        /// Quest does not exist
+        QuestNotFound,
+        /// Quest target has not been reached
+        QuestNotCompleted,
+        /// Quest reward has already been claimed
+        QuestAlreadyClaimed,
+        /// Purchase would exceed the buyer's spending limit for this period
+        SpendingLimitExceeded,
+        /// Kitty cannot be moved or listed until its transfer lock expires
+        KittyTransferLocked,
+        /// Unlock block must be in the future
+        InvalidUnlockBlock,
+        /// Floor purchases must be for between one and `MaxFloorBuy` kitties
+        InvalidFloorCount,
+        /// Fewer kitties are listed by others than were asked for
+        NotEnoughListings,
+        /// The cheapest listings cost more than the given maximum
+        FloorTotalExceeded,
+        /// Collection bids must be for at least one kitty
+        InvalidCollectionBid,
+        /// No collection bid with that id is open
+        CollectionBidNotFound,
+        /// Only the bidder can cancel a collection bid
+        NotCollectionBidder,
+        /// The kitty lacks the trait the collection bid asks for
+        KittyDoesNotMatchBid,
+        /// No fee rebates have vested yet
+        NoRebateToClaim,
+        /// Kitties can only breed during a breeding season
+        OutOfSeason,
+        /// Breeding seasons must be non-empty, sorted and must not overlap
+        InvalidBreedingSeasons,
+        /// More breeding seasons than `MaxBreedingSeasons`
+        TooManyBreedingSeasons,
+        /// Kitty is not in the shelter
+        KittyNotInShelter,
+        /// Kitty has been cloned or is a clone too many times over
+        CloneLimitReached,
+        /// Price changes can only be scheduled for future blocks
+        ScheduleInPast,
+        /// Too many price changes are scheduled for the block
+        TooManyScheduled,
+        /// Seller's reputation is too low to list at this price
+        InsufficientReputation,
+        /// Races must close in the future and have at least one winner
+        InvalidRace,
+        /// Race does not exist
+        RaceNotFound,
+        /// Race no longer takes entries
+        RaceClosed,
+        /// Race has not closed yet
+        RaceNotClosed,
+        /// Race has as many entrants as it can take
+        RaceFull,
+        /// Kitty has already entered the race
+        AlreadyEntered,
+        /// Sale is not waiting for confirmation
+        PendingSaleNotFound,
+        /// Only the seller can confirm a sale
+        NotSeller,
+        /// The collection is frozen
+        CollectionFrozen,
+        /// Coupon code has already been issued or redeemed
+        CouponExists,
+        /// Coupon code is unknown or has been redeemed
+        InvalidCoupon,
+        /// Coupons only apply to sales that settle immediately
+        CouponNotApplicable,
+        /// Listing price is outside the allowed bounds
+        PriceOutOfBounds,
+        /// Minimum listing price is above the maximum
+        InvalidPriceBounds,
+        /// Heir must be another account and the inactivity period must not be empty
+        InvalidHeir,
+        /// Caller is not the account's heir
+        NotHeir,
+        /// Account has been active within its inactivity period
+        OwnerStillActive,
+        /// Kitty has had as many offspring as its fertility allows
+        KittyInfertile,
+        /// Free balance cannot cover the funds held for the caller's bids and purchases
+        InsufficientFreeBalance,
+        /// Breeding license does not exist
+        LicenseNotFound,
+        /// Only the holder of a breeding license can use or sell it
+        NotLicenseHolder,
+        /// Breeding license is not for sale
+        LicenseNotForSale,
+        /// Breeding licenses must grant at least one use
+        InvalidLicenseUses,
+        /// Layaways take between two and `MaxInstallments` installments
+        InvalidInstallments,
+        /// Layaway does not exist
+        LayawayNotFound,
+        /// Only the buyer of a layaway can pay its installments
+        NotLayawayBuyer,
+        /// This block already holds `MaxMintsPerBlock` new kitties
+        MintRateLimited,
+        /// Grants mint between one and `MaxGrantKitties` kitties
+        InvalidGrant,
+        /// Grant proposal does not exist
+        GrantNotFound,
+        /// Kitty is not old enough for its next evolution stage
+        KittyTooYoung,
+        /// Kitty has reached its last evolution stage
+        KittyFullyEvolved,
+        /// Game servers need some known permissions, an expiry in the future and another account
+        InvalidGameServer,
+        /// Listing price was set less than `MinListingStability` blocks ago
+        ListingTooFresh,
+        /// Collection metadata fields are at most `MaxMetadataLength` bytes
+        MetadataTooLong,
+        /// Sale does not exist or its return period is over
+        ReturnNotFound,
+        /// Only the buyer of a sale can return its kitty
+        NotSaleBuyer,
+        /// Another airdrop is still being paid out
+        AirdropInProgress,
+        /// Trade terms are not the caller's, have expired, move no kitties or too many, or their
+        /// nonce is out of date
+        InvalidTrade,
+        /// Trade terms are not signed by the counterparty
+        InvalidTradeSignature,
+        /// The licensed kitty no longer belongs to the license's grantor
+        LicenseRevoked,
+    }
+
+    #[pallet::hooks]
+    impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
+        fn on_initialize(n: T::BlockNumber) -> Weight {
+            let expiries = PendingSaleExpiries::<T>::take(n);
+            let expired = expiries.len() as u64;
+            for sale_id in expiries {
+                if let Some(sale) = PendingSales::<T>::take(sale_id) {
+                    Self::release_funds(&sale.buyer, sale.price);
+                    if let Err(e) = NftModule::<T>::transfer(
+                        &Self::escrow_account_id(),
+                        &sale.seller,
+                        (Self::class_id(), sale.kitty),
+                    ) {
+                        debug::warn!(
+                            "Pending sale {} kitty could not be returned: {:?}",
+                            sale_id,
+                            e
+                        );
+                    }
+                    Self::deposit_event(Event::PendingSaleExpired {
+                        sale: sale_id,
+                        kitty: sale.kitty,
+                    });
+                }
+            }
+
+            let scheduled = ScheduledPriceChanges::<T>::take(n);
+            let applied = scheduled.len() as u64;
+            for (kitty_id, owner, new_price) in scheduled {
+                if let Err(e) = Self::do_set_price(&owner, kitty_id, new_price) {
+                    Self::deposit_event(Event::ScheduledPriceChangeFailed {
+                        kitty: kitty_id,
+                        owner,
+                        error: e,
+                    });
+                }
+            }
+
+            let deadlines = LayawayDeadlines::<T>::take(n);
+            let due = deadlines.len() as u64;
+            let mut defaulted = 0;
+            for layaway_id in deadlines {
+                match Self::layaways(layaway_id) {
+                    Some(layaway) if layaway.due == n => {
+                        Self::default_layaway(layaway_id, layaway);
+                        defaulted += 1;
+                    }
+                    _ => {}
+                }
+            }
+
+            let return_deadlines = ReturnDeadlines::<T>::take(n);
+            let returns_closed = return_deadlines.len() as u64;
+            for sale_id in return_deadlines {
+                if let Some(sale) = ReturnableSales::<T>::take(sale_id) {
+                    Self::release_funds(&sale.seller, sale.refund);
+                }
+            }
+
+            let airdropped = match Self::active_airdrop() {
+                Some(airdrop) => Self::continue_airdrop(airdrop),
+                None => 0,
+            };
+
+            let tax_pruned = Self::prune_tax_reports(n);
+
+            let period = T::OwnerSnapshotPeriod::get();
+            let mut pending = Self::pending_owner_snapshot();
+            if !period.is_zero() && (n % period).is_zero() && pending.is_none() {
+                pending = Some(OwnerSnapshotProgress {
+                    block: n.saturating_sub(One::one()),
+                    next: Zero::zero(),
+                    end: NftModule::<T>::next_token_id(Self::class_id()),
+                    kitties: 0,
+                    peaks: Vec::new(),
+                });
+            }
+            let snapshotted = match pending {
+                Some(progress) => Self::continue_owner_snapshot(progress),
+                None => 0,
+            };
+
+            for (start, end) in Self::breeding_seasons() {
+                if n == start {
+                    Self::deposit_event(Event::BreedingSeasonOpened { start, end });
+                } else if n == end {
+                    Self::deposit_event(Event::BreedingSeasonClosed { start, end });
+                }
+            }
+
+            // Reverting expired sales, applying the scheduled price changes, defaulting layaways,
+            // closing return periods, paying airdrops, pruning tax reports, taking owner
+            // snapshots, reading the breeding seasons, and reading and clearing the activity
+            // counters in `on_finalize`
+            T::DbWeight::get().reads_writes(
+                11 + 5 * applied
+                    + 4 * expired
+                    + due
+                    + 4 * defaulted
+                    + 2 * returns_closed
+                    + 3 * airdropped
+                    + tax_pruned
+                    + 2 * snapshotted,
+                6 + 3 * applied
+                    + 4 * expired
+                    + 5 * defaulted
+                    + 2 * returns_closed
+                    + 2 * airdropped
+                    + snapshotted
+                    + tax_pruned
+                    + 7,
+            )
+        }
+
+        fn on_finalize(_n: T::BlockNumber) {
+            let (mints, sales, volume) = BlockActivity::<T>::take();
+            if mints > 0 || sales > 0 {
+                Self::deposit_event(Event::BlockMarketDigest {
+                    mints,
+                    sales,
+                    volume,
+                });
+            }
+        }
+
+        fn on_runtime_upgrade() -> Weight {
+            let mut weight = 0;
+            if StorageVersion::<T>::get() == Releases::V1 {
+                weight += crate::migrations::migrate_to_v2::<T>();
+            }
+            if StorageVersion::<T>::get() == Releases::V2 {
+                weight += crate::migrations::migrate_to_v3::<T>();
+            }
+            if StorageVersion::<T>::get() == Releases::V3 {
+                weight += crate::migrations::migrate_to_v4::<T>();
+            }
+            if StorageVersion::<T>::get() == Releases::V4 {
+                weight += crate::migrations::migrate_to_v5::<T>();
+            }
+            if StorageVersion::<T>::get() == Releases::V5 {
+                weight += crate::migrations::migrate_to_v6::<T>();
+            }
+            if StorageVersion::<T>::get() == Releases::V6 {
+                weight += crate::migrations::migrate_to_v7::<T>();
+            }
+            if StorageVersion::<T>::get() == Releases::V7 {
+                weight += crate::migrations::migrate_to_v8::<T>();
+            }
+            if StorageVersion::<T>::get() == Releases::V8 {
+                weight += crate::migrations::migrate_to_v9::<T>();
+            }
+            weight
+        }
+
+        fn offchain_worker(block_number: T::BlockNumber) {
+            if let Err(e) = Self::notify_webhook(block_number) {
+                debug::warn!("Kitties webhook notification failed: {:?}", e);
+            }
+        }
+    }
+
+    impl<T: Config> Pallet<T> {
+        /// Deposit an event, indexed under the topics `event_topics` finds for it.
+        pub(super) fn deposit_event(event: Event<T>) {
+            let topics = Self::event_topics(&event);
+            let event = <<T as Config>::Event as From<Event<T>>>::from(event);
+            let event =
+                <<T as Config>::Event as Into<<T as frame_system::Config>::Event>>::into(event);
+            frame_system::Module::<T>::deposit_event_indexed(&topics, event)
+        }
+
+        /// Topic under which every marketplace event about `kitty_id` is indexed.
+        pub fn kitty_topic(kitty_id: KittyIndexOf<T>) -> T::Hash {
+            T::Hashing::hash_of(&(b"kitties/kitty", kitty_id))
+        }
+
+        /// Topic under which every marketplace event about `seller`'s listings and sales is
+        /// indexed.
+        pub fn seller_topic(seller: &T::AccountId) -> T::Hash {
+            T::Hashing::hash_of(&(b"kitties/seller", seller))
+        }
+
+        fn event_topics(event: &Event<T>) -> Vec<T::Hash> {
+            match event {
+                Event::KittyListed { kitty, owner, .. }
+                | Event::KittyRepriced { kitty, owner, .. }
+                | Event::KittyDelisted { kitty, owner }
+                | Event::KittyReferencePriceUpdated { kitty, owner, .. }
+                | Event::PriceChangeScheduled { kitty, owner, .. }
+                | Event::ScheduledPriceChangeFailed { kitty, owner, .. }
+                | Event::KittySold {
+                    kitty,
+                    seller: owner,
+                    ..
+                }
+                | Event::SaleAwaitingConfirmation {
+                    kitty,
+                    seller: owner,
+                    ..
+                }
+                | Event::CollectionBidFilled {
+                    kitty,
+                    seller: owner,
+                    ..
+                }
+                | Event::KittyReturned {
+                    kitty,
+                    seller: owner,
+                    ..
+                } => vec![Self::kitty_topic(*kitty), Self::seller_topic(owner)],
+                Event::LayawayStarted { kitty, seller, .. } => {
+                    vec![Self::kitty_topic(*kitty), Self::seller_topic(seller)]
+                }
+                Event::PendingSaleExpired { kitty, .. }
+                | Event::LayawayDefaulted { kitty, .. }
+                | Event::SalesTaxPaid { kitty, .. }
+                | Event::ReferralPaid { kitty, .. }
+                | Event::CouponRedeemed { kitty, .. }
+                | Event::ReturnWindowOpened { kitty, .. } => vec![Self::kitty_topic(*kitty)],
+                _ => Vec::new(),
+            }
+        }
+
+        /// POST this block's sales to the configured webhook, SCALE-encoded as
+        /// `(block_number, Vec<Event>)`. Does nothing unless the node has a webhook URL set.
+        fn notify_webhook(block_number: T::BlockNumber) -> Result<(), http::Error> {
+            let url = match StorageValueRef::persistent(WEBHOOK_URL_KEY).get::<Vec<u8>>() {
+                Some(Some(url)) => url,
+                _ => return Ok(()),
+            };
+            let url = sp_std::str::from_utf8(&url).map_err(|_| http::Error::Unknown)?;
+
+            let sales = frame_system::Module::<T>::events()
+                .into_iter()
+                .filter_map(|record| <T as Config>::Event::from(record.event).try_into().ok())
+                .filter(|event| matches!(event, Event::KittySold { .. }))
+                .collect::<Vec<Event<T>>>();
+            if sales.is_empty() {
+                return Ok(());
+            }
+
+            let deadline =
+                sp_io::offchain::timestamp().add(Duration::from_millis(WEBHOOK_TIMEOUT_MS));
+            let pending = http::Request::post(url, vec![(block_number, sales).encode()])
+                .add_header("Content-Type", "application/octet-stream")
+                .deadline(deadline)
+                .send()
+                .map_err(|_| http::Error::IoError)?;
+            let response = pending
+                .try_wait(deadline)
+                .map_err(|_| http::Error::DeadlineReached)??;
+            if response.code != 200 {
+                return Err(http::Error::Unknown);
+            }
+            Ok(())
+        }
+
+        /// The account that collects marketplace fees.
+        pub fn account_id() -> T::AccountId {
+            T::ModuleId::get().into_account()
+        }
+
+        /// The account holding fee rebates until they are claimed.
+        pub fn rebate_account_id() -> T::AccountId {
+            T::ModuleId::get().into_sub_account(b"rebates")
+        }
+
+        /// The account subsidising purchases made with `PriceSubsidy` coupons.
+        pub fn promo_account_id() -> T::AccountId {
+            T::ModuleId::get().into_sub_account(b"promo")
+        }
+
+        /// The account holding kitties bought in installments until they are paid.
+        pub fn escrow_account_id() -> T::AccountId {
+            T::ModuleId::get().into_sub_account(b"escrow")
+        }
+
+        /// The account owning sheltered kitties.
+        pub fn shelter_account_id() -> T::AccountId {
+            T::ModuleId::get().into_sub_account(b"shelter")
+        }
+
+        /// Up to `limit` listings and up to `limit` collection bids, for hydrating a market view
+        /// in one call.
+        ///
+        /// Native listings come first, cheapest first, followed by listings priced in the
+        /// reference unit by kitty id. Bids are best first, then oldest first.
+        pub fn order_book(limit: u32) -> OrderBookOf<T> {
+            let limit = limit as usize;
+            let mut listings = PriceIndex::<T>::iter()
+                .filter_map(|(_, kitty_id, _)| {
+                    Self::kitty_exchange(kitty_id).map(
+                        |Listing::<T>(seller, price, denomination)| {
+                            (kitty_id, seller, price, denomination)
+                        },
+                    )
+                })
+                .take(limit)
+                .collect::<Vec<_>>();
+            if listings.len() < limit {
+                let mut by_reference = KittyExchange::<T>::iter_prefix(Self::class_id())
+                    .filter_map(|(kitty_id, listing)| match listing {
+                        Some(Listing::<T>(seller, price, Denomination::Reference)) => {
+                            Some((kitty_id, seller, price, Denomination::Reference))
+                        }
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                by_reference.sort_by_key(|(kitty_id, ..)| *kitty_id);
+                by_reference.truncate(limit - listings.len());
+                listings.append(&mut by_reference);
+            }
+
+            let mut bids = CollectionBids::<T>::iter().collect::<Vec<_>>();
+            bids.sort_by(|(a_id, a), (b_id, b)| b.max_price.cmp(&a.max_price).then(a_id.cmp(b_id)));
+            bids.truncate(limit);
+
+            (listings, bids)
+        }
+
+        /// Hash of a kitty's dna and, `depth` generations back, those of its ancestors. Burned
+        /// ancestors count by id alone.
+        pub fn lineage_hash(kitty_id: KittyIndexOf<T>, depth: u32) -> [u8; 32] {
+            let dna = Self::kitty_of(kitty_id).map(|(_, kitty)| kitty);
+            match Self::kitty_parents(kitty_id) {
+                Some((first, second)) if depth > 0 => {
+                    let first = Self::lineage_hash(first, depth - 1);
+                    let second = Self::lineage_hash(second, depth - 1);
+                    (kitty_id, dna, first, second).using_encoded(blake2_256)
+                }
+                _ => (kitty_id, dna).using_encoded(blake2_256),
+            }
+        }
+
+        /// Merkle leaf of `owner` holding `kitty_id` in owner snapshots.
+        pub fn owner_leaf(kitty_id: KittyIndexOf<T>, owner: &T::AccountId) -> [u8; 32] {
+            (kitty_id, owner).using_encoded(blake2_256)
+        }
+
+        /// Remove up to `MaxTaxPrunesPerBlock` tax reports of eras that are no longer kept as of
+        /// block `n`, oldest era first. Finding an era empty counts towards the limit too.
+        /// Returns the reports removed and eras found empty.
+        fn prune_tax_reports(n: T::BlockNumber) -> u64 {
+            let era_length = T::TaxEraLength::get();
+            let history = T::BlockNumber::from(T::TaxHistory::get());
+            let kept_from = match n.checked_div(&era_length) {
+                Some(era) if era >= history => era + One::one() - history,
+                _ => return 0,
+            };
+            let mut era = NextTaxPruneEra::<T>::get();
+            let limit = T::MaxTaxPrunesPerBlock::get() as usize;
+            let mut pruned = 0;
+            while era < kept_from && pruned < limit {
+                let removed = TaxReports::<T>::drain_prefix(era)
+                    .take(limit - pruned)
+                    .count();
+                if pruned + removed < limit {
+                    era += One::one();
+                }
+                pruned += removed.max(1);
+            }
+            NextTaxPruneEra::<T>::put(era);
+            pruned as u64
+        }
+
+        /// Read up to `MaxSnapshotKittiesPerBlock` more kitties into an owner snapshot, storing
+        /// it after its last kitty. Returns the kitty ids read.
+        fn continue_owner_snapshot(mut progress: OwnerSnapshotProgressOf<T>) -> u64 {
+            let end = progress.end.min(
+                progress
+                    .next
+                    .saturating_add(T::MaxSnapshotKittiesPerBlock::get().into()),
+            );
+            let buffer = 1 - OwnerSnapshotBuffer::<T>::get();
+            let mut read = 0;
+            while progress.next < end {
+                match Self::kitty_of(progress.next) {
+                    Some((owner, _)) => {
+                        let leaf = Self::owner_leaf(progress.next, &owner);
+                        crate::merkle::append(&mut progress.peaks, leaf);
+                        SnapshotOwners::<T>::insert(
+                            buffer,
+                            progress.next,
+                            (owner, progress.kitties),
+                        );
+                        progress.kitties = progress.kitties.saturating_add(1);
+                    }
+                    None => SnapshotOwners::<T>::remove(buffer, progress.next),
+                }
+                progress.next += One::one();
+                read += 1;
+            }
+
+            if progress.next >= progress.end {
+                let snapshot = OwnerSnapshot {
+                    root: crate::merkle::fold(&progress.peaks),
+                    kitties: progress.kitties,
+                };
+                OwnerSnapshots::<T>::insert(progress.block, snapshot);
+                LatestOwnerSnapshot::<T>::put(progress.block);
+                OwnerSnapshotBuffer::<T>::put(buffer);
+                PendingOwnerSnapshot::<T>::kill();
+
+                Self::deposit_event(Event::OwnersSnapshotted {
+                    block: progress.block,
+                    root: snapshot.root,
+                    kitties: snapshot.kitties,
+                });
+            } else {
+                PendingOwnerSnapshot::<T>::put(progress);
+            }
+            read
+        }
+
+        /// Prove who owned `kitty_id` in the latest owner snapshot. Kitties are read into a
+        /// snapshot over several blocks, so one moved while it was taken may show either owner.
+        pub fn ownership_proof(kitty_id: KittyIndexOf<T>) -> Option<OwnershipProof<T::AccountId>> {
+            let snapshot = Self::owner_snapshots(Self::latest_owner_snapshot()?)?;
+            let buffer = OwnerSnapshotBuffer::<T>::get();
+            let (owner, index) = SnapshotOwners::<T>::get(buffer, kitty_id)?;
+            let mut leaves = SnapshotOwners::<T>::iter_prefix(buffer)
+                .map(|(id, (owner, index))| (index, Self::owner_leaf(id, &owner)))
+                .collect::<Vec<_>>();
+            leaves.sort_unstable_by_key(|(index, _)| *index);
+            let siblings = crate::merkle::proof(
+                leaves.into_iter().map(|(_, leaf)| leaf).collect(),
+                index as usize,
+            );
+            Some(OwnershipProof {
+                owner,
+                index,
+                kitties: snapshot.kitties,
+                siblings,
+            })
+        }
+
+        /// Up to `limit` kitties waiting in the shelter.
+        pub fn sheltered_kitties(limit: u32) -> Vec<KittyIndexOf<T>> {
+            Shelter::<T>::iter()
+                .map(|(kitty_id, _)| kitty_id)
+                .take(limit as usize)
+                .collect()
+        }
+
+        /// The kitties with ids from `start` up to `start + limit` and their owners, and the id
+        /// to start the next page from if any kitty may come after them. Burned ids are skipped,
+        /// so pages can hold fewer than `limit` kitties.
+        pub fn kitties_page(start: KittyIndexOf<T>, limit: u32) -> KittiesPageOf<T> {
+            let next_id = NftModule::<T>::next_token_id(Self::class_id());
+            let end = start.saturating_add(limit.into()).min(next_id);
+            let mut kitties = Vec::new();
+            let mut kitty_id = start;
+            while kitty_id < end {
+                if let Some((owner, kitty)) = Self::kitty_of(kitty_id) {
+                    kitties.push((kitty_id, owner, kitty));
+                }
+                kitty_id += One::one();
+            }
+            (kitties, if end < next_id { Some(end) } else { None })
+        }
+
+        /// The listing of a kitty, if it is for sale.
+        pub fn kitty_exchange(kitty_id: KittyIndexOf<T>) -> Option<Listing<T>> {
+            KittyExchange::<T>::get(Self::class_id(), kitty_id)
+        }
+
+        /// Look up a kitty and its owner by id alone.
+        pub fn kitty_of(kitty_id: KittyIndexOf<T>) -> Option<(T::AccountId, Kitty)> {
+            NftModule::<T>::tokens(Self::class_id(), kitty_id)
+                .map(|token| (token.owner, token.data))
+        }
+
+        pub fn kitties(owner: &T::AccountId, kitty_id: KittyIndexOf<T>) -> Option<Kitty> {
+            NftModule::<T>::tokens(Self::class_id(), kitty_id).and_then(|x| {
+                if x.owner == *owner {
+                    Some(x.data)
+                } else {
+                    None
+                }
+            })
+        }
+
+        /// Ensure `origin` is signed and return the signer, keeping track of its activity if it
+        /// has an heir. Failed calls count as activity too.
+        fn active_signer(origin: OriginFor<T>) -> Result<T::AccountId, DispatchError> {
+            let who = ensure_signed(origin)?;
+            if Heirs::<T>::contains_key(&who) {
+                LastActive::<T>::insert(&who, frame_system::Module::<T>::block_number());
+            }
+            Ok(who)
+        }
+
+        /// Ensure `count` more kitties can be minted in this block without going over
+        /// `MaxMintsPerBlock`.
+        fn ensure_mint_capacity(count: u32) -> DispatchResult {
+            let (mints, _, _) = BlockActivity::<T>::get();
+            ensure!(
+                mints.saturating_add(count) <= T::MaxMintsPerBlock::get(),
+                Error::<T>::MintRateLimited
+            );
+            Ok(())
+        }
+
+        /// Ensure the collection is not frozen.
+        fn ensure_not_frozen() -> DispatchResult {
+            ensure!(!Self::collection_frozen(), Error::<T>::CollectionFrozen);
+            Ok(())
+        }
+
+        /// Ensure the current block is outside the maintenance window.
+        fn ensure_trading_open() -> DispatchResult {
+            if let Some((start, end)) = Self::maintenance_window() {
+                let now = frame_system::Module::<T>::block_number();
+                ensure!(now < start || now >= end, Error::<T>::TradingPaused);
+            }
+            Ok(())
+        }
+
+        /// Ensure the current block is within a breeding season, if any are scheduled.
+        fn ensure_in_season() -> DispatchResult {
+            let seasons = Self::breeding_seasons();
+            if !seasons.is_empty() {
+                let now = frame_system::Module::<T>::block_number();
+                ensure!(
+                    seasons
+                        .iter()
+                        .any(|(start, end)| *start <= now && now < *end),
+                    Error::<T>::OutOfSeason
+                );
+            }
+            Ok(())
+        }
+
+        /// Ensure two kitties are not parent and child or full siblings, if configured.
+        fn ensure_not_related(
+            first: KittyIndexOf<T>,
+            second: KittyIndexOf<T>,
+        ) -> Result<(), Error<T>> {
+            if !T::ForbidIncest::get() {
+                return Ok(());
+            }
+
+            let first_parents = Self::kitty_parents(first);
+            let second_parents = Self::kitty_parents(second);
+            let is_parent_of = |parent, parents: Option<(_, _)>| matches!(parents, Some((a, b)) if a == parent || b == parent);
+            let siblings = match (first_parents, second_parents) {
+                (Some((a, b)), Some(other)) => other == (a, b) || other == (b, a),
+                _ => false,
+            };
+
+            ensure!(
+                !is_parent_of(first, second_parents)
+                    && !is_parent_of(second, first_parents)
+                    && !siblings,
+                Error::<T>::IncestuousBreeding
+            );
+            Ok(())
+        }
+
+        /// Ensure the marketplace is open and `who` is allowed to trade on it.
+        fn ensure_can_trade(who: &T::AccountId) -> DispatchResult {
+            Self::ensure_trading_open()?;
+            ensure!(
+                !TradingRestricted::<T>::contains_key(who),
+                Error::<T>::AccountRestricted
+            );
+            Ok(())
+        }
+
+        /// Count a sale between `seller` and `buyer`, flagging it if it reverses a recent trade
+        /// between them.
+        fn record_trade(kitty_id: KittyIndexOf<T>, seller: &T::AccountId, buyer: &T::AccountId) {
+            let now = frame_system::Module::<T>::block_number();
+            PairTrades::<T>::mutate(buyer, seller, |stats| {
+                let (count, _) = stats.unwrap_or((0, now));
+                *stats = Some((count.saturating_add(1), now));
+            });
+
+            let window = T::WashTradeWindow::get();
+            let reversed = matches!(
+                PairTrades::<T>::get(seller, buyer),
+                Some((_, last_trade)) if now.saturating_sub(last_trade) <= window
+            );
+            if !reversed {
+                return;
+            }
+
+            Self::deposit_event(Event::SuspiciousTrade {
+                kitty: kitty_id,
+                seller: seller.clone(),
+                buyer: buyer.clone(),
+            });
+            for account in [seller, buyer].iter() {
+                let flagged = SuspiciousTrades::<T>::mutate(*account, |count| {
+                    *count = count.saturating_add(1);
+                    *count
+                });
+                let threshold_reached = matches!(
+                    Self::wash_trade_threshold(),
+                    Some(threshold) if flagged >= threshold
+                );
+                if threshold_reached && !TradingRestricted::<T>::contains_key(*account) {
+                    TradingRestricted::<T>::insert(*account, ());
+                    Self::deposit_event(Event::TradingRestricted {
+                        account: (*account).clone(),
+                    });
+                }
+            }
+        }
+
+        /// Add a sale to the trade history of both parties, dropping their oldest trades beyond
+        /// `TradeHistoryLength`.
+        fn record_receipt(receipt: TradeReceiptOf<T>) {
+            for account in [&receipt.seller, &receipt.buyer].iter() {
+                TradeHistory::<T>::mutate(*account, |history| {
+                    history.push(receipt.clone());
+                    let excess = history
+                        .len()
+                        .saturating_sub(T::TradeHistoryLength::get() as usize);
+                    history.drain(..excess);
+                });
+            }
+        }
+
+        /// Count `goal` for `who` towards every running quest with that goal.
+        fn record_quest_progress(who: &T::AccountId, goal: QuestGoal) {
+            let now = frame_system::Module::<T>::block_number();
+            for (quest_id, quest) in Quests::<T>::iter() {
+                if quest.goal == goal && now < quest.ends_at {
+                    QuestProgress::<T>::mutate(quest_id, who, |(progress, _)| {
+                        *progress = progress.saturating_add(1).min(quest.target)
+                    });
+                }
+            }
+        }
+
+        /// Count `amount` against `who`'s spending limit for the current period, if it has one.
+        fn record_spending(who: &T::AccountId, amount: BalanceOf<T>) -> DispatchResult {
+            let limit = match Self::spending_limit(who) {
+                Some(limit) => limit,
+                None => return Ok(()),
+            };
+
+            let period = frame_system::Module::<T>::block_number()
+                .checked_div(&T::SpendingPeriod::get())
+                .unwrap_or_default();
+            Spent::<T>::try_mutate(who, |(spent_period, spent)| {
+                if *spent_period != period {
+                    *spent_period = period;
+                    *spent = Zero::zero();
+                }
+                *spent = spent.saturating_add(amount);
+                ensure!(*spent <= limit, Error::<T>::SpendingLimitExceeded);
+                Ok(())
+            })
+        }
+
+        /// Vest the rebates of past periods and move `rebates` to the current period.
+        fn vest_rebates(rebates: &mut (T::BlockNumber, BalanceOf<T>, BalanceOf<T>)) {
+            let period = frame_system::Module::<T>::block_number()
+                .checked_div(&T::RebatePeriod::get())
+                .unwrap_or_default();
+            let (rebate_period, pending, vested) = rebates;
+            if *rebate_period != period {
+                *rebate_period = period;
+                *vested = vested.saturating_add(sp_std::mem::take(pending));
+            }
+        }
+
+        /// Index of the current tax era.
+        pub(crate) fn tax_era() -> T::BlockNumber {
+            frame_system::Module::<T>::block_number()
+                .checked_div(&T::TaxEraLength::get())
+                .unwrap_or_default()
+        }
+
+        /// `who`'s sales and purchases in tax era `era`, zero if it is too old to be kept.
+        pub fn tax_report(who: &T::AccountId, era: T::BlockNumber) -> TaxReport<BalanceOf<T>> {
+            TaxReports::<T>::get(era, who)
+        }
+
+        /// Bytes the counterparty of `terms` signs: the terms after `TRADE_SIGNING_CONTEXT` and
+        /// the chain's genesis hash, so that the signature is good for this trade on this chain
+        /// only.
+        pub fn trade_payload(terms: &TradeTermsOf<T>) -> Vec<u8> {
+            let genesis_hash = frame_system::Module::<T>::block_hash(T::BlockNumber::zero());
+            (TRADE_SIGNING_CONTEXT, genesis_hash, terms).encode()
+        }
+
+        /// Traits of a kitty that `TraitPrices` follows: those of its first `DnaLength` dna
+        /// bytes, so that longer imported dna costs no more than `trait_prices_weight`.
+        fn priced_traits(kitty: &Kitty) -> impl Iterator<Item = (u8, u8)> + '_ {
+            kitty.traits().take(T::DnaLength::get() as usize)
+        }
+
+        /// Fold a sale into the moving average price of each priced trait of the kitty sold.
+        fn record_trait_prices(kitty: &Kitty, price: BalanceOf<T>) {
+            let smoothing: BalanceOf<T> = APPRAISAL_SALES.into();
+            for trait_key in Self::priced_traits(kitty) {
+                TraitPrices::<T>::mutate(trait_key, |entry| {
+                    *entry = Some(match *entry {
+                        Some((average, sales)) => (
+                            average - average / smoothing + price / smoothing,
+                            sales.saturating_add(1),
+                        ),
+                        None => (price, 1),
+                    });
+                });
+            }
+        }
+
+        /// Add a sale to the current period's price statistics, dropping the oldest period once
+        /// more than `StatsHistory` are kept.
+        fn record_price(price: BalanceOf<T>) {
+            let period = frame_system::Module::<T>::block_number()
+                .checked_div(&T::StatsPeriod::get())
+                .unwrap_or_default();
+
+            PriceStats::<T>::mutate(period, |candle| match candle {
+                Some(candle) => {
+                    candle.high = candle.high.max(price);
+                    candle.low = candle.low.min(price);
+                    candle.close = price;
+                    candle.volume = candle.volume.saturating_add(price);
+                    candle.sales = candle.sales.saturating_add(1);
+                }
+                None => {
+                    *candle = Some(PriceCandle {
+                        open: price,
+                        high: price,
+                        low: price,
+                        close: price,
+                        volume: price,
+                        sales: 1,
+                    });
+                    StatsPeriods::<T>::mutate(|periods| {
+                        periods.push(period);
+                        while periods.len() > T::StatsHistory::get() as usize {
+                            PriceStats::<T>::remove(periods.remove(0));
+                        }
+                    });
+                }
+            });
+        }
+
+        /// `PriceIndex` key of a price.
+        pub(crate) fn price_key(price: BalanceOf<T>) -> [u8; 16] {
+            UniqueSaturatedInto::<u128>::unique_saturated_into(price).to_be_bytes()
+        }
+
+        /// Put a kitty up for sale, returning the listing it replaces if it had one.
+        fn list(kitty_id: KittyIndexOf<T>, listing: Listing<T>) -> Option<Listing<T>> {
+            let previous = Self::delist(kitty_id);
+            if previous.is_none() {
+                AcceptsReturns::<T>::remove(kitty_id);
+            }
+            ListedAt::<T>::insert(kitty_id, frame_system::Module::<T>::block_number());
+            OwnerListings::<T>::insert(&listing.0, kitty_id, ());
+            if listing.2 == Denomination::Native {
+                PriceIndex::<T>::insert(Self::price_key(listing.1), kitty_id, ());
+            }
+            KittyExchange::<T>::insert(Self::class_id(), kitty_id, Some(listing));
+            previous
+        }
+
+        /// List `who`'s kitty at `new_price`, or delist it if `None`.
+        fn do_set_price(
+            who: &T::AccountId,
+            kitty_id: KittyIndexOf<T>,
+            new_price: Option<BalanceOf<T>>,
+        ) -> DispatchResult {
+            Self::ensure_not_frozen()?;
+            Self::ensure_owner(who, kitty_id)?;
+            // Delisting is always allowed
+            if let Some(new_price) = new_price {
+                Self::ensure_can_trade(who)?;
+                Self::ensure_resale_allowed(kitty_id)?;
+                Self::ensure_unlocked(kitty_id)?;
+                Self::ensure_listing_settled(kitty_id)?;
+                Self::ensure_price_in_bounds(new_price, Denomination::Native)?;
+                if let Some((threshold, min_score)) = Self::reputation_gate() {
+                    ensure!(
+                        new_price <= threshold || Self::reputation(who).score() >= min_score,
+                        Error::<T>::InsufficientReputation
+                    );
+                }
+            }
 
             match new_price {
-                Some(new_price) => KittyExchange::<T>::mutate_exists(kitty_id, |price| {
-                    *price = Some(Some(Listing::<T>(who.clone(), new_price)))
+                Some(new_price) => {
+                    let listing = Listing::<T>(who.clone(), new_price, Denomination::Native);
+                    match Self::list(kitty_id, listing) {
+                        Some(Listing::<T>(_, old_price, _)) => {
+                            Self::deposit_event(Event::KittyRepriced {
+                                kitty: kitty_id,
+                                old_price,
+                                new_price,
+                                owner: who.clone(),
+                            })
+                        }
+                        None => Self::deposit_event(Event::KittyListed {
+                            kitty: kitty_id,
+                            price: new_price,
+                            owner: who.clone(),
+                        }),
+                    }
+                }
+                None => {
+                    if Self::delist(kitty_id).is_some() {
+                        Self::deposit_event(Event::KittyDelisted {
+                            kitty: kitty_id,
+                            owner: who.clone(),
+                        });
+                    }
+                }
+            }
+
+            Ok(())
+        }
+
+        /// Schedule `who`'s kitty to be listed at `new_price`, or delisted if `None`, at the
+        /// start of block `at`.
+        fn schedule_price_change(
+            who: T::AccountId,
+            kitty_id: KittyIndexOf<T>,
+            new_price: Option<BalanceOf<T>>,
+            at: T::BlockNumber,
+        ) -> DispatchResult {
+            Self::ensure_not_frozen()?;
+            Self::ensure_owner(&who, kitty_id)?;
+            ensure!(
+                at > frame_system::Module::<T>::block_number(),
+                Error::<T>::ScheduleInPast
+            );
+
+            ScheduledPriceChanges::<T>::try_mutate(at, |scheduled| -> DispatchResult {
+                ensure!(
+                    (scheduled.len() as u32) < T::MaxScheduledPerBlock::get(),
+                    Error::<T>::TooManyScheduled
+                );
+                scheduled.push((kitty_id, who.clone(), new_price));
+                Ok(())
+            })?;
+
+            Self::deposit_event(Event::PriceChangeScheduled {
+                kitty: kitty_id,
+                owner: who,
+                price: new_price,
+                block: at,
+            });
+            Ok(())
+        }
+
+        /// Take a kitty off the market, returning its listing if it had one.
+        fn delist(kitty_id: KittyIndexOf<T>) -> Option<Listing<T>> {
+            let listing = KittyExchange::<T>::take(Self::class_id(), kitty_id);
+            if let Some(Listing::<T>(seller, price, denomination)) = &listing {
+                ListedAt::<T>::remove(kitty_id);
+                OwnerListings::<T>::remove(seller, kitty_id);
+                if *denomination == Denomination::Native {
+                    PriceIndex::<T>::remove(Self::price_key(*price), kitty_id);
+                }
+            }
+            listing
+        }
+
+        /// Withdraw a kitty given away from the market: its listing, and the seller's acceptance
+        /// of returns with it.
+        fn take_off_market(kitty_id: KittyIndexOf<T>) {
+            Self::delist(kitty_id);
+            AcceptsReturns::<T>::remove(kitty_id);
+        }
+
+        /// Deposit the event of a `ForceOrigin` action and record it in the audit log.
+        fn deposit_admin_event(event: Event<T>) {
+            let encoded = event.encode();
+            AuditLog::<T>::mutate(|log| {
+                log.push(AuditEntry {
+                    block: frame_system::Module::<T>::block_number(),
+                    action: encoded[0],
+                    payload_hash: blake2_256(&encoded),
+                });
+                let max = T::MaxAuditLogLength::get() as usize;
+                if log.len() > max {
+                    log.drain(..log.len() - max);
+                }
+            });
+            Self::deposit_event(event);
+        }
+
+        /// Ensure a listed kitty's price has stood for `MinListingStability` blocks.
+        fn ensure_listing_settled(kitty_id: KittyIndexOf<T>) -> DispatchResult {
+            if let Some(listed_at) = Self::listed_at(kitty_id) {
+                ensure!(
+                    frame_system::Module::<T>::block_number()
+                        >= listed_at.saturating_add(T::MinListingStability::get()),
+                    Error::<T>::ListingTooFresh
+                );
+            }
+            Ok(())
+        }
+
+        /// Ensure `who` owns a kitty and return it, reading the token only once.
+        fn ensure_owner(
+            who: &T::AccountId,
+            kitty_id: KittyIndexOf<T>,
+        ) -> Result<Kitty, DispatchError> {
+            match Self::kitty_of(kitty_id) {
+                Some((owner, kitty)) if owner == *who => Ok(kitty),
+                _ => Err(Error::<T>::KittyNotFound.into()),
+            }
+        }
+
+        /// Ensure `who` owns a kitty or is a game server its owner has given `permission` that
+        /// has not expired, and return the owner and the kitty.
+        fn ensure_owner_or_game_server(
+            who: &T::AccountId,
+            kitty_id: KittyIndexOf<T>,
+            permission: u8,
+        ) -> Result<(T::AccountId, Kitty), DispatchError> {
+            let (owner, kitty) = Self::kitty_of(kitty_id).ok_or(Error::<T>::KittyNotFound)?;
+            if owner == *who {
+                return Ok((owner, kitty));
+            }
+            match Self::game_server(&owner, who) {
+                Some((permissions, expires))
+                    if permissions & permission != 0
+                        && frame_system::Module::<T>::block_number() < expires =>
+                {
+                    Ok((owner, kitty))
+                }
+                _ => Err(Error::<T>::KittyNotFound.into()),
+            }
+        }
+
+        /// Reserve `amount` more of `who`'s free balance for the marketplace. Reserved funds
+        /// cannot back the locks of other pallets, so they are there when the market pays them.
+        pub(crate) fn hold_funds(who: &T::AccountId, amount: BalanceOf<T>) -> DispatchResult {
+            ensure!(
+                T::Currency::can_reserve(who, amount),
+                Error::<T>::InsufficientFreeBalance
+            );
+            T::Currency::reserve(who, amount)?;
+            MarketHolds::<T>::mutate(who, |held| *held = held.saturating_add(amount));
+            Ok(())
+        }
+
+        /// Give `amount` of the funds held for `who` back to its free balance.
+        fn release_funds(who: &T::AccountId, amount: BalanceOf<T>) {
+            T::Currency::unreserve(who, amount);
+            Self::forget_held(who, amount);
+        }
+
+        /// Pay `amount` of the funds held for `from` to `to`.
+        ///
+        /// Reserves can only be moved to existing accounts, so payments to any other account
+        /// go through `from`'s free balance. Callers run this in a storage transaction.
+        fn pay_held(
+            from: &T::AccountId,
+            to: &T::AccountId,
+            amount: BalanceOf<T>,
+        ) -> DispatchResult {
+            if T::Currency::total_balance(to).is_zero() {
+                T::Currency::unreserve(from, amount);
+                T::Currency::transfer(from, to, amount, ExistenceRequirement::AllowDeath)?;
+            } else {
+                let unpaid =
+                    T::Currency::repatriate_reserved(from, to, amount, BalanceStatus::Free)?;
+                ensure!(unpaid.is_zero(), Error::<T>::InsufficientFreeBalance);
+            }
+            Self::forget_held(from, amount);
+            Ok(())
+        }
+
+        /// Drop `amount` from the funds `MarketHolds` records for `who`.
+        fn forget_held(who: &T::AccountId, amount: BalanceOf<T>) {
+            let held = Self::market_hold(who).saturating_sub(amount);
+            if held.is_zero() {
+                MarketHolds::<T>::remove(who);
+            } else {
+                MarketHolds::<T>::insert(who, held);
+            }
+        }
+
+        /// Ensure a breeding license's kitty still belongs to the account that granted it.
+        fn ensure_license_granted(license: &BreedingLicenseOf<T>) -> DispatchResult {
+            ensure!(
+                matches!(Self::kitty_of(license.kitty), Some((owner, _)) if owner == license.grantor),
+                Error::<T>::LicenseRevoked
+            );
+            Ok(())
+        }
+
+        /// Ensure a kitty is not locked to its owner by `transfer_with_lock`.
+        fn ensure_unlocked(kitty_id: KittyIndexOf<T>) -> DispatchResult {
+            if let Some(unlock_block) = Self::transfer_lock(kitty_id) {
+                ensure!(
+                    frame_system::Module::<T>::block_number() >= unlock_block,
+                    Error::<T>::KittyTransferLocked
+                );
+                TransferLocks::<T>::remove(kitty_id);
+            }
+            Ok(())
+        }
+
+        /// Marketplace fee on selling `kitty_id` at `price` now. Kitties bought less than
+        /// `FlipFeeDecay` blocks ago pay up to `FlipFee` instead of `MarketplaceFee`.
+        pub fn marketplace_fee(kitty_id: KittyIndexOf<T>, price: BalanceOf<T>) -> BalanceOf<T> {
+            let fee = T::MarketplaceFee::get() * price;
+            let decay = T::FlipFeeDecay::get();
+            let held = match Self::purchased_at(kitty_id) {
+                Some(bought) => frame_system::Module::<T>::block_number().saturating_sub(bought),
+                None => return fee,
+            };
+            if held >= decay {
+                return fee;
+            }
+            let surcharge = (T::FlipFee::get() * price).saturating_sub(fee);
+            fee + Permill::from_rational_approximation(decay - held, decay) * surcharge
+        }
+
+        /// Ensure a kitty is not within its post-purchase cooldown.
+        fn ensure_resale_allowed(kitty_id: KittyIndexOf<T>) -> DispatchResult {
+            if let Some(locked_until) = Self::resale_locked_until(kitty_id) {
+                ensure!(
+                    frame_system::Module::<T>::block_number() >= locked_until,
+                    Error::<T>::KittyInResaleCooldown
+                );
+                ResaleLockedUntil::<T>::remove(kitty_id);
+            }
+            Ok(())
+        }
+
+        /// Check that a listing price is within `MinListingPrice` and `MaxListingPrice`, comparing
+        /// prices in other denominations at their current native value.
+        fn ensure_price_in_bounds(
+            price: BalanceOf<T>,
+            denomination: Denomination,
+        ) -> DispatchResult {
+            let (min, max) = (Self::min_listing_price(), Self::max_listing_price());
+            if min.is_none() && max.is_none() {
+                return Ok(());
+            }
+            let price = Self::native_price(price, denomination)?;
+            ensure!(
+                min.iter().all(|min| price >= *min) && max.iter().all(|max| price <= *max),
+                Error::<T>::PriceOutOfBounds
+            );
+            Ok(())
+        }
+
+        /// Convert a listing price to the native currency.
+        fn native_price(
+            price: BalanceOf<T>,
+            denomination: Denomination,
+        ) -> Result<BalanceOf<T>, Error<T>> {
+            match denomination {
+                Denomination::Native => Ok(price),
+                Denomination::Reference => {
+                    let (rate, updated_at) =
+                        T::PriceOracle::latest_rate().ok_or(Error::<T>::StaleOraclePrice)?;
+                    let age = frame_system::Module::<T>::block_number().saturating_sub(updated_at);
+                    ensure!(age <= T::MaxOracleAge::get(), Error::<T>::StaleOraclePrice);
+                    Ok(price.saturating_mul(rate))
+                }
+            }
+        }
+
+        /// Current price of creating a kitty.
+        pub fn mint_price() -> BalanceOf<T> {
+            MintPriceOverride::<T>::get().unwrap_or_else(T::MintPrice::get)
+        }
+
+        /// Charge `owner` the mint price and mint a new kitty with random dna to them.
+        fn do_create_kitty(
+            owner: &T::AccountId,
+        ) -> Result<(Kitty, KittyIndexOf<T>, [u8; 32]), DispatchError> {
+            let gender = if T::BalanceMintGenders::get() {
+                match Self::recent_mint_genders() {
+                    (males, females) if males > females => Some(Gender::Female),
+                    (males, females) if females > males => Some(Gender::Male),
+                    _ => None,
+                }
+            } else {
+                None
+            };
+            Self::ensure_mint_capacity(1)?;
+            let (kitty, seed_hash) = Kitty::new::<T>(owner.clone(), gender)?;
+            with_transaction_result(|| {
+                let payment = T::Currency::withdraw(
+                    owner,
+                    Self::mint_price(),
+                    WithdrawReasons::FEE,
+                    ExistenceRequirement::KeepAlive,
+                )?;
+                let kitty_id = Self::do_mint(owner, kitty.clone())?;
+                DnaSeeds::<T>::insert(kitty_id, seed_hash);
+                RecentMintGenders::<T>::mutate(|(males, females)| {
+                    match kitty.gender() {
+                        Gender::Male => *males = males.saturating_add(1),
+                        Gender::Female => *females = females.saturating_add(1),
+                    }
+                    if males.saturating_add(*females) >= GENDER_WINDOW {
+                        *males /= 2;
+                        *females /= 2;
+                    }
+                });
+                T::MintPriceDestination::on_unbalanced(payment);
+                Ok((kitty, kitty_id, seed_hash))
+            })
+        }
+
+        /// Mint `kitty` to `owner` and index its dna and traits.
+        pub(crate) fn do_mint(
+            owner: &T::AccountId,
+            kitty: Kitty,
+        ) -> Result<KittyIndexOf<T>, DispatchError> {
+            let (kitty_id, minted) = Self::mint_unrewarded(owner, kitty)?;
+            let interval = T::MilestoneInterval::get().unwrap_or_default();
+            if interval > 0 && minted % interval == 0 {
+                Self::reward_milestone(owner, kitty_id, minted);
+            }
+            Ok(kitty_id)
+        }
+
+        /// Mint a kitty like `do_mint` without paying milestone rewards, returning its id and
+        /// the number of kitties minted so far.
+        pub(crate) fn mint_unrewarded(
+            owner: &T::AccountId,
+            kitty: Kitty,
+        ) -> Result<(KittyIndexOf<T>, u64), DispatchError> {
+            let dna_hash = blake2_256(kitty.dna());
+            ensure!(
+                !DnaIndex::<T>::contains_key(dna_hash),
+                Error::<T>::DuplicateKitty
+            );
+            let traits = kitty.traits().collect::<Vec<_>>();
+            let kitty_id =
+                NftModule::<T>::mint(owner, Self::class_id(), Default::default(), kitty)?;
+            DnaIndex::<T>::insert(dna_hash, kitty_id);
+            for trait_key in traits {
+                TraitIndex::<T>::insert(trait_key, kitty_id, ());
+            }
+            LastMintedBy::<T>::insert(owner, kitty_id);
+            MintedAt::<T>::insert(kitty_id, frame_system::Module::<T>::block_number());
+            BlockActivity::<T>::mutate(|(mints, _, _)| *mints = mints.saturating_add(1));
+
+            let minted = TotalMinted::<T>::mutate(|minted| {
+                *minted = minted.saturating_add(1);
+                *minted
+            });
+            Ok((kitty_id, minted))
+        }
+
+        /// Badge a milestone kitty and pay its minter from the pallet account. Minting goes
+        /// ahead without the reward if the pallet account cannot cover it.
+        fn reward_milestone(owner: &T::AccountId, kitty_id: KittyIndexOf<T>, milestone: u64) {
+            let reward = match T::Currency::transfer(
+                &Self::account_id(),
+                owner,
+                T::MilestoneReward::get(),
+                ExistenceRequirement::KeepAlive,
+            ) {
+                Ok(()) => T::MilestoneReward::get(),
+                Err(_) => Zero::zero(),
+            };
+
+            MilestoneKitties::<T>::insert(kitty_id, milestone);
+            Self::deposit_event(Event::MilestoneKitty {
+                kitty: kitty_id,
+                milestone,
+                minter: owner.clone(),
+                reward,
+            });
+        }
+
+        /// Burn `owner`'s kitty and drop everything stored about it.
+        fn do_burn(
+            owner: &T::AccountId,
+            kitty_id: KittyIndexOf<T>,
+            kitty: &Kitty,
+        ) -> DispatchResult {
+            NftModule::<T>::burn(owner, (Self::class_id(), kitty_id))?;
+            DnaIndex::<T>::remove(blake2_256(kitty.dna()));
+            for trait_key in kitty.traits() {
+                TraitIndex::<T>::remove(trait_key, kitty_id);
+            }
+            Self::delist(kitty_id);
+            KittyGeneration::<T>::remove(kitty_id);
+            KittyParents::<T>::remove(kitty_id);
+            DnaSeeds::<T>::remove(kitty_id);
+            FailedPurchases::<T>::remove(kitty_id);
+            OffspringCount::<T>::remove(kitty_id);
+            MintedAt::<T>::remove(kitty_id);
+            EvolutionStage::<T>::remove(kitty_id);
+            CloneDepth::<T>::remove(kitty_id);
+            CloneOriginal::<T>::remove(kitty_id);
+            ResaleLockedUntil::<T>::remove(kitty_id);
+            PurchasedAt::<T>::remove(kitty_id);
+            CostBasis::<T>::remove(kitty_id);
+            AcceptsReturns::<T>::remove(kitty_id);
+            TransferLocks::<T>::remove(kitty_id);
+            Ok(())
+        }
+
+        /// Describe the error with `code`, the index reported in `DispatchError::Module`, as
+        /// `Name: documentation`. Empty if there is no such error.
+        pub fn describe_error(code: u8) -> Vec<u8> {
+            let error = match Error::<T>::metadata().get(code as usize) {
+                Some(error) => error,
+                None => return Vec::new(),
+            };
+
+            let mut description = Vec::new();
+            if let DecodeDifferent::Encode(name) = error.name {
+                description.extend_from_slice(name.as_bytes());
+            }
+            if let DecodeDifferent::Encode(lines) = error.documentation {
+                let mut separator: &[u8] = b":";
+                for line in lines
+                    .iter()
+                    .map(|line| line.trim())
+                    .filter(|line| !line.is_empty())
+                {
+                    description.extend_from_slice(separator);
+                    description.push(b' ');
+                    description.extend_from_slice(line.as_bytes());
+                    separator = b"";
+                }
+            }
+            description
+        }
+
+        /// SVG portrait of a kitty, or nothing if it does not exist.
+        #[cfg(feature = "render")]
+        pub fn render_kitty(kitty_id: KittyIndexOf<T>) -> Vec<u8> {
+            Self::kitty_of(kitty_id)
+                .map(|(_, kitty)| crate::render::render_svg(&kitty))
+                .unwrap_or_default()
+        }
+
+        /// ERC-721 style metadata JSON of a kitty, or nothing if it does not exist.
+        #[cfg(feature = "render")]
+        pub fn token_metadata_json(kitty_id: KittyIndexOf<T>) -> Vec<u8> {
+            Self::kitty_of(kitty_id)
+                .map(|(_, kitty)| {
+                    crate::render::metadata_json(
+                        kitty_id.unique_saturated_into(),
+                        &kitty,
+                        Self::kitty_generation(kitty_id),
+                    )
+                })
+                .unwrap_or_default()
+        }
+
+        /// Value a kitty from the recent sale prices of kitties sharing its traits: the average
+        /// of its traits' moving averages, each weighted by its number of sales up to
+        /// `APPRAISAL_SALES`. `None` if no kitty with any of its traits has sold.
+        pub fn appraise(kitty_id: KittyIndexOf<T>) -> Option<BalanceOf<T>> {
+            let (_, kitty) = Self::kitty_of(kitty_id)?;
+            let (mut total, mut weights) = (BalanceOf::<T>::zero(), 0u32);
+            for trait_key in Self::priced_traits(&kitty) {
+                if let Some((average, sales)) = Self::trait_price(trait_key) {
+                    let weight = sales.min(APPRAISAL_SALES);
+                    total = total.saturating_add(average.saturating_mul(weight.into()));
+                    weights += weight;
+                }
+            }
+            if weights == 0 {
+                return None;
+            }
+            Some(total / weights.into())
+        }
+
+        /// Up to `limit` kitties whose trait `trait_id` falls in bucket `value`.
+        pub fn kitties_with_trait(trait_id: u8, value: u8, limit: u32) -> Vec<KittyIndexOf<T>> {
+            TraitIndex::<T>::iter_prefix((trait_id, value))
+                .map(|(kitty_id, _)| kitty_id)
+                .take(limit as usize)
+                .collect()
+        }
+
+        /// Up to `limit` kitties within `max_distance` dna bits of `kitty_id`, closest first,
+        /// with their distance.
+        ///
+        /// Only kitties sharing a trait bucket with `kitty_id` are considered, and at most
+        /// `SIMILARITY_CANDIDATES` of those, spread evenly over its traits.
+        pub fn similar_kitties(
+            kitty_id: KittyIndexOf<T>,
+            max_distance: u32,
+            limit: u32,
+        ) -> Vec<(KittyIndexOf<T>, u32)> {
+            let kitty = match Self::kitty_of(kitty_id) {
+                Some((_, kitty)) => kitty,
+                None => return Vec::new(),
+            };
+            let per_trait = SIMILARITY_CANDIDATES / kitty.dna().len().max(1);
+
+            let mut candidates = BTreeSet::new();
+            for (trait_id, value) in kitty.traits() {
+                candidates.extend(
+                    TraitIndex::<T>::iter_prefix((trait_id, value))
+                        .map(|(candidate, _)| candidate)
+                        .filter(|candidate| *candidate != kitty_id)
+                        .take(per_trait),
+                );
+            }
+
+            let mut similar: Vec<_> = candidates
+                .into_iter()
+                .filter_map(|candidate| {
+                    let (_, other) = Self::kitty_of(candidate)?;
+                    Some((candidate, kitty.hamming_distance(&other)))
+                })
+                .filter(|(_, distance)| *distance <= max_distance)
+                .collect();
+            similar.sort_by_key(|(candidate, distance)| (*distance, *candidate));
+            similar.truncate(limit as usize);
+            similar
+        }
+
+        /// Buy a listed kitty at its asking price, returning the native amount paid.
+        fn do_buy(
+            who: &T::AccountId,
+            kitty_id: KittyIndexOf<T>,
+            referrer: Option<T::AccountId>,
+        ) -> Result<BalanceOf<T>, DispatchError> {
+            let Listing::<T>(owner, price, denomination) =
+                Self::kitty_exchange(kitty_id).ok_or(Error::<T>::KittyNotForSale)?;
+            ensure!(*who != owner, Error::<T>::CannotBuyOwnKitty);
+            let price = Self::native_price(price, denomination)?;
+            if let Some(referrer) = &referrer {
+                ensure!(
+                    referrer != who && *referrer != owner,
+                    Error::<T>::InvalidReferrer
+                );
+            }
+
+            with_transaction_result(|| {
+                Self::delist(kitty_id);
+                Self::record_spending(who, price)?;
+                match Self::high_value_threshold() {
+                    Some(threshold) if price > threshold => {
+                        Self::hold_sale(kitty_id, owner, who, price, referrer)?
+                    }
+                    _ => Self::settle_sale(kitty_id, &owner, who, price, referrer, false)?,
+                }
+
+                Ok(price)
+            })
+        }
+
+        /// Return a layaway's kitty to its seller and release the buyer's installments, less the
+        /// `LayawayPenalty` paid to the seller.
+        fn default_layaway(layaway_id: u32, layaway: LayawayOf<T>) {
+            Layaways::<T>::remove(layaway_id);
+            let paid = layaway.price.saturating_sub(
+                layaway
+                    .installment
+                    .saturating_mul(layaway.payments_left.into()),
+            );
+            let penalty = (T::LayawayPenalty::get() * layaway.price).min(paid);
+            let penalty = match with_transaction_result(|| {
+                Self::pay_held(&layaway.buyer, &layaway.seller, penalty)
+            }) {
+                Ok(()) => penalty,
+                Err(_) => Zero::zero(),
+            };
+            Self::release_funds(&layaway.buyer, paid - penalty);
+            if let Err(e) = NftModule::<T>::transfer(
+                &Self::escrow_account_id(),
+                &layaway.seller,
+                (Self::class_id(), layaway.kitty),
+            ) {
+                debug::warn!(
+                    "Layaway {} kitty could not be returned: {:?}",
+                    layaway_id,
+                    e
+                );
+            }
+
+            Self::deposit_event(Event::LayawayDefaulted {
+                layaway: layaway_id,
+                kitty: layaway.kitty,
+                buyer: layaway.buyer,
+                penalty,
+            });
+        }
+
+        /// Reserve `price` with the buyer, escrow the kitty and wait `ConfirmationPeriod` blocks
+        /// for the seller to confirm the sale. Callers take the kitty off the market first.
+        fn hold_sale(
+            kitty_id: KittyIndexOf<T>,
+            seller: T::AccountId,
+            buyer: &T::AccountId,
+            price: BalanceOf<T>,
+            referrer: Option<T::AccountId>,
+        ) -> DispatchResult {
+            Self::hold_funds(buyer, price)?;
+            NftModule::<T>::transfer(
+                &seller,
+                &Self::escrow_account_id(),
+                (Self::class_id(), kitty_id),
+            )?;
+
+            let sale_id = NextPendingSaleId::<T>::get();
+            let expires_at =
+                frame_system::Module::<T>::block_number() + T::ConfirmationPeriod::get();
+            PendingSales::<T>::insert(
+                sale_id,
+                PendingSale {
+                    kitty: kitty_id,
+                    seller: seller.clone(),
+                    buyer: buyer.clone(),
+                    price,
+                    referrer,
+                    expires_at,
+                },
+            );
+            PendingSaleExpiries::<T>::append(expires_at, sale_id);
+            NextPendingSaleId::<T>::put(sale_id.saturating_add(1));
+
+            Self::deposit_event(Event::SaleAwaitingConfirmation {
+                sale: sale_id,
+                kitty: kitty_id,
+                seller,
+                buyer: buyer.clone(),
+                price,
+                expires_at,
+            });
+            Ok(())
+        }
+
+        /// Whether a purchase failed because another buyer or a price change got there first,
+        /// i.e. the listing was gone or paying for it failed, rather than being invalid.
+        fn is_contention(error: &DispatchError) -> bool {
+            match (error, Error::<T>::KittyNotForSale.into()) {
+                (
+                    DispatchError::Module { index, error, .. },
+                    DispatchError::Module {
+                        index: own_index,
+                        error: not_for_sale,
+                        ..
+                    },
+                ) => *index != own_index || *error == not_for_sale,
+                _ => true,
+            }
+        }
+
+        /// Hand a kitty from `seller` to `buyer` for `price`, splitting off the marketplace fee
+        /// and the `referrer`'s cut of it. Callers take the kitty off the market first.
+        ///
+        /// The price is paid from funds held for `buyer` if `held` is set, and from its free
+        /// balance otherwise.
+        fn settle_sale(
+            kitty_id: KittyIndexOf<T>,
+            seller: &T::AccountId,
+            buyer: &T::AccountId,
+            price: BalanceOf<T>,
+            referrer: Option<T::AccountId>,
+            held: bool,
+        ) -> DispatchResult {
+            let pay = |to: &T::AccountId, amount| {
+                if held {
+                    Self::pay_held(buyer, to, amount)
+                } else {
+                    T::Currency::transfer(buyer, to, amount, T::BuyerExistenceRequirement::get())
+                }
+            };
+            NftModule::<T>::transfer(seller, buyer, (Self::class_id(), kitty_id))?;
+            ResaleLockedUntil::<T>::insert(
+                kitty_id,
+                frame_system::Module::<T>::block_number() + T::ResaleCooldown::get(),
+            );
+
+            let fee = Self::marketplace_fee(kitty_id, price);
+            PurchasedAt::<T>::insert(kitty_id, frame_system::Module::<T>::block_number());
+            let referral_cut = match referrer {
+                Some(_) => T::ReferralShare::get() * fee,
+                None => Zero::zero(),
+            };
+            let tax = T::TaxHandler::sales_tax(
+                Self::jurisdiction(seller),
+                Self::jurisdiction(buyer),
+                price,
+            )
+            .map(|(collector, tax)| (collector, tax.min(price - fee)));
+            let tax_amount = tax.as_ref().map(|(_, tax)| *tax).unwrap_or_else(Zero::zero);
+            let proceeds = price - fee - tax_amount;
+            pay(seller, proceeds)?;
+            if let Some((collector, tax)) = tax {
+                if !tax.is_zero() {
+                    pay(&collector, tax)?;
+                    Self::deposit_event(Event::SalesTaxPaid {
+                        kitty: kitty_id,
+                        amount: tax,
+                        collector,
+                    });
+                }
+            }
+            let rebate = T::FeeRebate::get() * (fee - referral_cut);
+            pay(&Self::account_id(), fee - referral_cut - rebate)?;
+            if !rebate.is_zero() {
+                pay(&Self::rebate_account_id(), rebate)?;
+                FeeRebates::<T>::mutate(buyer, |rebates| {
+                    Self::vest_rebates(rebates);
+                    rebates.1 = rebates.1.saturating_add(rebate);
+                });
+            }
+
+            if let Some(referrer) = referrer {
+                if !referral_cut.is_zero() {
+                    pay(&referrer, referral_cut)?;
+                    ReferralEarnings::<T>::mutate(&referrer, |earnings| {
+                        *earnings = earnings.saturating_add(referral_cut)
+                    });
+                    Self::deposit_event(Event::ReferralPaid {
+                        kitty: kitty_id,
+                        amount: referral_cut,
+                        referrer,
+                    });
+                }
+            }
+
+            Self::deposit_event(Event::KittySold {
+                kitty: kitty_id,
+                price,
+                seller: seller.clone(),
+                buyer: buyer.clone(),
+            });
+            Self::record_trade(kitty_id, seller, buyer);
+            Self::record_receipt(TradeReceipt {
+                kitty: kitty_id,
+                seller: seller.clone(),
+                buyer: buyer.clone(),
+                price,
+                block: frame_system::Module::<T>::block_number(),
+            });
+            Reputations::<T>::mutate(seller, |reputation| {
+                reputation.sales = reputation.sales.saturating_add(1)
+            });
+            Self::record_quest_progress(buyer, QuestGoal::Buy);
+            Self::record_quest_progress(seller, QuestGoal::Sell);
+            BlockActivity::<T>::mutate(|(_, sales, volume)| {
+                *sales = sales.saturating_add(1);
+                *volume = volume.saturating_add(price);
+            });
+            Self::record_price(price);
+            let cost_basis =
+                CostBasis::<T>::mutate(kitty_id, |basis| sp_std::mem::replace(basis, price));
+            let era = Self::tax_era();
+            TaxReports::<T>::mutate(era, seller, |report| {
+                report.proceeds = report.proceeds.saturating_add(proceeds);
+                report.cost_basis = report.cost_basis.saturating_add(cost_basis);
+            });
+            TaxReports::<T>::mutate(era, buyer, |report| {
+                report.purchases = report.purchases.saturating_add(price);
+            });
+            if let Some((_, kitty)) = Self::kitty_of(kitty_id) {
+                Self::record_trait_prices(&kitty, price);
+            }
+            if AcceptsReturns::<T>::take(kitty_id).as_ref() == Some(seller)
+                && !T::ReturnPeriod::get().is_zero()
+            {
+                // Held out of the proceeds just paid, so the seller can always cover it
+                let refund = price
+                    .saturating_sub(T::RestockingFee::get() * price)
+                    .min(proceeds);
+                Self::open_return_window(
+                    kitty_id, seller, buyer, refund, price, proceeds, cost_basis,
+                )?;
+            }
+            Ok(())
+        }
+
+        /// Pay the owners of up to `MaxAirdropsPerBlock` more kitties of an airdrop, finishing it
+        /// after its last kitty or once the promo pot cannot pay. Returns the kitties visited.
+        fn continue_airdrop(mut airdrop: AirdropOf<T>) -> u64 {
+            let end = airdrop.end.min(
+                airdrop
+                    .next
+                    .saturating_add(T::MaxAirdropsPerBlock::get().into()),
+            );
+            let mut visited = 0;
+            let mut dry = false;
+            while airdrop.next < end {
+                if let Some((owner, _)) = Self::kitty_of(airdrop.next) {
+                    if T::Currency::transfer(
+                        &Self::promo_account_id(),
+                        &owner,
+                        airdrop.amount_per_kitty,
+                        ExistenceRequirement::KeepAlive,
+                    )
+                    .is_err()
+                    {
+                        dry = true;
+                        break;
+                    }
+                    airdrop.paid = airdrop.paid.saturating_add(1);
+                }
+                airdrop.next += One::one();
+                visited += 1;
+            }
+
+            if dry || airdrop.next >= airdrop.end {
+                ActiveAirdrop::<T>::kill();
+                Self::deposit_event(Event::AirdropFinished {
+                    amount_per_kitty: airdrop.amount_per_kitty,
+                    paid: airdrop.paid,
+                });
+            } else {
+                ActiveAirdrop::<T>::put(airdrop);
+            }
+            visited
+        }
+
+        /// Hold `refund` of the seller's funds and let the buyer return the kitty for it until
+        /// `ReturnPeriod` blocks from now. `price`, `proceeds` and the kitty's previous
+        /// `cost_basis` are what the sale added to the tax reports and took from `CostBasis`.
+        fn open_return_window(
+            kitty_id: KittyIndexOf<T>,
+            seller: &T::AccountId,
+            buyer: &T::AccountId,
+            refund: BalanceOf<T>,
+            price: BalanceOf<T>,
+            proceeds: BalanceOf<T>,
+            cost_basis: BalanceOf<T>,
+        ) -> DispatchResult {
+            Self::hold_funds(seller, refund)?;
+            let sale_id = NextReturnableSaleId::<T>::get();
+            let until = frame_system::Module::<T>::block_number() + T::ReturnPeriod::get();
+            ReturnableSales::<T>::insert(
+                sale_id,
+                ReturnableSale {
+                    kitty: kitty_id,
+                    seller: seller.clone(),
+                    buyer: buyer.clone(),
+                    refund,
+                    until,
+                    era: Self::tax_era(),
+                    price,
+                    proceeds,
+                    cost_basis,
+                },
+            );
+            ReturnDeadlines::<T>::append(until, sale_id);
+            NextReturnableSaleId::<T>::put(sale_id.saturating_add(1));
+
+            Self::deposit_event(Event::ReturnWindowOpened {
+                sale: sale_id,
+                kitty: kitty_id,
+                buyer: buyer.clone(),
+                refund,
+                until,
+            });
+            Ok(())
+        }
+
+        /// Check that two kitties can breed now, returning them and the generation of the older
+        /// one. Ownership by `owner` is only checked if it is given.
+        fn breeding_pair(
+            owner: Option<&T::AccountId>,
+            first_parent: KittyIndexOf<T>,
+            second_parent: KittyIndexOf<T>,
+        ) -> Result<(Kitty, Kitty, u32), DispatchError> {
+            Self::ensure_in_season()?;
+
+            // Ensure that kitties exist
+            let kitty = |kitty_id| match owner {
+                Some(owner) => Self::ensure_owner(owner, kitty_id),
+                None => Self::kitty_of(kitty_id)
+                    .map(|(_, kitty)| kitty)
+                    .ok_or_else(|| Error::<T>::KittyNotFound.into()),
+            };
+            let (first, second) = (kitty(first_parent)?, kitty(second_parent)?);
+            Self::ensure_not_related(first_parent, second_parent)?;
+            ensure!(
+                Self::offspring_count(first_parent) < first.fertility()
+                    && Self::offspring_count(second_parent) < second.fertility(),
+                Error::<T>::KittyInfertile
+            );
+
+            // Ensure the offspring stays within the generation cap
+            let parent_generation =
+                Self::kitty_generation(first_parent).max(Self::kitty_generation(second_parent));
+            if let Some(max_generation) = T::MaxGeneration::get() {
+                ensure!(
+                    parent_generation.saturating_add(1) <= max_generation,
+                    Error::<T>::MaxGenerationReached
+                );
+            }
+
+            Kitty::ensure_can_breed::<T>(&first, &second)?;
+            Ok((first, second, parent_generation))
+        }
+
+        /// Breed a pair checked by `breeding_pair` into a kitty owned by `who`, who pays the
+        /// breeding fee.
+        fn do_breed(
+            who: &T::AccountId,
+            first_parent: KittyIndexOf<T>,
+            second_parent: KittyIndexOf<T>,
+            (first_parent_struct, second_parent_struct, parent_generation): (Kitty, Kitty, u32),
+        ) -> DispatchResult {
+            let generation = parent_generation.saturating_add(1);
+
+            let (kitty, twin) = Kitty::breed::<T>(&first_parent_struct, &second_parent_struct)?;
+            Self::ensure_mint_capacity(if twin.is_some() { 2 } else { 1 })?;
+            let (current_id, twin_id) = with_transaction_result(|| {
+                T::Currency::transfer(
+                    who,
+                    &Self::account_id(),
+                    T::BreedingFee::convert(parent_generation),
+                    ExistenceRequirement::KeepAlive,
+                )?;
+
+                // Insert the created kitties into storage
+                let mint = |(kitty, seed_hash): SeededKitty| {
+                    let kitty_id = Self::do_mint(who, kitty)?;
+                    KittyGeneration::<T>::insert(kitty_id, generation);
+                    KittyParents::<T>::insert(kitty_id, (first_parent, second_parent));
+                    DnaSeeds::<T>::insert(kitty_id, seed_hash);
+                    Ok::<_, DispatchError>(kitty_id)
+                };
+                let current_id = mint(kitty.clone())?;
+                let twin_id = twin.clone().map(mint).transpose()?;
+                let offspring = if twin_id.is_some() { 2 } else { 1 };
+                for parent in [first_parent, second_parent].iter() {
+                    OffspringCount::<T>::mutate(parent, |count| {
+                        *count = count.saturating_add(offspring)
+                    });
+                }
+                Ok((current_id, twin_id))
+            })?;
+            Self::record_quest_progress(who, QuestGoal::Breed);
+
+            // Emit an event.
+            let (kitty, seed_hash) = kitty;
+            Self::deposit_event(Event::KittyBred {
+                dna: kitty,
+                kitty: current_id,
+                owner: who.clone(),
+                seed_hash,
+            });
+            if let (Some((twin, seed_hash)), Some(twin_id)) = (twin, twin_id) {
+                Self::deposit_event(Event::KittyBred {
+                    dna: twin,
+                    kitty: twin_id,
+                    owner: who.clone(),
+                    seed_hash,
+                });
+                Self::deposit_event(Event::KittyTwinsBorn {
+                    kitty: current_id,
+                    twin: twin_id,
+                    owner: who.clone(),
+                });
+            }
+            Ok(())
+        }
+
+        /// Run every check `breed_kitty` makes on a pair of kitties without breeding them, and
+        /// ownership by `owner` if it is given.
+        pub fn can_breed(
+            owner: Option<T::AccountId>,
+            first_parent: KittyIndexOf<T>,
+            second_parent: KittyIndexOf<T>,
+        ) -> DispatchResult {
+            Self::breeding_pair(owner.as_ref(), first_parent, second_parent).map(|_| ())
+        }
+
+        /// Whether `kitty_id`'s dna was derived from `seed_preimage`, the SCALE encoded seed
+        /// whose hash is in its `KittyCreated` or `KittyBred` event.
+        ///
+        /// Bred kitties can only be verified while both parents exist. Kitties that were not
+        /// created or bred, like clones and imports, never verify. Evolved kitties verify with
+        /// their evolution stages replayed on the derived dna.
+        pub fn verify_dna(kitty_id: KittyIndexOf<T>, seed_preimage: Vec<u8>) -> bool {
+            let kitty = match Self::kitty_of(kitty_id) {
+                Some((_, kitty)) => kitty,
+                None => return false,
+            };
+            if Self::dna_seed(kitty_id) != Some(blake2_256(&seed_preimage)) {
+                return false;
+            }
+            let parents = match Self::kitty_parents(kitty_id) {
+                Some((first, second)) => match (Self::kitty_of(first), Self::kitty_of(second)) {
+                    (Some((_, first)), Some((_, second))) => Some((first, second)),
+                    _ => return false,
+                },
+                None => None,
+            };
+
+            let parents = parents.as_ref().map(|(first, second)| (first, second));
+            let dna = Kitty::derive_dna::<T>(parents, &seed_preimage, kitty.dna().len());
+            let evolved = (0..Self::evolution_stage(kitty_id)).fold(
+                Kitty(Genome {
+                    version: kitty.0.version,
+                    dna,
                 }),
-                None => KittyExchange::<T>::remove(kitty_id),
+                |kitty, _| kitty.evolved(),
+            );
+            evolved == kitty
+        }
+
+        /// Preview the offspring of two existing kitties without changing any state.
+        ///
+        /// Ownership is not checked, so UIs can evaluate any pair on the chain. Every other
+        /// rule of `breed` applies.
+        pub fn simulate_breed(
+            first_parent: KittyIndexOf<T>,
+            second_parent: KittyIndexOf<T>,
+            seed: H256,
+        ) -> Result<Kitty, DispatchError> {
+            let (first, second, _) = Self::breeding_pair(None, first_parent, second_parent)?;
+            // Same combination as `breed`, with the caller's seed standing in for randomness
+            Ok(Kitty::generate::<T>(
+                Some((&first, &second)),
+                &seed.encode(),
+            ))
+        }
+    }
+
+    // Dispatchable functions allows users to interact with the pallet and invoke state changes.
+    // These functions materialize as "extrinsics", which are often compared to transactions.
+    // Dispatchable functions must be annotated with a weight and must return a DispatchResult.
+    #[pallet::call]
+    impl<T: Config> Pallet<T> {
+        /// An example dispatchable that takes a singles value as a parameter, writes the value to
+        /// storage and emits an event. This function must be dispatched by a signed extrinsic.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(3, 8 + T::DnaLength::get() as u64) + quest_weight::<T>())]
+        pub fn create_kitty(origin: OriginFor<T>) -> DispatchResultWithPostInfo {
+            // Check that the extrinsic was signed and get the signer.
+            // This function will return an error if the extrinsic is not signed.
+            // https://substrate.dev/docs/en/knowledgebase/runtime/origin
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+
+            // Insert the created kitty into storage
+            let (kitty, current_id, seed_hash) = Self::do_create_kitty(&who)?;
+            Self::record_quest_progress(&who, QuestGoal::Create);
+
+            // Emit an event.
+            Self::deposit_event(Event::KittyCreated {
+                dna: kitty,
+                kitty: current_id,
+                owner: who,
+                seed_hash,
+            });
+            // Return a successful DispatchResultWithPostInfo
+            Ok(().into())
+        }
+
+        /// Create a kitty and list it on the exchange at `price` in a single call.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(3, 9 + T::DnaLength::get() as u64) + quest_weight::<T>())]
+        pub fn create_and_list_kitty(
+            origin: OriginFor<T>,
+            price: BalanceOf<T>,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+            Self::ensure_can_trade(&who)?;
+            Self::ensure_price_in_bounds(price, Denomination::Native)?;
+
+            let (kitty, current_id, seed_hash) = Self::do_create_kitty(&who)?;
+            Self::record_quest_progress(&who, QuestGoal::Create);
+            Self::list(
+                current_id,
+                Listing::<T>(who.clone(), price, Denomination::Native),
+            );
+
+            Self::deposit_event(Event::KittyCreated {
+                dna: kitty,
+                kitty: current_id,
+                owner: who.clone(),
+                seed_hash,
+            });
+            Self::deposit_event(Event::KittyListed {
+                kitty: current_id,
+                price,
+                owner: who,
+            });
+            Ok(().into())
+        }
+
+        /// Breed two of the caller's kitties into a new one.
+        ///
+        /// The offspring's generation is one more than its older parent's, and the caller pays
+        /// `BreedingFee` for that parent generation into the pallet account. With
+        /// `TwinProbability` a second offspring is born for the same fee.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(9, 18 + 2 * T::DnaLength::get() as u64) + quest_weight::<T>())]
+        pub fn breed_kitty(
+            origin: OriginFor<T>,
+            first_parent: KittyIndexOf<T>,
+            second_parent: KittyIndexOf<T>,
+        ) -> DispatchResultWithPostInfo {
+            // Ensure signed origin
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+            let pair = Self::breeding_pair(Some(&who), first_parent, second_parent)?;
+            Self::do_breed(&who, first_parent, second_parent, pair)?;
+            // Return a successful DispatchResultWithPostInfo
+            Ok(().into())
+        }
+
+        /// Clone one of the caller's kitties for `CloneFee`, doubled for every step the kitty is
+        /// removed from its original.
+        ///
+        /// The clone copies the kitty's dna, except that its last byte counts the clones of the
+        /// original made so far and each step away from the original resets one more trait to
+        /// zero. Clones keep their kitty's generation but have no parents.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(6, 10 + T::DnaLength::get() as u64))]
+        pub fn clone_kitty(
+            origin: OriginFor<T>,
+            kitty_id: KittyIndexOf<T>,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+            let Kitty(mut genome) = Self::ensure_owner(&who, kitty_id)?;
+
+            let original = Self::clone_original(kitty_id).unwrap_or(kitty_id);
+            let clones = Self::clone_count(original);
+            let depth = Self::clone_depth(kitty_id);
+            // The last byte holds the clone counter, the ones before it decay
+            ensure!(
+                clones < u8::MAX && (depth as usize) + 1 < genome.dna.len(),
+                Error::<T>::CloneLimitReached
+            );
+            let fee = T::CloneFee::get().saturating_mul(2u32.saturating_pow(depth.into()).into());
+            Self::ensure_mint_capacity(1)?;
+
+            let original_counter = match Self::kitty_of(original) {
+                Some((_, kitty)) => kitty.dna().last().copied().unwrap_or_default(),
+                None => genome.dna[genome.dna.len() - 1].wrapping_sub(clones),
+            };
+            let last = genome.dna.len() - 1;
+            genome.dna[last] = original_counter.wrapping_add(clones + 1);
+            genome.dna[depth as usize] = 0;
+
+            let clone_id = with_transaction_result(|| {
+                T::Currency::transfer(
+                    &who,
+                    &Self::account_id(),
+                    fee,
+                    ExistenceRequirement::KeepAlive,
+                )?;
+                let clone_id = Self::do_mint(&who, Kitty(genome))?;
+                KittyGeneration::<T>::insert(clone_id, Self::kitty_generation(kitty_id));
+                CloneDepth::<T>::insert(clone_id, depth + 1);
+                CloneOriginal::<T>::insert(clone_id, original);
+                CloneCount::<T>::insert(original, clones + 1);
+                Ok(clone_id)
+            })?;
+
+            Self::deposit_event(Event::KittyCloned {
+                original: kitty_id,
+                clone: clone_id,
+                owner: who,
+                fee,
+            });
+            Ok(().into())
+        }
+
+        /// Give a kitty to `receiver`, taking it off the market. Transferring a kitty to its own
+        /// owner changes nothing, so its listing stands.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(2,4))]
+        pub fn transfer_kitty(
+            origin: OriginFor<T>,
+            receiver: T::AccountId,
+            kitty_id: KittyIndexOf<T>,
+        ) -> DispatchResultWithPostInfo {
+            // Ensure signed origin
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+            Self::ensure_unlocked(kitty_id)?;
+
+            NftModule::<T>::transfer(&who, &receiver, (Self::class_id(), kitty_id))?;
+
+            if who != receiver {
+                Self::take_off_market(kitty_id);
+                Self::deposit_event(Event::KittyTransfer {
+                    kitty: kitty_id,
+                    from: who,
+                    to: receiver,
+                });
+            }
+            Ok(().into())
+        }
+
+        /// Give a kitty up to the shelter, where anyone can adopt it.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(3, 7))]
+        pub fn surrender_kitty(
+            origin: OriginFor<T>,
+            kitty_id: KittyIndexOf<T>,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+            Self::ensure_unlocked(kitty_id)?;
+
+            NftModule::<T>::transfer(
+                &who,
+                &Self::shelter_account_id(),
+                (Self::class_id(), kitty_id),
+            )?;
+            Self::take_off_market(kitty_id);
+            Shelter::<T>::insert(kitty_id, &who);
+
+            Self::deposit_event(Event::KittySurrendered {
+                kitty: kitty_id,
+                owner: who,
+            });
+            Ok(().into())
+        }
+
+        /// Adopt a kitty from the shelter for the `AdoptionFee`.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(3, 6))]
+        pub fn adopt_kitty(
+            origin: OriginFor<T>,
+            kitty_id: KittyIndexOf<T>,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+            ensure!(
+                Shelter::<T>::contains_key(kitty_id),
+                Error::<T>::KittyNotInShelter
+            );
+
+            let fee = T::AdoptionFee::get();
+            with_transaction_result(|| {
+                let payment = T::Currency::withdraw(
+                    &who,
+                    fee,
+                    WithdrawReasons::FEE,
+                    ExistenceRequirement::KeepAlive,
+                )?;
+                NftModule::<T>::transfer(
+                    &Self::shelter_account_id(),
+                    &who,
+                    (Self::class_id(), kitty_id),
+                )?;
+                T::AdoptionFeeDestination::on_unbalanced(payment);
+                Ok(())
+            })?;
+            Shelter::<T>::remove(kitty_id);
+
+            Self::deposit_event(Event::KittyAdopted {
+                kitty: kitty_id,
+                adopter: who,
+                fee,
+            });
+            Ok(().into())
+        }
+
+        /// Take all of the caller's kitties off the market, up to `MaxDelistAll` of them.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(
+            1 + T::MaxDelistAll::get() as u64,
+            3 * T::MaxDelistAll::get() as u64,
+        ))]
+        pub fn delist_all(origin: OriginFor<T>) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+
+            let listed = OwnerListings::<T>::iter_prefix(&who)
+                .map(|(kitty_id, _)| kitty_id)
+                .take(T::MaxDelistAll::get() as usize)
+                .collect::<Vec<_>>();
+            for &kitty_id in &listed {
+                Self::delist(kitty_id);
+                AcceptsReturns::<T>::remove(kitty_id);
+                Self::deposit_event(Event::KittyDelisted {
+                    kitty: kitty_id,
+                    owner: who.clone(),
+                });
+            }
+
+            let delisted = listed.len() as u64;
+            Ok(Some(10_000 + T::DbWeight::get().reads_writes(1 + delisted, 3 * delisted)).into())
+        }
+
+        /// Give a kitty to `receiver`, who cannot transfer, list or export it before
+        /// `unlock_block`.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(2,4))]
+        pub fn transfer_with_lock(
+            origin: OriginFor<T>,
+            receiver: T::AccountId,
+            kitty_id: KittyIndexOf<T>,
+            unlock_block: T::BlockNumber,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+            ensure!(
+                unlock_block > frame_system::Module::<T>::block_number(),
+                Error::<T>::InvalidUnlockBlock
+            );
+            Self::ensure_unlocked(kitty_id)?;
+
+            NftModule::<T>::transfer(&who, &receiver, (Self::class_id(), kitty_id))?;
+            Self::take_off_market(kitty_id);
+            TransferLocks::<T>::insert(kitty_id, unlock_block);
+
+            Self::deposit_event(Event::KittyTransferLocked {
+                kitty: kitty_id,
+                from: who,
+                to: receiver,
+                unlock_at: unlock_block,
+            });
+            Ok(().into())
+        }
+
+        /// Swap kitties and balance with the `counterparty` of `terms`, who signed them off-chain.
+        /// Either everything changes hands or nothing does, no fee is taken and nothing is kept
+        /// on chain. Traded kitties are taken off the market.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(
+            6 + 2 * MAX_TRADE_KITTIES as u64,
+            5 + 6 * MAX_TRADE_KITTIES as u64,
+        ))]
+        pub fn execute_trade(
+            origin: OriginFor<T>,
+            terms: TradeTermsOf<T>,
+            counterparty_sig: T::TradeSignature,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+            let kitties = terms.give.len().saturating_add(terms.take.len());
+            ensure!(
+                terms.submitter == who
+                    && terms.counterparty != who
+                    && kitties > 0
+                    && kitties <= MAX_TRADE_KITTIES as usize
+                    && frame_system::Module::<T>::block_number() <= terms.expires
+                    && Self::trade_nonce(&terms.counterparty) == terms.nonce,
+                Error::<T>::InvalidTrade
+            );
+            ensure!(
+                counterparty_sig.verify(&Self::trade_payload(&terms)[..], &terms.counterparty),
+                Error::<T>::InvalidTradeSignature
+            );
+            Self::ensure_can_trade(&who)?;
+            Self::ensure_can_trade(&terms.counterparty)?;
+
+            let counterparty = &terms.counterparty;
+            with_transaction_result(|| {
+                for (from, to, kitties) in [
+                    (&who, counterparty, &terms.give),
+                    (counterparty, &who, &terms.take),
+                ]
+                .iter()
+                {
+                    for &kitty_id in kitties.iter() {
+                        Self::ensure_unlocked(kitty_id)?;
+                        NftModule::<T>::transfer(from, to, (Self::class_id(), kitty_id))?;
+                        Self::take_off_market(kitty_id);
+                    }
+                }
+                let existence = ExistenceRequirement::KeepAlive;
+                T::Currency::transfer(&who, counterparty, terms.submitter_pays, existence)?;
+                T::Currency::transfer(counterparty, &who, terms.counterparty_pays, existence)?;
+                TradeNonces::<T>::insert(counterparty, terms.nonce.saturating_add(1));
+                Ok(())
+            })?;
+
+            Self::deposit_event(Event::TradeExecuted {
+                submitter: who,
+                counterparty: terms.counterparty,
+                given: terms.give,
+                taken: terms.take,
+            });
+            Ok(().into())
+        }
+
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(6,3))]
+        pub fn set_price(
+            origin: OriginFor<T>,
+            kitty_id: KittyIndexOf<T>,
+            new_price: Option<BalanceOf<T>>,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            Self::do_set_price(&who, kitty_id, new_price)?;
+            Ok(().into())
+        }
+
+        /// Schedule a kitty to be listed at `price` at the start of block `at`.
+        ///
+        /// The listing is checked when it is applied, and is dropped with a
+        /// `ScheduledPriceChangeFailed` event if the caller no longer owns the kitty or may not
+        /// list it then.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(2, 1))]
+        pub fn schedule_listing(
+            origin: OriginFor<T>,
+            kitty_id: KittyIndexOf<T>,
+            price: BalanceOf<T>,
+            at: T::BlockNumber,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            Self::schedule_price_change(who, kitty_id, Some(price), at)?;
+            Ok(().into())
+        }
+
+        /// Schedule a kitty to be taken off the market at the start of block `at`.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(2, 1))]
+        pub fn schedule_delist(
+            origin: OriginFor<T>,
+            kitty_id: KittyIndexOf<T>,
+            at: T::BlockNumber,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            Self::schedule_price_change(who, kitty_id, None, at)?;
+            Ok(().into())
+        }
+
+        /// List a kitty at a price in the `PriceOracle` reference unit.
+        ///
+        /// The buyer pays the equivalent native amount at the latest oracle rate. Use `set_price`
+        /// with `None` to delist.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(2,2))]
+        pub fn set_reference_price(
+            origin: OriginFor<T>,
+            kitty_id: KittyIndexOf<T>,
+            price: BalanceOf<T>,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+
+            Self::ensure_owner(&who, kitty_id)?;
+            Self::ensure_can_trade(&who)?;
+            Self::ensure_resale_allowed(kitty_id)?;
+            Self::ensure_unlocked(kitty_id)?;
+            Self::ensure_listing_settled(kitty_id)?;
+            Self::ensure_price_in_bounds(price, Denomination::Reference)?;
+
+            Self::list(
+                kitty_id,
+                Listing::<T>(who.clone(), price, Denomination::Reference),
+            );
+
+            Self::deposit_event(Event::KittyReferencePriceUpdated {
+                kitty: kitty_id,
+                price,
+                owner: who,
+            });
+            Ok(().into())
+        }
+
+        /// Buy a listed kitty at its asking price, within the buyer's spending limit.
+        ///
+        /// A `MarketplaceFee` share of the price goes to the pallet account instead of the seller.
+        /// If a `referrer` is given, it receives a `ReferralShare` cut of that fee. Purchases that
+        /// lose a race for the listing are counted in `FailedPurchases`.
+        #[pallet::weight(buy_weight::<T>() + T::DbWeight::get().reads_writes(1, 1))]
+        pub fn buy_kitty(
+            origin: OriginFor<T>,
+            kitty_id: KittyIndexOf<T>,
+            referrer: Option<T::AccountId>,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+            Self::ensure_can_trade(&who)?;
+
+            if let Err(error) = Self::do_buy(&who, kitty_id, referrer) {
+                if Self::is_contention(&error)
+                    && NftModule::<T>::tokens(Self::class_id(), kitty_id).is_some()
+                {
+                    FailedPurchases::<T>::mutate(kitty_id, |failed| {
+                        *failed = failed.saturating_add(1)
+                    });
+                }
+                return Err(error.into());
+            }
+            Ok(().into())
+        }
+
+        /// Buy a listed kitty like `buy_kitty`, redeeming the coupon whose code hashes to the
+        /// blake2_256 of `preimage`. The discount is refunded once the sale has settled, so
+        /// purchases that need the seller's confirmation cannot use coupons.
+        #[pallet::weight(buy_weight::<T>() + T::DbWeight::get().reads_writes(3, 4))]
+        pub fn buy_kitty_with_coupon(
+            origin: OriginFor<T>,
+            kitty_id: KittyIndexOf<T>,
+            preimage: Vec<u8>,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+            Self::ensure_can_trade(&who)?;
+            let code_hash = blake2_256(&preimage);
+            let coupon = Self::coupons(code_hash).ok_or(Error::<T>::InvalidCoupon)?;
+
+            with_transaction_result(|| {
+                // The fee as charged, flip fee included, before the purchase resets it
+                let Listing::<T>(_, price, denomination) =
+                    Self::kitty_exchange(kitty_id).ok_or(Error::<T>::KittyNotForSale)?;
+                let fee = Self::marketplace_fee(kitty_id, Self::native_price(price, denomination)?);
+                let price = Self::do_buy(&who, kitty_id, None)?;
+                ensure!(
+                    Self::kitties(&who, kitty_id).is_some(),
+                    Error::<T>::CouponNotApplicable
+                );
+
+                let (pot, discount) = match coupon.kind {
+                    CouponKind::FeeDiscount => (Self::account_id(), coupon.discount * fee),
+                    CouponKind::PriceSubsidy => (Self::promo_account_id(), coupon.discount * price),
+                };
+                T::Currency::transfer(&pot, &who, discount, ExistenceRequirement::KeepAlive)?;
+                Coupons::<T>::remove(code_hash);
+                RedeemedCoupons::<T>::insert(code_hash, ());
+
+                Self::deposit_event(Event::CouponRedeemed {
+                    code_hash,
+                    kitty: kitty_id,
+                    buyer: who,
+                    discount,
+                });
+                Ok(())
+            })?;
+            Ok(().into())
+        }
+
+        /// Complete a purchase of one of the caller's kitties that is waiting for confirmation
+        /// because its price is above the high value threshold.
+        ///
+        /// The kitty stays in escrow until then. Unconfirmed sales are reverted after
+        /// `ConfirmationPeriod` blocks, returning the funds to the buyer and the kitty, unlisted,
+        /// to the seller.
+        #[pallet::weight(buy_weight::<T>() + T::DbWeight::get().reads_writes(3, 4))]
+        pub fn confirm_high_value_sale(
+            origin: OriginFor<T>,
+            sale_id: u32,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+            let sale = Self::pending_sales(sale_id).ok_or(Error::<T>::PendingSaleNotFound)?;
+            ensure!(who == sale.seller, Error::<T>::NotSeller);
+
+            with_transaction_result(|| {
+                PendingSales::<T>::remove(sale_id);
+                NftModule::<T>::transfer(
+                    &Self::escrow_account_id(),
+                    &sale.seller,
+                    (Self::class_id(), sale.kitty),
+                )?;
+                Self::settle_sale(
+                    sale.kitty,
+                    &sale.seller,
+                    &sale.buyer,
+                    sale.price,
+                    sale.referrer,
+                    true,
+                )
+            })?;
+            Ok(().into())
+        }
+
+        /// Buy a listed kitty in `num_payments` installments, one due every
+        /// `InstallmentPeriod` blocks. The first is paid now and the kitty is escrowed until
+        /// the last.
+        ///
+        /// Installments stay held with the buyer until the sale settles as if bought with
+        /// `buy_kitty`. A missed installment returns the kitty to the seller, who keeps
+        /// `LayawayPenalty` of the price out of what was paid.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(8, 10))]
+        pub fn buy_kitty_installments(
+            origin: OriginFor<T>,
+            kitty_id: KittyIndexOf<T>,
+            num_payments: u32,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+            Self::ensure_can_trade(&who)?;
+            ensure!(
+                num_payments >= 2 && num_payments <= T::MaxInstallments::get(),
+                Error::<T>::InvalidInstallments
+            );
+            let Listing::<T>(seller, price, denomination) =
+                Self::kitty_exchange(kitty_id).ok_or(Error::<T>::KittyNotForSale)?;
+            ensure!(who != seller, Error::<T>::CannotBuyOwnKitty);
+            let price = Self::native_price(price, denomination)?;
+            let installment = price / num_payments.into();
+            let first = price - installment * (num_payments - 1).into();
+
+            let layaway_id = NextLayawayId::<T>::get();
+            let due = frame_system::Module::<T>::block_number() + T::InstallmentPeriod::get();
+            with_transaction_result(|| {
+                Self::delist(kitty_id);
+                Self::record_spending(&who, first)?;
+                Self::hold_funds(&who, first)?;
+                NftModule::<T>::transfer(
+                    &seller,
+                    &Self::escrow_account_id(),
+                    (Self::class_id(), kitty_id),
+                )?;
+                Layaways::<T>::insert(
+                    layaway_id,
+                    Layaway {
+                        kitty: kitty_id,
+                        seller: seller.clone(),
+                        buyer: who.clone(),
+                        price,
+                        installment,
+                        payments_left: num_payments - 1,
+                        due,
+                    },
+                );
+                LayawayDeadlines::<T>::append(due, layaway_id);
+                NextLayawayId::<T>::put(layaway_id.saturating_add(1));
+                Ok(())
+            })?;
+
+            Self::deposit_event(Event::LayawayStarted {
+                layaway: layaway_id,
+                kitty: kitty_id,
+                seller,
+                buyer: who,
+                price,
+                payments: num_payments,
+            });
+            Ok(().into())
+        }
+
+        /// Pay the next installment of one of the caller's layaways, moving its deadline on by
+        /// `InstallmentPeriod`. Paying the last one settles the sale.
+        #[pallet::weight(buy_weight::<T>() + T::DbWeight::get().reads_writes(4, 5))]
+        pub fn pay_installment(
+            origin: OriginFor<T>,
+            layaway_id: u32,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+            let mut layaway = Self::layaways(layaway_id).ok_or(Error::<T>::LayawayNotFound)?;
+            ensure!(layaway.buyer == who, Error::<T>::NotLayawayBuyer);
+
+            layaway.payments_left -= 1;
+            with_transaction_result(|| {
+                Self::record_spending(&who, layaway.installment)?;
+                Self::hold_funds(&who, layaway.installment)?;
+                if layaway.payments_left == 0 {
+                    Layaways::<T>::remove(layaway_id);
+                    NftModule::<T>::transfer(
+                        &Self::escrow_account_id(),
+                        &layaway.seller,
+                        (Self::class_id(), layaway.kitty),
+                    )?;
+                    Self::settle_sale(
+                        layaway.kitty,
+                        &layaway.seller,
+                        &who,
+                        layaway.price,
+                        None,
+                        true,
+                    )
+                } else {
+                    layaway.due += T::InstallmentPeriod::get();
+                    LayawayDeadlines::<T>::append(layaway.due, layaway_id);
+                    Layaways::<T>::insert(layaway_id, layaway.clone());
+                    Ok(())
+                }
+            })?;
+
+            Self::deposit_event(Event::InstallmentPaid {
+                layaway: layaway_id,
+                buyer: who,
+                amount: layaway.installment,
+                payments_left: layaway.payments_left,
+            });
+            Ok(().into())
+        }
+
+        /// Let buyers of one of the caller's listed kitties return it within `ReturnPeriod`
+        /// blocks, refunding the price less the `RestockingFee`. The refund is held out of the
+        /// seller's proceeds, so it is at most what the seller received for the kitty.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(2, 1))]
+        pub fn set_accepts_returns(
+            origin: OriginFor<T>,
+            kitty_id: KittyIndexOf<T>,
+            accepted: bool,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+            Self::ensure_owner(&who, kitty_id)?;
+            ensure!(
+                Self::kitty_exchange(kitty_id).is_some(),
+                Error::<T>::KittyNotForSale
+            );
+
+            if accepted {
+                AcceptsReturns::<T>::insert(kitty_id, &who);
+            } else {
+                AcceptsReturns::<T>::remove(kitty_id);
+            }
+
+            Self::deposit_event(Event::ReturnsAcceptedSet {
+                kitty: kitty_id,
+                accepted,
+            });
+            Ok(().into())
+        }
+
+        /// Return a kitty bought from a listing that accepts returns to its seller, getting the
+        /// held refund back. The sale is taken back out of both parties' tax reports.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(8, 13))]
+        pub fn return_kitty(origin: OriginFor<T>, sale_id: u32) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+            let sale = Self::returnable_sales(sale_id).ok_or(Error::<T>::ReturnNotFound)?;
+            ensure!(sale.buyer == who, Error::<T>::NotSaleBuyer);
+            Self::ensure_owner(&who, sale.kitty)?;
+
+            with_transaction_result(|| {
+                ReturnableSales::<T>::remove(sale_id);
+                Self::delist(sale.kitty);
+                NftModule::<T>::transfer(&who, &sale.seller, (Self::class_id(), sale.kitty))?;
+                // The seller gets the kitty back as if it had never been sold
+                ResaleLockedUntil::<T>::remove(sale.kitty);
+                PurchasedAt::<T>::remove(sale.kitty);
+                CostBasis::<T>::insert(sale.kitty, sale.cost_basis);
+                Self::pay_held(&sale.seller, &who, sale.refund)
+            })?;
+            if TaxReports::<T>::contains_key(sale.era, &sale.seller) {
+                TaxReports::<T>::mutate(sale.era, &sale.seller, |report| {
+                    report.proceeds = report.proceeds.saturating_sub(sale.proceeds);
+                    report.cost_basis = report.cost_basis.saturating_sub(sale.cost_basis);
+                });
+            }
+            if TaxReports::<T>::contains_key(sale.era, &who) {
+                TaxReports::<T>::mutate(sale.era, &who, |report| {
+                    report.purchases = report.purchases.saturating_sub(sale.price);
+                });
+            }
+
+            Self::deposit_event(Event::KittyReturned {
+                sale: sale_id,
+                kitty: sale.kitty,
+                seller: sale.seller,
+                buyer: who,
+                refund: sale.refund,
+            });
+            Ok(().into())
+        }
+
+        /// Buy the `count` cheapest natively priced kitties listed by others, for at most
+        /// `max_total` in all. Either every purchase goes through or none does.
+        ///
+        /// Only `count` of the caller's own listings are skipped while looking for them.
+        #[pallet::weight(buy_weight::<T>().saturating_mul((*count).min(T::MaxFloorBuy::get()).into()))]
+        pub fn buy_floor(
+            origin: OriginFor<T>,
+            count: u32,
+            max_total: BalanceOf<T>,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+            ensure!(
+                count > 0 && count <= T::MaxFloorBuy::get(),
+                Error::<T>::InvalidFloorCount
+            );
+            Self::ensure_can_trade(&who)?;
+
+            let floor = PriceIndex::<T>::iter()
+                .map(|(_, kitty_id, _)| kitty_id)
+                .take(2 * count as usize)
+                .filter(|kitty_id| {
+                    !matches!(Self::kitty_exchange(*kitty_id), Some(Listing::<T>(seller, ..)) if seller == who)
+                })
+                .take(count as usize)
+                .collect::<Vec<_>>();
+            ensure!(floor.len() == count as usize, Error::<T>::NotEnoughListings);
+
+            let total = with_transaction_result(|| {
+                let mut total = BalanceOf::<T>::zero();
+                for &kitty_id in &floor {
+                    total = total.saturating_add(Self::do_buy(&who, kitty_id, None)?);
+                }
+                ensure!(total <= max_total, Error::<T>::FloorTotalExceeded);
+                Ok(total)
+            })?;
+
+            Self::deposit_event(Event::FloorSwept {
+                buyer: who,
+                count,
+                total,
+            });
+            Ok(().into())
+        }
+
+        /// Offer to buy up to `quantity` kitties at `max_price` each, reserving the full
+        /// amount.
+        ///
+        /// With a `trait_filter` of `(trait, value)` only kitties indexed under that trait
+        /// bucket can be sold into the bid.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(3, 3))]
+        pub fn place_collection_bid(
+            origin: OriginFor<T>,
+            max_price: BalanceOf<T>,
+            trait_filter: Option<(u8, u8)>,
+            quantity: u32,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+            ensure!(quantity > 0, Error::<T>::InvalidCollectionBid);
+            Self::ensure_can_trade(&who)?;
+
+            Self::hold_funds(&who, max_price.saturating_mul(quantity.into()))?;
+            let bid_id = NextCollectionBidId::<T>::get();
+            CollectionBids::<T>::insert(
+                bid_id,
+                CollectionBid {
+                    bidder: who.clone(),
+                    max_price,
+                    trait_filter,
+                    quantity,
+                },
+            );
+            NextCollectionBidId::<T>::put(bid_id.saturating_add(1));
+
+            Self::deposit_event(Event::CollectionBidPlaced {
+                bid: bid_id,
+                bidder: who,
+                max_price,
+                quantity,
+            });
+            Ok(().into())
+        }
+
+        /// Sell one of the caller's kitties into a collection bid at the bid's price. Any
+        /// listing of the kitty is withdrawn.
+        #[pallet::weight(buy_weight::<T>())]
+        pub fn fill_collection_bid(
+            origin: OriginFor<T>,
+            bid_id: u32,
+            kitty_id: KittyIndexOf<T>,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+            let mut bid = Self::collection_bids(bid_id).ok_or(Error::<T>::CollectionBidNotFound)?;
+            Self::ensure_owner(&who, kitty_id)?;
+            ensure!(who != bid.bidder, Error::<T>::CannotBuyOwnKitty);
+            if let Some(trait_key) = bid.trait_filter {
+                ensure!(
+                    TraitIndex::<T>::contains_key(trait_key, kitty_id),
+                    Error::<T>::KittyDoesNotMatchBid
+                );
+            }
+            Self::ensure_can_trade(&who)?;
+            Self::ensure_resale_allowed(kitty_id)?;
+            Self::ensure_unlocked(kitty_id)?;
+
+            with_transaction_result(|| {
+                Self::delist(kitty_id);
+                Self::record_spending(&bid.bidder, bid.max_price)?;
+                Self::settle_sale(kitty_id, &who, &bid.bidder, bid.max_price, None, true)
+            })?;
+
+            bid.quantity -= 1;
+            if bid.quantity == 0 {
+                CollectionBids::<T>::remove(bid_id);
+            } else {
+                CollectionBids::<T>::insert(bid_id, bid);
+            }
+
+            Self::deposit_event(Event::CollectionBidFilled {
+                bid: bid_id,
+                kitty: kitty_id,
+                seller: who,
+            });
+            Ok(().into())
+        }
+
+        /// Withdraw the caller's collection bid, releasing the funds held for it.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(2, 3))]
+        pub fn cancel_collection_bid(
+            origin: OriginFor<T>,
+            bid_id: u32,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+            let bid = Self::collection_bids(bid_id).ok_or(Error::<T>::CollectionBidNotFound)?;
+            ensure!(who == bid.bidder, Error::<T>::NotCollectionBidder);
+
+            Self::release_funds(&who, bid.max_price.saturating_mul(bid.quantity.into()));
+            CollectionBids::<T>::remove(bid_id);
+            Reputations::<T>::mutate(&who, |reputation| {
+                reputation.cancelled_bids = reputation.cancelled_bids.saturating_add(1)
+            });
+
+            Self::deposit_event(Event::CollectionBidCancelled { bid: bid_id });
+            Ok(().into())
+        }
+
+        /// Burn a kitty so it can be imported on another chain.
+        ///
+        /// The `KittyExported` event carries the export, which the import authority signs for
+        /// `import_kitty` on the target chain.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(4, 7 + T::DnaLength::get() as u64))]
+        pub fn export_kitty(
+            origin: OriginFor<T>,
+            kitty_id: KittyIndexOf<T>,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+
+            let kitty = Self::ensure_owner(&who, kitty_id)?;
+            Self::ensure_unlocked(kitty_id)?;
+            let export = KittyExport {
+                owner: who.clone(),
+                dna: kitty.clone(),
+                parents: Self::kitty_parents(kitty_id),
+                generation: Self::kitty_generation(kitty_id),
+                nonce: Self::export_nonce(),
+            };
+
+            Self::do_burn(&who, kitty_id, &kitty)?;
+            ExportNonce::<T>::put(export.nonce.saturating_add(1));
+
+            Self::deposit_event(Event::KittyExported {
+                kitty: kitty_id,
+                export,
+            });
+            Ok(().into())
+        }
+
+        /// Mint a kitty exported from another chain to the owner named in the export.
+        ///
+        /// Anyone may submit the export, as long as it is signed by the import authority.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(4, 9 + T::DnaLength::get() as u64))]
+        pub fn import_kitty(
+            origin: OriginFor<T>,
+            export: KittyExportOf<T>,
+            signature: T::ImportSignature,
+        ) -> DispatchResultWithPostInfo {
+            Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+
+            let authority = Self::import_authority().ok_or(Error::<T>::ImportsDisabled)?;
+            let encoded = export.encode();
+            ensure!(
+                signature.verify(&encoded[..], &authority),
+                Error::<T>::InvalidExportSignature
+            );
+            let export_hash = T::Hashing::hash(&encoded);
+            ensure!(
+                !ImportedExports::<T>::contains_key(export_hash),
+                Error::<T>::ExportAlreadyImported
+            );
+
+            let genome = &export.dna.0;
+            ensure!(
+                genome.version <= GENOME_VERSION
+                    && genome.dna.len() <= T::DnaLength::get() as usize,
+                Error::<T>::UnsupportedGenome
+            );
+            Self::ensure_mint_capacity(1)?;
+
+            let kitty_id = Self::do_mint(&export.owner, export.dna.clone())?;
+            KittyGeneration::<T>::insert(kitty_id, export.generation);
+            ImportedExports::<T>::insert(export_hash, ());
+
+            Self::deposit_event(Event::KittyImported {
+                dna: export.dna,
+                kitty: kitty_id,
+                owner: export.owner,
+            });
+            Ok(().into())
+        }
+
+        /// Set the account whose signature `import_kitty` accepts, or `None` to disable imports.
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1) + audit_weight::<T>())]
+        pub fn set_import_authority(
+            origin: OriginFor<T>,
+            authority: Option<T::AccountId>,
+        ) -> DispatchResultWithPostInfo {
+            T::ForceOrigin::ensure_origin(origin)?;
+
+            match &authority {
+                Some(authority) => ImportAuthority::<T>::put(authority),
+                None => ImportAuthority::<T>::kill(),
+            }
+
+            Self::deposit_admin_event(Event::ImportAuthoritySet { authority });
+            Ok(().into())
+        }
+
+        /// Cap what the caller can spend on kitties per `SpendingPeriod`, or lift the cap with
+        /// `None`.
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(2))]
+        pub fn set_spending_limit(
+            origin: OriginFor<T>,
+            limit: Option<BalanceOf<T>>,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+
+            match limit {
+                Some(limit) => SpendingLimits::<T>::insert(&who, limit),
+                None => {
+                    SpendingLimits::<T>::remove(&who);
+                    Spent::<T>::remove(&who);
+                }
+            }
+
+            Self::deposit_event(Event::SpendingLimitSet {
+                account: who,
+                limit,
+            });
+            Ok(().into())
+        }
+
+        /// Let `heir` claim the caller's kitties once the caller has made no kitties call for
+        /// `inactivity_blocks` blocks.
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(2))]
+        pub fn set_heir(
+            origin: OriginFor<T>,
+            heir: T::AccountId,
+            inactivity_blocks: T::BlockNumber,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            ensure!(
+                heir != who && !inactivity_blocks.is_zero(),
+                Error::<T>::InvalidHeir
+            );
+
+            Heirs::<T>::insert(&who, (&heir, inactivity_blocks));
+            LastActive::<T>::insert(&who, frame_system::Module::<T>::block_number());
+
+            Self::deposit_event(Event::HeirSet {
+                owner: who,
+                heir,
+                inactivity_blocks,
+            });
+            Ok(().into())
+        }
+
+        /// Let `server` act on the caller's kitties with the `GAME_*` `permissions` until block
+        /// `expires`, replacing any it had. Game servers can never transfer kitties.
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+        pub fn authorize_game_server(
+            origin: OriginFor<T>,
+            server: T::AccountId,
+            permissions: u8,
+            expires: T::BlockNumber,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            ensure!(
+                server != who
+                    && permissions != 0
+                    && permissions & !GAME_PERMISSIONS == 0
+                    && expires > frame_system::Module::<T>::block_number(),
+                Error::<T>::InvalidGameServer
+            );
+
+            GameServers::<T>::insert(&who, &server, (permissions, expires));
+
+            Self::deposit_event(Event::GameServerAuthorized {
+                owner: who,
+                server,
+                permissions,
+                expires,
+            });
+            Ok(().into())
+        }
+
+        /// Take away all of `server`'s permissions over the caller's kitties.
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+        pub fn revoke_game_server(
+            origin: OriginFor<T>,
+            server: T::AccountId,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+
+            if GameServers::<T>::take(&who, &server).is_some() {
+                Self::deposit_event(Event::GameServerRevoked { owner: who, server });
+            }
+            Ok(().into())
+        }
+
+        /// Stop tracking the caller's activity and forget its heir.
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(2))]
+        pub fn remove_heir(origin: OriginFor<T>) -> DispatchResultWithPostInfo {
+            let who = ensure_signed(origin)?;
+
+            if Heirs::<T>::take(&who).is_some() {
+                LastActive::<T>::remove(&who);
+                Self::deposit_event(Event::HeirRemoved { owner: who });
+            }
+            Ok(().into())
+        }
+
+        /// As `owner`'s heir, take up to `MaxInheritedKitties` of its kitties once it has been
+        /// inactive for its chosen period. Kitties locked by `transfer_with_lock` stay until
+        /// their lock ends. Claim again for the rest; the heir is forgotten once `owner` has no
+        /// kitties left.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(
+            3 + 4 * T::MaxInheritedKitties::get() as u64,
+            2 + 6 * T::MaxInheritedKitties::get() as u64,
+        ))]
+        pub fn claim_inheritance(
+            origin: OriginFor<T>,
+            owner: T::AccountId,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+            let (heir, inactivity_blocks) = Self::heir(&owner).ok_or(Error::<T>::NotHeir)?;
+            ensure!(heir == who, Error::<T>::NotHeir);
+            ensure!(
+                frame_system::Module::<T>::block_number()
+                    >= Self::last_active(&owner).saturating_add(inactivity_blocks),
+                Error::<T>::OwnerStillActive
+            );
+
+            let class_id = Self::class_id();
+            let now = frame_system::Module::<T>::block_number();
+            let max = T::MaxInheritedKitties::get() as usize;
+            let owned = |owner: &T::AccountId| {
+                orml_nft::TokensByOwner::<T>::iter_prefix(owner)
+                    .filter(move |((class, _), _)| *class == class_id)
+                    .map(|((_, kitty_id), _)| kitty_id)
+            };
+            let inherited = owned(&owner)
+                .take(2 * max)
+                .filter(
+                    |kitty_id| !matches!(Self::transfer_lock(kitty_id), Some(until) if now < until),
+                )
+                .take(max)
+                .collect::<Vec<_>>();
+
+            with_transaction_result(|| {
+                for &kitty_id in &inherited {
+                    Self::ensure_unlocked(kitty_id)?;
+                    NftModule::<T>::transfer(&owner, &heir, (class_id, kitty_id))?;
+                    Self::take_off_market(kitty_id);
+                }
+                Ok(())
+            })?;
+            for &kitty_id in &inherited {
+                Self::deposit_event(Event::KittyTransfer {
+                    kitty: kitty_id,
+                    from: owner.clone(),
+                    to: heir.clone(),
+                });
+            }
+            if owned(&owner).next().is_none() {
+                Heirs::<T>::remove(&owner);
+                LastActive::<T>::remove(&owner);
             }
 
-            Self::deposit_event(Event::KittyPriceUpdated(kitty_id, new_price, who));
+            Self::deposit_event(Event::InheritanceClaimed {
+                owner,
+                heir,
+                count: inherited.len() as u32,
+            });
             Ok(().into())
         }
 
-        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(1,2))]
-        pub fn buy_kitty(
+        /// Create a quest to perform `goal` `target` times before block `ends_at`.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(1, 2) + quest_weight::<T>() + audit_weight::<T>())]
+        pub fn create_quest(
+            origin: OriginFor<T>,
+            goal: QuestGoal,
+            target: u32,
+            reward: BalanceOf<T>,
+            ends_at: T::BlockNumber,
+        ) -> DispatchResultWithPostInfo {
+            T::ForceOrigin::ensure_origin(origin)?;
+            ensure!(target > 0, Error::<T>::InvalidQuest);
+            ensure!(
+                (Quests::<T>::iter().count() as u32) < T::MaxQuests::get(),
+                Error::<T>::TooManyQuests
+            );
+
+            let quest_id = NextQuestId::<T>::get();
+            Quests::<T>::insert(
+                quest_id,
+                Quest {
+                    goal,
+                    target,
+                    reward,
+                    ends_at,
+                },
+            );
+            NextQuestId::<T>::put(quest_id.saturating_add(1));
+
+            Self::deposit_admin_event(Event::QuestCreated { quest: quest_id });
+            Ok(().into())
+        }
+
+        /// Require sales above `threshold` to be confirmed by the seller, or `None` to settle
+        /// every sale immediately. Sales already waiting for confirmation are not affected.
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1) + audit_weight::<T>())]
+        pub fn set_high_value_threshold(
+            origin: OriginFor<T>,
+            threshold: Option<BalanceOf<T>>,
+        ) -> DispatchResultWithPostInfo {
+            T::ForceOrigin::ensure_origin(origin)?;
+
+            match threshold {
+                Some(threshold) => HighValueThreshold::<T>::put(threshold),
+                None => HighValueThreshold::<T>::kill(),
+            }
+
+            Self::deposit_admin_event(Event::HighValueThresholdSet { threshold });
+            Ok(().into())
+        }
+
+        /// Issue a one-time coupon whose code hashes to `code_hash` with blake2_256.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(2, 1) + audit_weight::<T>())]
+        pub fn issue_coupon(
+            origin: OriginFor<T>,
+            code_hash: [u8; 32],
+            coupon: Coupon,
+        ) -> DispatchResultWithPostInfo {
+            T::ForceOrigin::ensure_origin(origin)?;
+            ensure!(
+                !Coupons::<T>::contains_key(code_hash)
+                    && !RedeemedCoupons::<T>::contains_key(code_hash),
+                Error::<T>::CouponExists
+            );
+
+            Coupons::<T>::insert(code_hash, coupon);
+
+            Self::deposit_admin_event(Event::CouponIssued { code_hash, coupon });
+            Ok(().into())
+        }
+
+        /// Propose that the pallet account pays to mint `count` promotional kitties for
+        /// `beneficiary`. Nothing happens until `ForceOrigin` approves it.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(1, 2))]
+        pub fn propose_grant(
+            origin: OriginFor<T>,
+            beneficiary: T::AccountId,
+            count: u32,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            ensure!(
+                count > 0 && count <= T::MaxGrantKitties::get(),
+                Error::<T>::InvalidGrant
+            );
+
+            let proposal_id = NextGrantProposalId::<T>::get();
+            GrantProposals::<T>::insert(
+                proposal_id,
+                GrantProposal {
+                    proposer: who.clone(),
+                    beneficiary: beneficiary.clone(),
+                    count,
+                },
+            );
+            NextGrantProposalId::<T>::put(proposal_id.saturating_add(1));
+
+            Self::deposit_event(Event::GrantProposed {
+                proposal: proposal_id,
+                proposer: who,
+                beneficiary,
+                count,
+            });
+            Ok(().into())
+        }
+
+        /// Approve a grant proposal, minting its kitties to the beneficiary with the pallet
+        /// account paying the mint price of each.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(
+            3 + 2 * T::MaxGrantKitties::get() as u64,
+            3 + (8 + T::DnaLength::get() as u64) * T::MaxGrantKitties::get() as u64,
+        ) + audit_weight::<T>())]
+        pub fn approve_grant(origin: OriginFor<T>, proposal_id: u32) -> DispatchResultWithPostInfo {
+            T::ForceOrigin::ensure_origin(origin)?;
+            Self::ensure_not_frozen()?;
+            let proposal = Self::grant_proposals(proposal_id).ok_or(Error::<T>::GrantNotFound)?;
+            Self::ensure_mint_capacity(proposal.count)?;
+            let cost = Self::mint_price().saturating_mul(proposal.count.into());
+
+            with_transaction_result(|| {
+                GrantProposals::<T>::remove(proposal_id);
+                let payment = T::Currency::withdraw(
+                    &Self::account_id(),
+                    cost,
+                    WithdrawReasons::TRANSFER,
+                    ExistenceRequirement::KeepAlive,
+                )?;
+                let random = T::RandomnessSource::random(&(b"grant", proposal_id).encode());
+                for index in 0..proposal.count {
+                    let seed = (&proposal.beneficiary, random, proposal_id, index);
+                    let (kitty, seed_hash) = Kitty::generate_unique::<T>(None, seed, None)?;
+                    let kitty_id = Self::do_mint(&proposal.beneficiary, kitty)?;
+                    DnaSeeds::<T>::insert(kitty_id, seed_hash);
+                }
+                T::MintPriceDestination::on_unbalanced(payment);
+                Ok(())
+            })?;
+
+            Self::deposit_admin_event(Event::GrantApproved {
+                proposal: proposal_id,
+                beneficiary: proposal.beneficiary,
+                count: proposal.count,
+                cost,
+            });
+            Ok(().into())
+        }
+
+        /// Reject a grant proposal without minting anything.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(1, 1) + audit_weight::<T>())]
+        pub fn reject_grant(origin: OriginFor<T>, proposal_id: u32) -> DispatchResultWithPostInfo {
+            T::ForceOrigin::ensure_origin(origin)?;
+            GrantProposals::<T>::take(proposal_id).ok_or(Error::<T>::GrantNotFound)?;
+
+            Self::deposit_admin_event(Event::GrantRejected {
+                proposal: proposal_id,
+            });
+            Ok(().into())
+        }
+
+        /// Open a race closing at block `closes_at`, where the `winners` fastest kitties split
+        /// the entry fees.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(1, 2) + audit_weight::<T>())]
+        pub fn open_race(
+            origin: OriginFor<T>,
+            entry_fee: BalanceOf<T>,
+            closes_at: T::BlockNumber,
+            winners: u32,
+        ) -> DispatchResultWithPostInfo {
+            T::ForceOrigin::ensure_origin(origin)?;
+            ensure!(
+                winners > 0 && closes_at > frame_system::Module::<T>::block_number(),
+                Error::<T>::InvalidRace
+            );
+
+            let race_id = NextRaceId::<T>::get();
+            Races::<T>::insert(
+                race_id,
+                Race {
+                    entry_fee,
+                    closes_at,
+                    winners,
+                    entrants: Vec::new(),
+                },
+            );
+            NextRaceId::<T>::put(race_id.saturating_add(1));
+
+            Self::deposit_admin_event(Event::RaceOpened {
+                race: race_id,
+                entry_fee,
+                closes_at,
+            });
+            Ok(().into())
+        }
+
+        /// Enter one of the caller's kitties into a race, paying its entry fee into the prize
+        /// pool. Game servers with `GAME_ENTER_RACES` can enter their owners' kitties, with the
+        /// owner paying the fee.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(4, 3))]
+        pub fn enter_race(
             origin: OriginFor<T>,
+            race_id: u32,
             kitty_id: KittyIndexOf<T>,
         ) -> DispatchResultWithPostInfo {
-            let who = ensure_signed(origin)?;
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+            let (who, _) = Self::ensure_owner_or_game_server(&who, kitty_id, GAME_ENTER_RACES)?;
 
-            KittyExchange::<T>::try_mutate(kitty_id, |listing_option| {
-                let Listing::<T>(owner, price) =
-                    listing_option.take().ok_or(Error::<T>::KittyNotForSale)?;
-                ensure!(who != owner, Error::<T>::CannotBuyOwnKitty);
+            Races::<T>::try_mutate(race_id, |race| -> DispatchResult {
+                let race = race.as_mut().ok_or(Error::<T>::RaceNotFound)?;
+                ensure!(
+                    frame_system::Module::<T>::block_number() < race.closes_at,
+                    Error::<T>::RaceClosed
+                );
+                ensure!(
+                    (race.entrants.len() as u32) < T::MaxRaceEntrants::get(),
+                    Error::<T>::RaceFull
+                );
+                ensure!(
+                    race.entrants
+                        .iter()
+                        .all(|(entrant, _)| *entrant != kitty_id),
+                    Error::<T>::AlreadyEntered
+                );
 
-                with_transaction_result(|| {
-                    NftModule::<T>::transfer(&owner, &who, (Self::class_id(), kitty_id))?;
-                    T::Currency::transfer(&who, &owner, price, ExistenceRequirement::KeepAlive)?;
+                T::Currency::transfer(
+                    &who,
+                    &Self::account_id(),
+                    race.entry_fee,
+                    ExistenceRequirement::KeepAlive,
+                )?;
+                race.entrants.push((kitty_id, who.clone()));
+                Ok(())
+            })?;
 
-                    Self::deposit_event(Event::KittySold(kitty_id, price, owner, who));
+            Self::deposit_event(Event::RaceEntered {
+                race: race_id,
+                kitty: kitty_id,
+                owner: who,
+            });
+            Ok(().into())
+        }
 
-                    Ok(())
+        /// Run a closed race and split its prize pool between the winners.
+        ///
+        /// Kitties score their `speed` plus a random bonus, with ties going to the earlier
+        /// entrant. Kitties that changed hands since entering are disqualified; if none are
+        /// left, the pool stays with the pallet.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(
+            2 + 2 * T::MaxRaceEntrants::get() as u64,
+            1 + 2 * T::MaxRaceEntrants::get() as u64,
+        ))]
+        pub fn finish_race(origin: OriginFor<T>, race_id: u32) -> DispatchResultWithPostInfo {
+            Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+            let race = Self::races(race_id).ok_or(Error::<T>::RaceNotFound)?;
+            ensure!(
+                frame_system::Module::<T>::block_number() >= race.closes_at,
+                Error::<T>::RaceNotClosed
+            );
+
+            let entrants = race.entrants.len() as u64;
+            let pool = race
+                .entry_fee
+                .saturating_mul((race.entrants.len() as u32).into());
+            let mut results = race
+                .entrants
+                .into_iter()
+                .filter_map(|(kitty_id, owner)| {
+                    let kitty = Self::kitties(&owner, kitty_id)?;
+                    let luck = T::RandomnessSource::random(&(b"race", race_id, kitty_id).encode());
+                    let score = kitty.speed() + u32::from(luck.as_ref()[0] % 16);
+                    Some((score, kitty_id, owner))
                 })
+                .collect::<Vec<_>>();
+            // Stable, so ties keep entry order
+            results.sort_by_key(|(score, ..)| sp_std::cmp::Reverse(*score));
+            results.truncate(race.winners as usize);
+
+            let mut winners = Vec::with_capacity(results.len());
+            if !results.is_empty() {
+                let share = pool / (results.len() as u32).into();
+                // The first place also takes what doesn't split evenly
+                let mut prize = pool - share * (results.len() as u32).into() + share;
+                with_transaction_result(|| {
+                    for (_, kitty_id, owner) in results {
+                        T::Currency::transfer(
+                            &Self::account_id(),
+                            &owner,
+                            prize,
+                            ExistenceRequirement::KeepAlive,
+                        )?;
+                        winners.push((kitty_id, owner, prize));
+                        prize = share;
+                    }
+                    Ok(())
+                })?;
+            }
+            Races::<T>::remove(race_id);
+
+            let paid = winners.len() as u64;
+            Self::deposit_event(Event::RaceFinished {
+                race: race_id,
+                winners,
+            });
+            Ok(
+                Some(10_000 + T::DbWeight::get().reads_writes(2 + 2 * entrants, 1 + 2 * paid))
+                    .into(),
+            )
+        }
+
+        /// Remove a quest and up to `MaxQuestProgressRemovals` accounts' progress on it. Call
+        /// again until the event says it is `cleared` to remove the rest of the progress.
+        /// Badges already claimed are kept.
+        #[pallet::weight(10_000
+            + T::DbWeight::get().reads_writes(2, 1 + T::MaxQuestProgressRemovals::get() as u64)
+            + quest_weight::<T>()
+            + audit_weight::<T>())]
+        pub fn remove_quest(origin: OriginFor<T>, quest_id: u32) -> DispatchResultWithPostInfo {
+            T::ForceOrigin::ensure_origin(origin)?;
+            ensure!(
+                Quests::<T>::contains_key(quest_id)
+                    || QuestProgress::<T>::iter_prefix(quest_id).next().is_some(),
+                Error::<T>::QuestNotFound
+            );
+
+            Quests::<T>::remove(quest_id);
+            let removed = QuestProgress::<T>::drain_prefix(quest_id)
+                .take(T::MaxQuestProgressRemovals::get() as usize)
+                .count() as u64;
+            let cleared = QuestProgress::<T>::iter_prefix(quest_id).next().is_none();
+
+            Self::deposit_admin_event(Event::QuestRemoved {
+                quest: quest_id,
+                cleared,
+            });
+            Ok(Some(
+                10_000
+                    + T::DbWeight::get().reads_writes(2 + removed, 1 + removed)
+                    + quest_weight::<T>()
+                    + audit_weight::<T>(),
+            )
+            .into())
+        }
+
+        /// Claim the badge and reward of a completed quest.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(2, 4))]
+        pub fn claim_quest_reward(
+            origin: OriginFor<T>,
+            quest_id: u32,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+
+            let quest = Self::quests(quest_id).ok_or(Error::<T>::QuestNotFound)?;
+            QuestProgress::<T>::try_mutate(quest_id, &who, |(progress, claimed)| {
+                ensure!(!*claimed, Error::<T>::QuestAlreadyClaimed);
+                ensure!(*progress >= quest.target, Error::<T>::QuestNotCompleted);
+
+                T::Currency::transfer(
+                    &Self::account_id(),
+                    &who,
+                    quest.reward,
+                    ExistenceRequirement::KeepAlive,
+                )?;
+                *claimed = true;
+                Ok::<_, DispatchError>(())
+            })?;
+            QuestBadges::<T>::insert(&who, quest_id, ());
+
+            Self::deposit_event(Event::QuestRewardClaimed {
+                quest: quest_id,
+                account: who,
+                reward: quest.reward,
+            });
+            Ok(().into())
+        }
+
+        /// Pay out the caller's fee rebates from periods that have ended.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(3, 3))]
+        pub fn claim_fee_rebate(origin: OriginFor<T>) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+
+            let amount = FeeRebates::<T>::try_mutate_exists(&who, |maybe_rebates| {
+                let mut rebates = maybe_rebates.take().unwrap_or_default();
+                Self::vest_rebates(&mut rebates);
+                let amount = sp_std::mem::take(&mut rebates.2);
+                ensure!(!amount.is_zero(), Error::<T>::NoRebateToClaim);
+
+                T::Currency::transfer(
+                    &Self::rebate_account_id(),
+                    &who,
+                    amount,
+                    ExistenceRequirement::KeepAlive,
+                )?;
+                if !rebates.1.is_zero() {
+                    *maybe_rebates = Some(rebates);
+                }
+                Ok::<_, DispatchError>(amount)
+            })?;
+
+            Self::deposit_event(Event::FeeRebateClaimed {
+                account: who,
+                amount,
+            });
+            Ok(().into())
+        }
+
+        /// Evolve one of the caller's kitties to its next stage, raising its pattern intensity.
+        /// Stage `n` needs the kitty to be `n` times `EvolutionInterval` blocks old. Game servers
+        /// with `GAME_EVOLVE` can evolve their owners' kitties.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(6, 6))]
+        pub fn evolve_kitty(
+            origin: OriginFor<T>,
+            kitty_id: KittyIndexOf<T>,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+            let (_, kitty) = Self::ensure_owner_or_game_server(&who, kitty_id, GAME_EVOLVE)?;
+            ensure!(
+                kitty.dna().len() > PATTERN_GENE,
+                Error::<T>::UnsupportedGenome
+            );
+            let stage = Self::evolution_stage(kitty_id);
+            ensure!(stage < MAX_EVOLUTION_STAGE, Error::<T>::KittyFullyEvolved);
+            let age =
+                frame_system::Module::<T>::block_number().saturating_sub(Self::minted_at(kitty_id));
+            ensure!(
+                age >= T::EvolutionInterval::get().saturating_mul((stage + 1).into()),
+                Error::<T>::KittyTooYoung
+            );
+
+            let evolved = kitty.evolved();
+            let (old_hash, new_hash) = (blake2_256(kitty.dna()), blake2_256(evolved.dna()));
+            if new_hash != old_hash {
+                ensure!(
+                    !DnaIndex::<T>::contains_key(new_hash),
+                    Error::<T>::DuplicateKitty
+                );
+                let pattern = |kitty: &Kitty| (PATTERN_GENE as u8, kitty.dna()[PATTERN_GENE]);
+                let (old_trait, new_trait) = (pattern(&kitty), pattern(&evolved));
+                DnaIndex::<T>::remove(old_hash);
+                DnaIndex::<T>::insert(new_hash, kitty_id);
+                TraitIndex::<T>::remove((old_trait.0, old_trait.1 / TRAIT_BUCKET_SIZE), kitty_id);
+                TraitIndex::<T>::insert(
+                    (new_trait.0, new_trait.1 / TRAIT_BUCKET_SIZE),
+                    kitty_id,
+                    (),
+                );
+                orml_nft::Tokens::<T>::mutate(Self::class_id(), kitty_id, |token| {
+                    if let Some(token) = token {
+                        token.data = evolved.clone();
+                    }
+                });
+            }
+            EvolutionStage::<T>::insert(kitty_id, stage + 1);
+
+            Self::deposit_event(Event::KittyEvolved {
+                kitty: kitty_id,
+                stage: stage + 1,
+                dna: evolved,
+            });
+            Ok(().into())
+        }
+
+        /// Mint the caller a certificate of their kitty's pedigree, `PedigreeDepth`
+        /// generations deep. Certificates cannot be transferred.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(2u64.saturating_pow(T::PedigreeDepth::get() + 1), 2))]
+        pub fn mint_pedigree_certificate(
+            origin: OriginFor<T>,
+            kitty_id: KittyIndexOf<T>,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+            Self::ensure_owner(&who, kitty_id)?;
+
+            let lineage = Self::lineage_hash(kitty_id, T::PedigreeDepth::get());
+            let certificate_id = NextCertificateId::<T>::get();
+            PedigreeCertificates::<T>::insert(
+                certificate_id,
+                PedigreeCertificate {
+                    holder: who.clone(),
+                    kitty: kitty_id,
+                    lineage,
+                    issued_at: frame_system::Module::<T>::block_number(),
+                },
+            );
+            NextCertificateId::<T>::put(certificate_id.saturating_add(1));
+
+            Self::deposit_event(Event::PedigreeCertified {
+                certificate: certificate_id,
+                kitty: kitty_id,
+                holder: who,
+                lineage,
+            });
+            Ok(().into())
+        }
+
+        /// Mint a license to breed with the caller's kitty `uses` times, for sale at `price` if
+        /// it is set. The kitty itself stays with the caller.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(2, 2))]
+        pub fn mint_breeding_license(
+            origin: OriginFor<T>,
+            kitty_id: KittyIndexOf<T>,
+            uses: u32,
+            price: Option<BalanceOf<T>>,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+            Self::ensure_owner(&who, kitty_id)?;
+            ensure!(uses > 0, Error::<T>::InvalidLicenseUses);
+
+            let license_id = NextLicenseId::<T>::get();
+            BreedingLicenses::<T>::insert(
+                license_id,
+                BreedingLicense {
+                    kitty: kitty_id,
+                    grantor: who.clone(),
+                    holder: who.clone(),
+                    uses_left: uses,
+                    price,
+                },
+            );
+            NextLicenseId::<T>::put(license_id.saturating_add(1));
+
+            Self::deposit_event(Event::BreedingLicenseMinted {
+                license: license_id,
+                kitty: kitty_id,
+                holder: who,
+                uses,
+                price,
+            });
+            Ok(().into())
+        }
+
+        /// Put one of the caller's breeding licenses up for sale at `price`, or take it off the
+        /// market with `None`.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(1, 1))]
+        pub fn set_license_price(
+            origin: OriginFor<T>,
+            license_id: u32,
+            price: Option<BalanceOf<T>>,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            BreedingLicenses::<T>::try_mutate(license_id, |license| {
+                let license = license.as_mut().ok_or(Error::<T>::LicenseNotFound)?;
+                ensure!(license.holder == who, Error::<T>::NotLicenseHolder);
+                license.price = price;
+                Ok::<_, DispatchError>(())
+            })?;
+
+            Self::deposit_event(Event::BreedingLicensePriceSet {
+                license: license_id,
+                holder: who,
+                price,
+            });
+            Ok(().into())
+        }
+
+        /// Buy a breeding license at its asking price. It is taken off the market until its new
+        /// holder sets a price.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(5, 4))]
+        pub fn buy_breeding_license(
+            origin: OriginFor<T>,
+            license_id: u32,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+            Self::ensure_can_trade(&who)?;
+            let mut license =
+                Self::breeding_licenses(license_id).ok_or(Error::<T>::LicenseNotFound)?;
+            let price = license.price.ok_or(Error::<T>::LicenseNotForSale)?;
+            let seller = license.holder.clone();
+            ensure!(seller != who, Error::<T>::NotLicenseHolder);
+            Self::ensure_license_granted(&license)?;
+
+            with_transaction_result(|| {
+                Self::record_spending(&who, price)?;
+                T::Currency::transfer(&who, &seller, price, ExistenceRequirement::KeepAlive)?;
+                license.holder = who.clone();
+                license.price = None;
+                BreedingLicenses::<T>::insert(license_id, license);
+                Ok(())
             })?;
+
+            Self::deposit_event(Event::BreedingLicenseSold {
+                license: license_id,
+                seller,
+                buyer: who,
+                price,
+            });
+            Ok(().into())
+        }
+
+        /// Breed the caller's `kitty_id` with the kitty of one of the caller's breeding
+        /// licenses, using up one of its uses. The caller pays the breeding fee and owns the
+        /// offspring.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(10, 19 + 2 * T::DnaLength::get() as u64) + quest_weight::<T>())]
+        pub fn breed_with_license(
+            origin: OriginFor<T>,
+            license_id: u32,
+            kitty_id: KittyIndexOf<T>,
+        ) -> DispatchResultWithPostInfo {
+            let who = Self::active_signer(origin)?;
+            Self::ensure_not_frozen()?;
+            let mut license =
+                Self::breeding_licenses(license_id).ok_or(Error::<T>::LicenseNotFound)?;
+            ensure!(license.holder == who, Error::<T>::NotLicenseHolder);
+            Self::ensure_license_granted(&license)?;
+            Self::ensure_owner(&who, kitty_id)?;
+
+            let pair = Self::breeding_pair(None, kitty_id, license.kitty)?;
+            Self::do_breed(&who, kitty_id, license.kitty, pair)?;
+
+            license.uses_left = license.uses_left.saturating_sub(1);
+            let (licensed_kitty, uses_left) = (license.kitty, license.uses_left);
+            if uses_left == 0 {
+                BreedingLicenses::<T>::remove(license_id);
+            } else {
+                BreedingLicenses::<T>::insert(license_id, license);
+            }
+
+            Self::deposit_event(Event::BreedingLicenseUsed {
+                license: license_id,
+                kitty: licensed_kitty,
+                holder: who,
+                uses_left,
+            });
+            Ok(().into())
+        }
+
+        /// Let a kitty be listed again before its resale cooldown is over.
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1) + audit_weight::<T>())]
+        pub fn clear_resale_cooldown(
+            origin: OriginFor<T>,
+            kitty_id: KittyIndexOf<T>,
+        ) -> DispatchResultWithPostInfo {
+            T::ForceOrigin::ensure_origin(origin)?;
+
+            ResaleLockedUntil::<T>::remove(kitty_id);
+
+            Self::deposit_admin_event(Event::ResaleCooldownCleared { kitty: kitty_id });
+            Ok(().into())
+        }
+
+        /// Set the flagged trade count at which accounts are barred from trading, or `None` to
+        /// only flag.
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1) + audit_weight::<T>())]
+        pub fn set_wash_trade_threshold(
+            origin: OriginFor<T>,
+            threshold: Option<u32>,
+        ) -> DispatchResultWithPostInfo {
+            T::ForceOrigin::ensure_origin(origin)?;
+
+            match threshold {
+                Some(threshold) => WashTradeThreshold::<T>::put(threshold),
+                None => WashTradeThreshold::<T>::kill(),
+            }
+
+            Self::deposit_admin_event(Event::WashTradeThresholdSet { threshold });
+            Ok(().into())
+        }
+
+        /// Reject new listings priced below `min` or above `max`, either of which may be `None`
+        /// for no bound. Existing listings are not affected.
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(2) + audit_weight::<T>())]
+        pub fn set_listing_price_bounds(
+            origin: OriginFor<T>,
+            min: Option<BalanceOf<T>>,
+            max: Option<BalanceOf<T>>,
+        ) -> DispatchResultWithPostInfo {
+            T::ForceOrigin::ensure_origin(origin)?;
+            if let (Some(min), Some(max)) = (min, max) {
+                ensure!(min <= max, Error::<T>::InvalidPriceBounds);
+            }
+
+            MinListingPrice::<T>::set(min);
+            MaxListingPrice::<T>::set(max);
+
+            Self::deposit_admin_event(Event::ListingPriceBoundsSet { min, max });
+            Ok(().into())
+        }
+
+        /// Let a restricted account trade again and reset its flagged trade count.
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(2) + audit_weight::<T>())]
+        pub fn lift_trading_restriction(
+            origin: OriginFor<T>,
+            account: T::AccountId,
+        ) -> DispatchResultWithPostInfo {
+            T::ForceOrigin::ensure_origin(origin)?;
+
+            TradingRestricted::<T>::remove(&account);
+            SuspiciousTrades::<T>::remove(&account);
+
+            Self::deposit_admin_event(Event::TradingRestrictionLifted { account });
+            Ok(().into())
+        }
+
+        /// Set the price of creating a kitty, or go back to `MintPrice` with `None`.
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1) + audit_weight::<T>())]
+        pub fn set_mint_price(
+            origin: OriginFor<T>,
+            price: Option<BalanceOf<T>>,
+        ) -> DispatchResultWithPostInfo {
+            T::ForceOrigin::ensure_origin(origin)?;
+
+            MintPriceOverride::<T>::set(price);
+
+            Self::deposit_admin_event(Event::MintPriceSet {
+                price: Self::mint_price(),
+            });
+            Ok(().into())
+        }
+
+        /// Stop kitties from being minted, bred, moved or traded until `thaw_collection`, as a
+        /// safety net around runtime upgrades. Admin calls and migrations still work.
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1) + audit_weight::<T>())]
+        pub fn freeze_collection(origin: OriginFor<T>) -> DispatchResultWithPostInfo {
+            T::ForceOrigin::ensure_origin(origin)?;
+
+            Frozen::<T>::put(true);
+
+            Self::deposit_admin_event(Event::CollectionFrozen);
+            Ok(().into())
+        }
+
+        /// Lift a `freeze_collection`.
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1) + audit_weight::<T>())]
+        pub fn thaw_collection(origin: OriginFor<T>) -> DispatchResultWithPostInfo {
+            T::ForceOrigin::ensure_origin(origin)?;
+
+            Frozen::<T>::kill();
+
+            Self::deposit_admin_event(Event::CollectionThawed);
+            Ok(().into())
+        }
+
+        /// Close the marketplace for the block range `[start, end)`, or reopen it with `None`.
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1) + audit_weight::<T>())]
+        pub fn set_maintenance_window(
+            origin: OriginFor<T>,
+            window: Option<(T::BlockNumber, T::BlockNumber)>,
+        ) -> DispatchResultWithPostInfo {
+            T::ForceOrigin::ensure_origin(origin)?;
+
+            match window {
+                Some((start, end)) => {
+                    ensure!(start < end, Error::<T>::InvalidMaintenanceWindow);
+                    MaintenanceWindow::<T>::put((start, end));
+                }
+                None => MaintenanceWindow::<T>::kill(),
+            }
+
+            Self::deposit_admin_event(Event::MaintenanceWindowSet { window });
+            Ok(().into())
+        }
+
+        /// Set the jurisdiction `who` is taxed in, or `None` to clear it.
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1) + audit_weight::<T>())]
+        pub fn set_jurisdiction(
+            origin: OriginFor<T>,
+            who: T::AccountId,
+            jurisdiction: Option<JurisdictionTag>,
+        ) -> DispatchResultWithPostInfo {
+            T::ForceOrigin::ensure_origin(origin)?;
+
+            match jurisdiction {
+                Some(jurisdiction) => Jurisdictions::<T>::insert(&who, jurisdiction),
+                None => Jurisdictions::<T>::remove(&who),
+            }
+
+            Self::deposit_admin_event(Event::JurisdictionSet {
+                account: who,
+                jurisdiction,
+            });
+            Ok(().into())
+        }
+
+        /// Require a reputation score of at least `min_score` to list kitties above `threshold`,
+        /// or `None` to let anyone list at any price.
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1) + audit_weight::<T>())]
+        pub fn set_reputation_gate(
+            origin: OriginFor<T>,
+            gate: Option<(BalanceOf<T>, u32)>,
+        ) -> DispatchResultWithPostInfo {
+            T::ForceOrigin::ensure_origin(origin)?;
+
+            match gate {
+                Some(gate) => ReputationGate::<T>::put(gate),
+                None => ReputationGate::<T>::kill(),
+            }
+
+            Self::deposit_admin_event(Event::ReputationGateSet { gate });
+            Ok(().into())
+        }
+
+        /// Replace the breeding season schedule. An empty schedule lets kitties breed at any
+        /// time.
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1) + audit_weight::<T>())]
+        pub fn set_breeding_seasons(
+            origin: OriginFor<T>,
+            seasons: Vec<(T::BlockNumber, T::BlockNumber)>,
+        ) -> DispatchResultWithPostInfo {
+            T::ForceOrigin::ensure_origin(origin)?;
+            ensure!(
+                seasons.len() as u32 <= T::MaxBreedingSeasons::get(),
+                Error::<T>::TooManyBreedingSeasons
+            );
+            ensure!(
+                seasons.iter().all(|(start, end)| start < end)
+                    && seasons.windows(2).all(|pair| pair[0].1 <= pair[1].0),
+                Error::<T>::InvalidBreedingSeasons
+            );
+
+            BreedingSeasons::<T>::put(&seasons);
+
+            Self::deposit_admin_event(Event::BreedingSeasonsSet { seasons });
+            Ok(().into())
+        }
+
+        /// Pay `amount_per_kitty` from the promo pot to the owner of every kitty minted so far,
+        /// for each kitty they own. Owners are paid `MaxAirdropsPerBlock` kitties at a time from
+        /// the next block on, so a kitty sold meanwhile pays whoever owns it when its turn comes.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(2, 1) + audit_weight::<T>())]
+        pub fn snapshot_and_drop(
+            origin: OriginFor<T>,
+            amount_per_kitty: BalanceOf<T>,
+        ) -> DispatchResultWithPostInfo {
+            T::ForceOrigin::ensure_origin(origin)?;
+            ensure!(
+                ActiveAirdrop::<T>::get().is_none(),
+                Error::<T>::AirdropInProgress
+            );
+
+            let kitties = NftModule::<T>::next_token_id(Self::class_id());
+            ActiveAirdrop::<T>::put(Airdrop {
+                amount_per_kitty,
+                next: Zero::zero(),
+                end: kitties,
+                paid: 0,
+            });
+
+            Self::deposit_admin_event(Event::AirdropStarted {
+                amount_per_kitty,
+                kitties,
+            });
+            Ok(().into())
+        }
+
+        /// Set the name, description and banner image URI explorers show for the collection.
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1) + audit_weight::<T>())]
+        pub fn set_class_metadata(
+            origin: OriginFor<T>,
+            name: Vec<u8>,
+            description: Vec<u8>,
+            banner_uri: Vec<u8>,
+        ) -> DispatchResultWithPostInfo {
+            T::ForceOrigin::ensure_origin(origin)?;
+            let max = T::MaxMetadataLength::get() as usize;
+            ensure!(
+                name.len() <= max && description.len() <= max && banner_uri.len() <= max,
+                Error::<T>::MetadataTooLong
+            );
+
+            let metadata = CollectionMetadata {
+                name,
+                description,
+                banner_uri,
+            };
+            ClassMetadata::<T>::put(&metadata);
+
+            Self::deposit_admin_event(Event::ClassMetadataSet { metadata });
             Ok(().into())
         }
     }
@@ -0,0 +1,110 @@
+//! Binary merkle trees over blake2_256 leaf hashes, used for the snapshots of kitty owners.
+//!
+//! A node hashes the concatenation of its two children. A node without a sibling moves up a
+//! level unchanged, so every tree of leaves has exactly one root and an empty tree's root is
+//! all zeroes.
+//!
+//! Roots can also be built a few leaves at a time: `append` each leaf to a list of peaks, the
+//! roots of the complete subtrees so far with their heights, and `fold` them at the end.
+
+use sp_io::hashing::blake2_256;
+use sp_std::vec::Vec;
+
+fn parent(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut pair = [0u8; 64];
+    pair[..32].copy_from_slice(left);
+    pair[32..].copy_from_slice(right);
+    blake2_256(&pair)
+}
+
+fn next_level(level: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => parent(left, right),
+            [single] => *single,
+            _ => unreachable!("chunks of two hold one or two nodes"),
+        })
+        .collect()
+}
+
+/// Root of the tree over `leaves`.
+pub fn root(mut leaves: Vec<[u8; 32]>) -> [u8; 32] {
+    if leaves.is_empty() {
+        return [0; 32];
+    }
+    while leaves.len() > 1 {
+        leaves = next_level(&leaves);
+    }
+    leaves[0]
+}
+
+/// Add the next leaf to the `peaks` of the leaves before it.
+pub fn append(peaks: &mut Vec<(u32, [u8; 32])>, leaf: [u8; 32]) {
+    let (mut height, mut node) = (0, leaf);
+    while let Some(&(peak_height, peak)) = peaks.last() {
+        if peak_height != height {
+            break;
+        }
+        peaks.pop();
+        node = parent(&peak, &node);
+        height += 1;
+    }
+    peaks.push((height, node));
+}
+
+/// Root of the tree whose leaves were appended to `peaks`, the same as `root` over them.
+pub fn fold(peaks: &[(u32, [u8; 32])]) -> [u8; 32] {
+    let mut nodes = peaks.iter().rev().map(|(_, node)| *node);
+    let last = match nodes.next() {
+        Some(last) => last,
+        None => return [0; 32],
+    };
+    nodes.fold(last, |right, left| parent(&left, &right))
+}
+
+/// Siblings on the path from leaf `index` to the root, lowest first. Levels where the path
+/// has no sibling are left out.
+pub fn proof(mut leaves: Vec<[u8; 32]>, mut index: usize) -> Vec<[u8; 32]> {
+    let mut siblings = Vec::new();
+    while leaves.len() > 1 {
+        if let Some(sibling) = leaves.get(index ^ 1) {
+            siblings.push(*sibling);
+        }
+        leaves = next_level(&leaves);
+        index /= 2;
+    }
+    siblings
+}
+
+/// Whether `proof` shows `leaf` at `index` in a tree of `count` leaves with `root`.
+pub fn verify(
+    root: [u8; 32],
+    leaf: [u8; 32],
+    index: usize,
+    count: usize,
+    proof: &[[u8; 32]],
+) -> bool {
+    if index >= count {
+        return false;
+    }
+    let (mut node, mut index, mut width) = (leaf, index, count);
+    let mut siblings = proof.iter();
+    while width > 1 {
+        // The last node of an odd level has no sibling
+        if index % 2 == 1 || index + 1 < width {
+            let sibling = match siblings.next() {
+                Some(sibling) => sibling,
+                None => return false,
+            };
+            node = if index % 2 == 0 {
+                parent(&node, sibling)
+            } else {
+                parent(sibling, &node)
+            };
+        }
+        index /= 2;
+        width = width / 2 + width % 2;
+    }
+    siblings.next().is_none() && node == root
+}
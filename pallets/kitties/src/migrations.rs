@@ -0,0 +1,237 @@
+//! Storage migrations for the kitties pallet.
+
+use crate::{
+    BalanceOf, BreedingLicense, BreedingLicenses, CollectionBids, Config, CostBasis, Denomination,
+    DnaIndex, Kitty, KittyExchange, KittyIndexOf, Listing, MarketHolds, OwnerListings, Pallet,
+    PendingSales, PriceIndex, Releases, ReturnableSale, ReturnableSales, StorageVersion,
+};
+use codec::{Decode, Encode};
+use frame_support::{
+    debug,
+    storage::migration::{take_storage_value, StorageIterator},
+    traits::{Get, PalletInfo},
+    weights::Weight,
+};
+use sp_io::hashing::blake2_256;
+use sp_runtime::traits::{Saturating, Zero};
+use sp_std::vec::Vec;
+
+/// Kitty token as stored before genomes were versioned.
+#[derive(Decode)]
+struct V1TokenInfo<AccountId> {
+    metadata: Vec<u8>,
+    owner: AccountId,
+    data: [u8; 16],
+}
+
+/// Breeding license as stored before its grantor was recorded.
+#[derive(Decode)]
+struct V7BreedingLicense<AccountId, KittyIndex, Balance> {
+    kitty: KittyIndex,
+    holder: AccountId,
+    uses_left: u32,
+    price: Option<Balance>,
+}
+
+/// Returnable sale as stored before the tax entries to undo on a return were recorded.
+#[derive(Decode)]
+struct V8ReturnableSale<AccountId, KittyIndex, Balance, BlockNumber> {
+    kitty: KittyIndex,
+    seller: AccountId,
+    buyer: AccountId,
+    refund: Balance,
+    until: BlockNumber,
+}
+
+/// Storage prefix of the pallet in the runtime.
+fn pallet_prefix<T: Config>() -> &'static [u8] {
+    <T as frame_system::Config>::PalletInfo::name::<Pallet<T>>()
+        .expect("Pallet is part of the runtime")
+        .as_bytes()
+}
+
+/// Listings as stored before V6, in `KittyExchange: map KittyIndex => Option<Listing>`.
+fn unclassed_listings<T: Config>() -> StorageIterator<Option<Listing<T>>> {
+    StorageIterator::new(pallet_prefix::<T>(), b"KittyExchange")
+}
+
+/// Decode an `unclassed_listings` entry, whose key is `blake2_128_concat(kitty_id)`.
+fn decode_unclassed_listing<T: Config>(
+    (key, listing): (Vec<u8>, Option<Listing<T>>),
+) -> Option<(KittyIndexOf<T>, Option<Listing<T>>)> {
+    let kitty_id = key
+        .get(16..)
+        .and_then(|mut key| KittyIndexOf::<T>::decode(&mut key).ok())?;
+    Some((kitty_id, listing))
+}
+
+/// Wrap the raw dna of every kitty in a version 1 `Genome`.
+pub fn migrate_to_v2<T: Config>() -> Weight {
+    let mut translated = 0u64;
+    orml_nft::Tokens::<T>::translate_values::<V1TokenInfo<T::AccountId>, _>(|token| {
+        translated += 1;
+        Some(orml_nft::TokenInfo {
+            metadata: token.metadata,
+            owner: token.owner,
+            data: Kitty::from(token.data),
+        })
+    });
+    StorageVersion::<T>::put(Releases::V2);
+
+    T::DbWeight::get().reads_writes(translated + 1, translated + 1)
+}
+
+/// Index existing listings by seller in `OwnerListings` and by price in `PriceIndex`.
+pub fn migrate_to_v3<T: Config>() -> Weight {
+    let mut listings = 0u64;
+    for (kitty_id, listing) in unclassed_listings::<T>().filter_map(decode_unclassed_listing::<T>) {
+        listings += 1;
+        if let Some(Listing(seller, price, denomination)) = listing {
+            OwnerListings::<T>::insert(seller, kitty_id, ());
+            if denomination == Denomination::Native {
+                PriceIndex::<T>::insert(Pallet::<T>::price_key(price), kitty_id, ());
+            }
+        }
+    }
+    StorageVersion::<T>::put(Releases::V3);
+
+    T::DbWeight::get().reads_writes(listings + 1, 2 * listings + 1)
+}
+
+/// Re-mint the kitties of the legacy `pallets/kitty` template, which kept them in a
+/// `Kitties: double_map AccountId, u32 => Kitty([u8; 16])` next to a `NextKittyId` counter
+/// under the same pallet prefix. Owners and dna carry over; kitties get fresh ids.
+///
+/// The template allowed kitties with the same dna, which the pallet does not. A duplicate is
+/// re-minted with its dna remixed by hashing until it is unique. Re-mints pay no milestone
+/// rewards.
+pub fn migrate_to_v4<T: Config>() -> Weight {
+    let prefix = pallet_prefix::<T>();
+
+    let mut migrated = 0u64;
+    let mut remixed = 0u64;
+    for (key, mut dna) in StorageIterator::<[u8; 16]>::new(prefix, b"Kitties").drain() {
+        migrated += 1;
+        // Keys are `blake2_128_concat(owner) ++ blake2_128_concat(kitty_id)`
+        let owner = match key
+            .get(16..)
+            .and_then(|mut key| T::AccountId::decode(&mut key).ok())
+        {
+            Some(owner) => owner,
+            None => continue,
+        };
+        while DnaIndex::<T>::contains_key(blake2_256(&dna)) {
+            let remix = (b"kitties/remix", dna).using_encoded(blake2_256);
+            dna.copy_from_slice(&remix[..16]);
+            remixed += 1;
+        }
+        if let Err(e) = Pallet::<T>::mint_unrewarded(&owner, Kitty::from(dna)) {
+            debug::warn!("Could not re-mint legacy kitty: {:?}", e);
+        }
+    }
+    take_storage_value::<u32>(prefix, b"NextKittyId", &[]);
+    StorageVersion::<T>::put(Releases::V4);
+
+    // Each re-mint touches the nft tokens, owner index, traits and mint counters
+    T::DbWeight::get().reads_writes(6 * migrated + remixed + 2, 8 * migrated + 2)
+}
+
+/// Index the dna of every existing kitty in `DnaIndex`.
+pub fn migrate_to_v5<T: Config>() -> Weight {
+    let mut indexed = 0u64;
+    for (kitty_id, token) in orml_nft::Tokens::<T>::iter_prefix(Pallet::<T>::class_id()) {
+        indexed += 1;
+        DnaIndex::<T>::insert(blake2_256(token.data.dna()), kitty_id);
+    }
+    StorageVersion::<T>::put(Releases::V5);
+
+    T::DbWeight::get().reads_writes(indexed + 2, indexed + 1)
+}
+
+/// Key every listing by the pallet's kitty class as well as the kitty.
+pub fn migrate_to_v6<T: Config>() -> Weight {
+    // Collect before inserting, as the old and new keys share a prefix
+    let listings = unclassed_listings::<T>()
+        .drain()
+        .filter_map(decode_unclassed_listing::<T>)
+        .collect::<Vec<_>>();
+    let class_id = Pallet::<T>::class_id();
+    let migrated = listings.len() as u64;
+    for (kitty_id, listing) in listings {
+        KittyExchange::<T>::insert(class_id, kitty_id, listing);
+    }
+    StorageVersion::<T>::put(Releases::V6);
+
+    T::DbWeight::get().reads_writes(migrated + 2, 2 * migrated + 1)
+}
+
+/// Record the funds reserved for collection bids and pending sales in `MarketHolds`. The funds
+/// stay reserved.
+pub fn migrate_to_v7<T: Config>() -> Weight {
+    let held = CollectionBids::<T>::iter()
+        .map(|(_, bid)| {
+            (
+                bid.bidder,
+                bid.max_price.saturating_mul(bid.quantity.into()),
+            )
+        })
+        .chain(PendingSales::<T>::iter().map(|(_, sale)| (sale.buyer, sale.price)))
+        .collect::<Vec<_>>();
+    let migrated = held.len() as u64;
+    for (who, amount) in held {
+        MarketHolds::<T>::mutate(&who, |held| *held = held.saturating_add(amount));
+    }
+    StorageVersion::<T>::put(Releases::V7);
+
+    T::DbWeight::get().reads_writes(2 * migrated + 2, migrated + 1)
+}
+
+/// Record the current owner of each licensed kitty as the grantor of its breeding licenses.
+/// Licenses for kitties that no longer exist are dropped.
+pub fn migrate_to_v8<T: Config>() -> Weight {
+    let mut translated = 0u64;
+    BreedingLicenses::<T>::translate::<
+        V7BreedingLicense<T::AccountId, KittyIndexOf<T>, BalanceOf<T>>,
+        _,
+    >(|_, license| {
+        translated += 1;
+        let (grantor, _) = Pallet::<T>::kitty_of(license.kitty)?;
+        Some(BreedingLicense {
+            kitty: license.kitty,
+            grantor,
+            holder: license.holder,
+            uses_left: license.uses_left,
+            price: license.price,
+        })
+    });
+    StorageVersion::<T>::put(Releases::V8);
+
+    T::DbWeight::get().reads_writes(2 * translated + 1, translated + 1)
+}
+
+/// Record nothing to undo in the tax reports for returnable sales made before V9, and their
+/// kitty's current cost basis as the one a return restores.
+pub fn migrate_to_v9<T: Config>() -> Weight {
+    let mut translated = 0u64;
+    let era = Pallet::<T>::tax_era();
+    ReturnableSales::<T>::translate::<
+        V8ReturnableSale<T::AccountId, KittyIndexOf<T>, BalanceOf<T>, T::BlockNumber>,
+        _,
+    >(|_, sale| {
+        translated += 1;
+        Some(ReturnableSale {
+            kitty: sale.kitty,
+            seller: sale.seller,
+            buyer: sale.buyer,
+            refund: sale.refund,
+            until: sale.until,
+            era,
+            price: Zero::zero(),
+            proceeds: Zero::zero(),
+            cost_basis: CostBasis::<T>::get(sale.kitty),
+        })
+    });
+    StorageVersion::<T>::put(Releases::V9);
+
+    T::DbWeight::get().reads_writes(2 * translated + 2, translated + 1)
+}
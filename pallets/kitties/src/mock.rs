@@ -1,12 +1,16 @@
 use std::cell::RefCell;
 
 use crate as pallet_kitties;
-use frame_support::{parameter_types, traits::Randomness};
+use frame_support::{
+    parameter_types,
+    traits::{ExistenceRequirement, GenesisBuild, Randomness},
+};
 use frame_system as system;
 use sp_core::H256;
 use sp_runtime::{
-    testing::Header,
-    traits::{BlakeTwo256, IdentityLookup},
+    testing::{Header, TestSignature, UintAuthorityId},
+    traits::{BlakeTwo256, Convert, IdentityLookup},
+    ModuleId, Permill,
 };
 
 type UncheckedExtrinsic = frame_system::mocking::MockUncheckedExtrinsic<Test>;
@@ -20,15 +24,16 @@ frame_support::construct_runtime!(
         UncheckedExtrinsic = UncheckedExtrinsic,
     {
         System: frame_system::{Module, Call, Config, Storage, Event<T>},
-        KittiesModule: pallet_kitties::{Module, Call, Storage, Event<T>},
+        KittiesModule: pallet_kitties::{Module, Call, Config, Storage, Event<T>},
         Balances: pallet_balances::{Module, Call, Storage, Config<T>, Event<T>},
+        NFT: orml_nft::{Module, Storage},
     }
 );
 
 parameter_types! {
     pub const BlockHashCount: u64 = 250;
     pub const SS58Prefix: u8 = 42;
-    pub const ExistentialDeposit: u64 = 1;
+    pub static ExistentialDeposit: u64 = 1;
 }
 
 impl pallet_balances::Config for Test {
@@ -78,20 +83,184 @@ impl Randomness<H256> for MockRandom {
     }
 }
 
+impl orml_nft::Config for Test {
+    type ClassId = u32;
+    type TokenId = u32;
+    type ClassData = ();
+    type TokenData = pallet_kitties::Kitty;
+}
+
+parameter_types! {
+    pub const KittiesModuleId: ModuleId = ModuleId(*b"py/kitty");
+    pub const MarketplaceFee: Permill = Permill::from_percent(10);
+    pub const ReferralShare: Permill = Permill::from_percent(50);
+    pub const MaxGeneration: Option<u32> = Some(2);
+    pub const MaxOracleAge: u64 = 10;
+    pub const ResaleCooldown: u64 = 5;
+    pub const ConfirmationPeriod: u64 = 5;
+    pub const InstallmentPeriod: u64 = 10;
+    pub const MaxInstallments: u32 = 4;
+    pub const LayawayPenalty: Permill = Permill::from_percent(10);
+    pub const WashTradeWindow: u64 = 20;
+    pub const MilestoneReward: u64 = 50;
+    pub const MaxQuests: u32 = 2;
+    pub const MaxQuestProgressRemovals: u32 = 1;
+    pub const MaxRaceEntrants: u32 = 3;
+    pub const SpendingPeriod: u64 = 10;
+    pub const StatsPeriod: u64 = 10;
+    pub const StatsHistory: u32 = 2;
+    pub const TradeHistoryLength: u32 = 2;
+    pub const MaxDelistAll: u32 = 2;
+    pub const MaxGrantKitties: u32 = 3;
+    pub const FlipFeeDecay: u64 = 10;
+    pub const OwnerSnapshotPeriod: u64 = 10;
+    pub const MaxSnapshotKittiesPerBlock: u32 = 2;
+    pub const EvolutionInterval: u64 = 10;
+    pub const MaxInheritedKitties: u32 = 2;
+    pub const MaxFloorBuy: u32 = 3;
+    pub const MaxScheduledPerBlock: u32 = 2;
+    pub const CloneFee: u64 = 100;
+    pub const PedigreeDepth: u32 = 2;
+    pub const AdoptionFee: u64 = 5;
+    pub const MintPrice: u64 = 0;
+    pub const MaxBreedingSeasons: u32 = 2;
+    pub const MaxMetadataLength: u32 = 16;
+    pub const ReturnPeriod: u64 = 10;
+    pub const RestockingFee: Permill = Permill::from_percent(20);
+    pub const MaxAirdropsPerBlock: u32 = 2;
+    pub const MaxAuditLogLength: u32 = 2;
+    pub const TaxEraLength: u64 = 10;
+    pub const TaxHistory: u32 = 2;
+    pub const MaxTaxPrunesPerBlock: u32 = 2;
+    pub const RebatePeriod: u64 = 10;
+    pub static ForbidIncest: bool = true;
+    pub static BalanceMintGenders: bool = false;
+    pub static MaxMintsPerBlock: u32 = 1_000;
+    pub static FlipFee: Permill = Permill::from_percent(10);
+    pub static MinListingStability: u64 = 0;
+    pub static OracleRate: Option<(u64, u64)> = None;
+    pub static DnaLength: u32 = 16;
+    pub static TwinProbability: Permill = Permill::zero();
+    pub static MilestoneInterval: Option<u64> = None;
+    pub static FeeRebate: Permill = Permill::zero();
+    pub static BuyerExistenceRequirement: ExistenceRequirement = ExistenceRequirement::KeepAlive;
+}
+
+pub struct BreedingFee;
+
+impl Convert<u32, u64> for BreedingFee {
+    fn convert(generation: u32) -> u64 {
+        10 * (generation as u64 + 1)
+    }
+}
+
+pub struct MockTax;
+
+/// Sales to buyers in `EU` pay 10% VAT to account 300.
+impl pallet_kitties::TaxHandler<u64, u64> for MockTax {
+    fn sales_tax(
+        _seller: Option<pallet_kitties::JurisdictionTag>,
+        buyer: Option<pallet_kitties::JurisdictionTag>,
+        price: u64,
+    ) -> Option<(u64, u64)> {
+        match buyer {
+            Some(tag) if &tag == b"EU" => Some((300, price / 10)),
+            _ => None,
+        }
+    }
+}
+
+pub struct MockOracle;
+
+impl pallet_kitties::PriceOracle<u64, u64> for MockOracle {
+    fn latest_rate() -> Option<(u64, u64)> {
+        OracleRate::get()
+    }
+}
+
 impl pallet_kitties::Config for Test {
     type Event = Event;
     type RandomnessSource = MockRandom;
-    type KittyIndex = u32;
+    type DnaProvider = pallet_kitties::Blake2Dna;
     type Currency = Balances;
+    type ModuleId = KittiesModuleId;
+    type MarketplaceFee = MarketplaceFee;
+    type FlipFee = FlipFee;
+    type FlipFeeDecay = FlipFeeDecay;
+    type ReferralShare = ReferralShare;
+    type BreedingFee = BreedingFee;
+    type CloneFee = CloneFee;
+    type MaxGeneration = MaxGeneration;
+    type ForbidIncest = ForbidIncest;
+    type BalanceMintGenders = BalanceMintGenders;
+    type PriceOracle = MockOracle;
+    type MaxOracleAge = MaxOracleAge;
+    type ResaleCooldown = ResaleCooldown;
+    type ConfirmationPeriod = ConfirmationPeriod;
+    type InstallmentPeriod = InstallmentPeriod;
+    type MaxInstallments = MaxInstallments;
+    type LayawayPenalty = LayawayPenalty;
+    type WashTradeWindow = WashTradeWindow;
+    type ImportSignature = TestSignature;
+    type ImportSigner = UintAuthorityId;
+    type TradeSignature = TestSignature;
+    type TradeSigner = UintAuthorityId;
+    type DnaLength = DnaLength;
+    type TwinProbability = TwinProbability;
+    type MilestoneInterval = MilestoneInterval;
+    type MilestoneReward = MilestoneReward;
+    type MaxQuests = MaxQuests;
+    type MaxQuestProgressRemovals = MaxQuestProgressRemovals;
+    type MaxRaceEntrants = MaxRaceEntrants;
+    type SpendingPeriod = SpendingPeriod;
+    type StatsPeriod = StatsPeriod;
+    type StatsHistory = StatsHistory;
+    type TradeHistoryLength = TradeHistoryLength;
+    type MaxDelistAll = MaxDelistAll;
+    type MaxMintsPerBlock = MaxMintsPerBlock;
+    type MaxGrantKitties = MaxGrantKitties;
+    type OwnerSnapshotPeriod = OwnerSnapshotPeriod;
+    type MaxSnapshotKittiesPerBlock = MaxSnapshotKittiesPerBlock;
+    type EvolutionInterval = EvolutionInterval;
+    type MinListingStability = MinListingStability;
+    type MaxMetadataLength = MaxMetadataLength;
+    type ReturnPeriod = ReturnPeriod;
+    type RestockingFee = RestockingFee;
+    type MaxAirdropsPerBlock = MaxAirdropsPerBlock;
+    type MaxAuditLogLength = MaxAuditLogLength;
+    type TaxEraLength = TaxEraLength;
+    type TaxHistory = TaxHistory;
+    type MaxTaxPrunesPerBlock = MaxTaxPrunesPerBlock;
+    type MaxInheritedKitties = MaxInheritedKitties;
+    type MaxFloorBuy = MaxFloorBuy;
+    type MaxScheduledPerBlock = MaxScheduledPerBlock;
+    type MintPrice = MintPrice;
+    type MintPriceDestination = ();
+    type AdoptionFee = AdoptionFee;
+    type AdoptionFeeDestination = ();
+    type MaxBreedingSeasons = MaxBreedingSeasons;
+    type PedigreeDepth = PedigreeDepth;
+    type BuyerExistenceRequirement = BuyerExistenceRequirement;
+    type TaxHandler = MockTax;
+    type FeeRebate = FeeRebate;
+    type RebatePeriod = RebatePeriod;
+    type ForceOrigin = system::EnsureRoot<u64>;
 }
 
 // Build genesis storage according to the mock runtime.
 pub fn new_test_ext() -> sp_io::TestExternalities {
-    let mut s: sp_io::TestExternalities = system::GenesisConfig::default()
+    let mut t = system::GenesisConfig::default()
         .build_storage::<Test>()
-        .unwrap()
-        .into();
+        .unwrap();
+    pallet_balances::GenesisConfig::<Test> {
+        balances: vec![(100, 1_000), (101, 1_000), (200, 1_000), (300, 1_000)],
+    }
+    .assimilate_storage(&mut t)
+    .unwrap();
+    GenesisBuild::<Test>::assimilate_storage(&pallet_kitties::GenesisConfig::default(), &mut t)
+        .unwrap();
 
+    let mut s: sp_io::TestExternalities = t.into();
     s.execute_with(|| System::set_block_number(1));
     s
 }
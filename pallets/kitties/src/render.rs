@@ -0,0 +1,139 @@
+//! Deterministic SVG portraits of kitties, drawn from their dna alone, and ERC-721 style
+//! metadata embedding them.
+
+use crate::{Gender, Kitty};
+use sp_std::vec::Vec;
+
+/// Render `kitty` as a 100x100 SVG image.
+///
+/// Colours and shapes are read from fixed dna bytes, so the same kitty always renders the same
+/// image. Bytes beyond the end of a short dna read as zero.
+pub fn render_svg(kitty: &Kitty) -> Vec<u8> {
+    let gene = |index: usize| kitty.dna().get(index).copied().unwrap_or_default();
+    let background = [gene(0) / 2 + 128, gene(1) / 2 + 128, gene(2) / 2 + 128];
+    let fur = [gene(3), gene(4), gene(5)];
+    let eyes = [gene(6), gene(7), gene(8)];
+    let ear_height = 5 + gene(9) % 15;
+    let eye_size = 3 + gene(10) % 6;
+    let smile = 66 + gene(11) % 10;
+
+    let mut svg = Vec::with_capacity(512);
+    push(
+        &mut svg,
+        &[b"<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 100 100\"><rect width=\"100\" height=\"100\" fill=\""],
+    );
+    push_color(&mut svg, background);
+    push(&mut svg, &[b"\"/><polygon points=\"22,40 30,"]);
+    push_number(&mut svg, ear_height.into());
+    push(&mut svg, &[b" 45,28 55,28 70,"]);
+    push_number(&mut svg, ear_height.into());
+    push(&mut svg, &[b" 78,40\" fill=\""]);
+    push_color(&mut svg, fur);
+    push(
+        &mut svg,
+        &[b"\"/><circle cx=\"50\" cy=\"55\" r=\"30\" fill=\""],
+    );
+    push_color(&mut svg, fur);
+    for eye_x in [&b"38"[..], &b"62"[..]].iter() {
+        push(
+            &mut svg,
+            &[b"\"/><circle cx=\"", eye_x, b"\" cy=\"50\" r=\""],
+        );
+        push_number(&mut svg, eye_size.into());
+        push(&mut svg, &[b"\" fill=\""]);
+        push_color(&mut svg, eyes);
+    }
+    push(&mut svg, &[b"\"/><path d=\"M42 66 Q50 "]);
+    push_number(&mut svg, smile.into());
+    push(
+        &mut svg,
+        &[b" 58 66\" stroke=\"#000\" stroke-width=\"2\" fill=\"none\"/></svg>"],
+    );
+    svg
+}
+
+/// Describe kitty `kitty_id` as ERC-721 metadata JSON, the format NFT marketplaces such as
+/// OpenSea read: a name, a description, the portrait as a base64 `data:` URI and one attribute
+/// per trait, next to gender, generation and speed.
+pub fn metadata_json(kitty_id: u64, kitty: &Kitty, generation: u32) -> Vec<u8> {
+    let mut json = Vec::with_capacity(2048);
+    push(&mut json, &[b"{\"name\":\"Kitty #"]);
+    push_number(&mut json, kitty_id);
+    push(&mut json, &[b"\",\"description\":\"Generation "]);
+    push_number(&mut json, generation.into());
+    push(
+        &mut json,
+        &[b" kitty, minted on-chain with its traits read from its dna.\",\"image\":\"data:image/svg+xml;base64,"],
+    );
+    push_base64(&mut json, &render_svg(kitty));
+    let gender: &[u8] = match kitty.gender() {
+        Gender::Male => b"Male",
+        Gender::Female => b"Female",
+    };
+    push(
+        &mut json,
+        &[
+            b"\",\"attributes\":[{\"trait_type\":\"Gender\",\"value\":\"",
+            gender,
+        ],
+    );
+    push(
+        &mut json,
+        &[b"\"},{\"trait_type\":\"Generation\",\"display_type\":\"number\",\"value\":"],
+    );
+    push_number(&mut json, generation.into());
+    push(
+        &mut json,
+        &[b"},{\"trait_type\":\"Speed\",\"display_type\":\"number\",\"value\":"],
+    );
+    push_number(&mut json, kitty.speed().into());
+    for (trait_id, value) in kitty.traits() {
+        push(&mut json, &[b"},{\"trait_type\":\"Trait "]);
+        push_number(&mut json, trait_id.into());
+        push(&mut json, &[b"\",\"value\":"]);
+        push_number(&mut json, value.into());
+    }
+    push(&mut json, &[b"}]}"]);
+    json
+}
+
+fn push(svg: &mut Vec<u8>, parts: &[&[u8]]) {
+    for part in parts {
+        svg.extend_from_slice(part);
+    }
+}
+
+fn push_number(svg: &mut Vec<u8>, number: u64) {
+    if number >= 10 {
+        push_number(svg, number / 10);
+    }
+    svg.push(b'0' + (number % 10) as u8);
+}
+
+fn push_base64(out: &mut Vec<u8>, data: &[u8]) {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for position in 0..4 {
+            if position <= chunk.len() {
+                out.push(ALPHABET[(bits >> (18 - 6 * position) & 0x3f) as usize]);
+            } else {
+                out.push(b'=');
+            }
+        }
+    }
+}
+
+fn push_color(svg: &mut Vec<u8>, rgb: [u8; 3]) {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+    svg.push(b'#');
+    for channel in rgb.iter() {
+        svg.push(HEX[(channel >> 4) as usize]);
+        svg.push(HEX[(channel & 0xf) as usize]);
+    }
+}
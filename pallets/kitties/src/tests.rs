@@ -1,39 +1,95 @@
-use frame_support::{assert_noop, assert_ok, assert_storage_noop};
+use codec::Encode;
+use frame_support::{
+    assert_err, assert_noop, assert_ok, assert_storage_noop, traits::OffchainWorker,
+};
+use sp_core::offchain::{
+    testing::{PendingRequest, TestOffchainExt},
+    OffchainExt,
+};
+use sp_runtime::{offchain::storage::StorageValueRef, testing::TestSignature, Permill};
 
-use crate::{mock::*, Error, Gender, Kitty};
+use crate::{
+    mock::*, Coupon, CouponKind, Error, Gender, Kitty, KittyExport, QuestGoal, GAME_ENTER_RACES,
+    GAME_EVOLVE,
+};
+use sp_core::H256;
+
+fn next_kitty_id() -> u32 {
+    NFT::next_token_id(KittiesModule::class_id())
+}
 
 fn last_event() -> Event {
     System::events().last().unwrap().event.clone()
 }
 
+/// This pallet's events since the last `System::reset_events`.
+fn kitty_events() -> Vec<crate::Event<Test>> {
+    System::events()
+        .into_iter()
+        .filter_map(|record| match record.event {
+            Event::pallet_kitties(event) => Some(event),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Run `f` and check that it only moved funds between `accounts`: total issuance is
+/// unchanged and their balances, reserves included, add up to the same as before. Returns
+/// how much each account gained or lost, in the order given.
+fn balance_deltas(accounts: &[u64], f: impl FnOnce()) -> Vec<i64> {
+    use frame_support::traits::Currency;
+
+    let total = |who: &u64| Balances::total_balance(who) as i64;
+    let issuance = Balances::total_issuance();
+    let before: Vec<i64> = accounts.iter().map(total).collect();
+    f();
+    assert_eq!(
+        Balances::total_issuance(),
+        issuance,
+        "funds were minted or burned"
+    );
+    let deltas: Vec<i64> = accounts
+        .iter()
+        .zip(before)
+        .map(|(who, before)| total(who) - before)
+        .collect();
+    assert_eq!(
+        deltas.iter().sum::<i64>(),
+        0,
+        "funds leaked from {:?}",
+        accounts
+    );
+    deltas
+}
+
 #[test]
 fn can_create() {
     new_test_ext().execute_with(|| {
         assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
 
-        let kitty = Kitty {
-            dna: [
-                214, 209, 234, 245, 69, 67, 6, 171, 41, 106, 181, 116, 218, 245, 185, 201,
-            ],
-        };
-        assert_eq!(KittiesModule::kitties(100, 0), Some(kitty.clone()));
-        assert_eq!(KittiesModule::next_kitty_id(), 1);
+        let kitty = Kitty::from([
+            171, 239, 24, 60, 92, 222, 160, 137, 246, 159, 206, 34, 76, 117, 221, 106,
+        ]);
+        assert_eq!(KittiesModule::kitties(&100, 0), Some(kitty.clone()));
+        assert_eq!(next_kitty_id(), 1);
 
         assert_eq!(
             last_event(),
-            Event::pallet_kitties(crate::Event::<Test>::KittyCreated(kitty, 0, 100))
+            Event::pallet_kitties(crate::Event::<Test>::KittyCreated {
+                dna: kitty,
+                kitty: 0,
+                owner: 100,
+                seed_hash: KittiesModule::dna_seed(0).unwrap()
+            })
         );
     });
 }
 
 #[test]
 fn gender() {
-    assert_eq!(Kitty { dna: [0; 16] }.gender(), Gender::Male);
+    assert_eq!(Kitty::from([0; 16]).gender(), Gender::Male);
     assert_eq!(
-        Kitty {
-            dna: [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
-        }
-        .gender(),
+        Kitty::from([1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]).gender(),
         Gender::Female
     );
 }
@@ -62,18 +118,21 @@ fn can_breed() {
 
         assert_ok!(KittiesModule::breed_kitty(Origin::signed(100), 0, 1));
 
-        let kitty = Kitty {
-            dna: [
-                145, 236, 235, 229, 18, 100, 83, 204, 176, 115, 244, 197, 48, 106, 46, 45,
-            ],
-        };
+        let kitty = Kitty::from([
+            224, 139, 45, 104, 154, 3, 222, 48, 67, 109, 79, 231, 117, 133, 76, 250,
+        ]);
 
-        assert_eq!(KittiesModule::kitties(100, 2), Some(kitty.clone()));
-        assert_eq!(KittiesModule::next_kitty_id(), 3);
+        assert_eq!(KittiesModule::kitties(&100, 2), Some(kitty.clone()));
+        assert_eq!(next_kitty_id(), 3);
 
         assert_eq!(
             last_event(),
-            Event::pallet_kitties(crate::Event::<Test>::KittyBred(kitty, 2, 100))
+            Event::pallet_kitties(crate::Event::<Test>::KittyBred {
+                dna: kitty,
+                kitty: 2,
+                owner: 100,
+                seed_hash: KittiesModule::dna_seed(2).unwrap()
+            })
         );
     });
 }
@@ -81,8 +140,9 @@ fn can_breed() {
 #[test]
 fn test_overflow() {
     new_test_ext().execute_with(|| {
-        crate::pallet::NextKittyId::<Test>::mutate(|x| *x = u32::MAX - 3);
+        orml_nft::NextTokenId::<Test>::insert(KittiesModule::class_id(), u32::MAX - 3);
         assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        System::set_extrinsic_index(3);
         assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
         assert_ok!(KittiesModule::breed_kitty(
             Origin::signed(100),
@@ -91,13 +151,13 @@ fn test_overflow() {
         ));
         assert_noop!(
             KittiesModule::create_kitty(Origin::signed(100)),
-            Error::<Test>::KittyIdOverflow
+            orml_nft::Error::<Test>::NoAvailableTokenId
         );
         assert_noop!(
             KittiesModule::create_kitty(Origin::signed(100)),
-            Error::<Test>::KittyIdOverflow
+            orml_nft::Error::<Test>::NoAvailableTokenId
         );
-        assert_eq!(KittiesModule::next_kitty_id(), u32::MAX);
+        assert_eq!(next_kitty_id(), u32::MAX);
     });
 }
 
@@ -112,19 +172,3835 @@ fn test_transfer() {
         });
         assert_noop!(
             KittiesModule::transfer_kitty(Origin::signed(100), 100, 5),
-            Error::<Test>::KittyNotFound
+            orml_nft::Error::<Test>::TokenNotFound
         );
         assert_noop!(
             KittiesModule::transfer_kitty(Origin::signed(100), 200, 5),
-            Error::<Test>::KittyNotFound
+            orml_nft::Error::<Test>::TokenNotFound
         );
         assert_ok!(KittiesModule::transfer_kitty(Origin::signed(100), 200, 1));
 
         assert_eq!(
             last_event(),
-            Event::pallet_kitties(crate::Event::<Test>::KittyTransfer(1, 100, 200))
+            Event::pallet_kitties(crate::Event::<Test>::KittyTransfer {
+                kitty: 1,
+                from: 100,
+                to: 200
+            })
+        );
+        assert_eq!(KittiesModule::kitties(&100, 1).is_some(), false);
+        assert_eq!(KittiesModule::kitties(&200, 1).is_some(), true);
+    });
+}
+
+#[test]
+fn can_simulate_breed() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        System::set_extrinsic_index(3);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+
+        assert_eq!(
+            KittiesModule::simulate_breed(0, 11, Default::default()),
+            Err(Error::<Test>::KittyNotFound.into())
+        );
+        assert_eq!(
+            KittiesModule::simulate_breed(0, 0, Default::default()),
+            Err(Error::<Test>::KittyPartnerMissing.into())
+        );
+
+        // Different seeds give different previews, and none of them are minted
+        assert_storage_noop!({
+            let first = KittiesModule::simulate_breed(0, 1, H256::repeat_byte(1)).unwrap();
+            let second = KittiesModule::simulate_breed(1, 0, H256::repeat_byte(2)).unwrap();
+            assert_ne!(first, second);
+        });
+        assert_eq!(next_kitty_id(), 2);
+    });
+}
+
+#[test]
+fn can_buy() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_err!(
+            KittiesModule::buy_kitty(Origin::signed(200), 0, None),
+            Error::<Test>::KittyNotForSale
+        );
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, Some(100)));
+        assert_noop!(
+            KittiesModule::buy_kitty(Origin::signed(100), 0, None),
+            Error::<Test>::CannotBuyOwnKitty
+        );
+
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(200), 0, None));
+
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::KittySold {
+                kitty: 0,
+                price: 100,
+                seller: 100,
+                buyer: 200
+            })
+        );
+        assert!(KittiesModule::kitties(&200, 0).is_some());
+        assert_eq!(KittiesModule::kitty_exchange(0), None);
+        assert_eq!(Balances::free_balance(100), 1_090);
+        assert_eq!(Balances::free_balance(200), 900);
+        assert_eq!(Balances::free_balance(KittiesModule::account_id()), 1 + 10);
+    });
+}
+
+#[test]
+fn can_buy_with_referrer() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, Some(100)));
+
+        assert_noop!(
+            KittiesModule::buy_kitty(Origin::signed(200), 0, Some(200)),
+            Error::<Test>::InvalidReferrer
+        );
+        assert_noop!(
+            KittiesModule::buy_kitty(Origin::signed(200), 0, Some(100)),
+            Error::<Test>::InvalidReferrer
+        );
+
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(200), 0, Some(300)));
+
+        assert!(System::events().iter().any(|record| record.event
+            == Event::pallet_kitties(crate::Event::<Test>::ReferralPaid {
+                kitty: 0,
+                amount: 5,
+                referrer: 300
+            })));
+        assert_eq!(Balances::free_balance(100), 1_090);
+        assert_eq!(Balances::free_balance(200), 900);
+        assert_eq!(Balances::free_balance(300), 1_005);
+        assert_eq!(Balances::free_balance(KittiesModule::account_id()), 1 + 5);
+        assert_eq!(KittiesModule::referral_earnings(300), 5);
+
+        // Earnings accumulate across purchases
+        System::set_block_number(1 + ResaleCooldown::get());
+        assert_ok!(KittiesModule::set_price(Origin::signed(200), 0, Some(200)));
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(100), 0, Some(300)));
+        assert_eq!(KittiesModule::referral_earnings(300), 15);
+    });
+}
+
+#[test]
+fn breeding_tracks_generation_and_fee() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        System::set_extrinsic_index(3);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_eq!(KittiesModule::kitty_generation(0), 0);
+
+        assert_ok!(KittiesModule::breed_kitty(Origin::signed(100), 0, 1));
+        assert_eq!(KittiesModule::kitty_generation(2), 1);
+        assert_eq!(Balances::free_balance(100), 990);
+        assert_eq!(Balances::free_balance(KittiesModule::account_id()), 1 + 10);
+
+        // The fee follows the older parent's generation
+        crate::pallet::KittyGeneration::<Test>::insert(0, 1);
+        assert_ok!(KittiesModule::breed_kitty(Origin::signed(100), 0, 1));
+        assert_eq!(KittiesModule::kitty_generation(3), 2);
+        assert_eq!(Balances::free_balance(100), 970);
+
+        // Offspring beyond `MaxGeneration` are refused
+        crate::pallet::KittyGeneration::<Test>::insert(1, 2);
+        assert_noop!(
+            KittiesModule::breed_kitty(Origin::signed(100), 0, 1),
+            Error::<Test>::MaxGenerationReached
+        );
+        assert_eq!(
+            KittiesModule::simulate_breed(0, 1, Default::default()),
+            Err(Error::<Test>::MaxGenerationReached.into())
+        );
+    });
+}
+
+#[test]
+fn breeding_requires_fee() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        System::set_extrinsic_index(3);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::transfer_kitty(Origin::signed(100), 400, 0));
+        assert_ok!(KittiesModule::transfer_kitty(Origin::signed(100), 400, 1));
+
+        assert_noop!(
+            KittiesModule::breed_kitty(Origin::signed(400), 0, 1),
+            pallet_balances::Error::<Test, _>::InsufficientBalance
+        );
+        assert_eq!(next_kitty_id(), 2);
+    });
+}
+
+#[test]
+fn can_create_and_list() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_and_list_kitty(
+            Origin::signed(100),
+            50
+        ));
+
+        assert!(KittiesModule::kitties(&100, 0).is_some());
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::KittyListed {
+                kitty: 0,
+                price: 50,
+                owner: 100
+            })
+        );
+
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(200), 0, None));
+        assert!(KittiesModule::kitties(&200, 0).is_some());
+    });
+}
+
+#[test]
+fn maintenance_window_pauses_trading() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, Some(100)));
+
+        assert_noop!(
+            KittiesModule::set_maintenance_window(Origin::signed(100), Some((5, 10))),
+            sp_runtime::DispatchError::BadOrigin
+        );
+        assert_noop!(
+            KittiesModule::set_maintenance_window(Origin::root(), Some((10, 5))),
+            Error::<Test>::InvalidMaintenanceWindow
+        );
+        assert_ok!(KittiesModule::set_maintenance_window(
+            Origin::root(),
+            Some((5, 10))
+        ));
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::MaintenanceWindowSet {
+                window: Some((5, 10))
+            })
+        );
+
+        System::set_block_number(5);
+        assert_noop!(
+            KittiesModule::buy_kitty(Origin::signed(200), 0, None),
+            Error::<Test>::TradingPaused
+        );
+        assert_noop!(
+            KittiesModule::set_price(Origin::signed(100), 0, Some(200)),
+            Error::<Test>::TradingPaused
+        );
+        assert_noop!(
+            KittiesModule::create_and_list_kitty(Origin::signed(100), 100),
+            Error::<Test>::TradingPaused
+        );
+        // Sellers can still leave the market
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, None));
+
+        System::set_block_number(10);
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, Some(100)));
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(200), 0, None));
+
+        assert_ok!(KittiesModule::set_maintenance_window(Origin::root(), None));
+        assert_eq!(KittiesModule::maintenance_window(), None);
+    });
+}
+
+#[test]
+fn can_look_up_kitty_by_id() {
+    new_test_ext().execute_with(|| {
+        assert_eq!(KittiesModule::kitty_of(0), None);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::transfer_kitty(Origin::signed(100), 200, 0));
+
+        let kitty = KittiesModule::kitties(&200, 0).unwrap();
+        assert_eq!(KittiesModule::kitty_of(0), Some((200, kitty)));
+    });
+}
+
+#[test]
+fn cannot_breed_with_relatives() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        System::set_extrinsic_index(3);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::breed_kitty(Origin::signed(100), 0, 1));
+        System::set_extrinsic_index(4);
+        assert_ok!(KittiesModule::breed_kitty(Origin::signed(100), 1, 0));
+        assert_eq!(KittiesModule::kitty_parents(2), Some((0, 1)));
+
+        // Parent and child
+        assert_noop!(
+            KittiesModule::breed_kitty(Origin::signed(100), 0, 2),
+            Error::<Test>::IncestuousBreeding
+        );
+        assert_noop!(
+            KittiesModule::breed_kitty(Origin::signed(100), 3, 1),
+            Error::<Test>::IncestuousBreeding
+        );
+        // Full siblings
+        assert_noop!(
+            KittiesModule::breed_kitty(Origin::signed(100), 2, 3),
+            Error::<Test>::IncestuousBreeding
+        );
+        assert_eq!(
+            KittiesModule::simulate_breed(2, 3, Default::default()),
+            Err(Error::<Test>::IncestuousBreeding.into())
+        );
+
+        ForbidIncest::set(false);
+        assert_ne!(
+            KittiesModule::simulate_breed(2, 3, Default::default()),
+            Err(Error::<Test>::IncestuousBreeding.into())
+        );
+    });
+}
+
+#[test]
+fn can_buy_reference_priced_listing() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::set_reference_price(
+            Origin::signed(100),
+            0,
+            20
+        ));
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::KittyReferencePriceUpdated {
+                kitty: 0,
+                price: 20,
+                owner: 100
+            })
+        );
+
+        // No rate at all
+        assert_noop!(
+            KittiesModule::buy_kitty(Origin::signed(200), 0, None),
+            Error::<Test>::StaleOraclePrice
+        );
+
+        // Rate is too old
+        OracleRate::set(Some((5, 1)));
+        System::set_block_number(12);
+        assert_noop!(
+            KittiesModule::buy_kitty(Origin::signed(200), 0, None),
+            Error::<Test>::StaleOraclePrice
+        );
+
+        System::set_block_number(11);
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(200), 0, None));
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::KittySold {
+                kitty: 0,
+                price: 100,
+                seller: 100,
+                buyer: 200
+            })
+        );
+        assert_eq!(Balances::free_balance(200), 900);
+    });
+}
+
+#[test]
+fn can_find_kitties_by_trait() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        System::set_extrinsic_index(3);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::breed_kitty(Origin::signed(100), 0, 1));
+
+        for kitty_id in 0..3 {
+            let kitty = KittiesModule::kitties(&100, kitty_id).unwrap();
+            for (trait_id, value) in kitty.traits() {
+                assert!(KittiesModule::kitties_with_trait(trait_id, value, 3).contains(&kitty_id));
+            }
+        }
+
+        let kitty = KittiesModule::kitties(&100, 0).unwrap();
+        let value = kitty.trait_value(0).unwrap();
+        assert_eq!(
+            KittiesModule::kitties_with_trait(0, value, 0),
+            Vec::<u32>::new()
+        );
+        assert_eq!(kitty.trait_value(DnaLength::get() as u8), None);
+    });
+}
+
+#[test]
+fn offchain_worker_posts_sales_to_webhook() {
+    let (offchain, state) = TestOffchainExt::new();
+    let mut t = new_test_ext();
+    t.register_extension(OffchainExt::new(offchain));
+
+    t.execute_with(|| {
+        // Not opted in: nothing is sent
+        assert_ok!(KittiesModule::create_and_list_kitty(
+            Origin::signed(100),
+            50
+        ));
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(200), 0, None));
+        KittiesModule::offchain_worker(1);
+
+        StorageValueRef::persistent(crate::WEBHOOK_URL_KEY)
+            .set(&b"http://localhost/sales".to_vec());
+        let sale = crate::Event::<Test>::KittySold {
+            kitty: 0,
+            price: 50,
+            seller: 100,
+            buyer: 200,
+        };
+        state.write().expect_request(PendingRequest {
+            method: "POST".into(),
+            uri: "http://localhost/sales".into(),
+            headers: vec![("Content-Type".into(), "application/octet-stream".into())],
+            body: (1u64, vec![sale]).encode(),
+            response: Some(Vec::new()),
+            sent: true,
+            ..Default::default()
+        });
+        KittiesModule::offchain_worker(1);
+    });
+}
+
+#[test]
+fn bought_kitty_cannot_be_relisted_during_cooldown() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_and_list_kitty(
+            Origin::signed(100),
+            50
+        ));
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(200), 0, None));
+        assert_eq!(KittiesModule::resale_locked_until(0), Some(6));
+
+        System::set_block_number(5);
+        assert_noop!(
+            KittiesModule::set_price(Origin::signed(200), 0, Some(60)),
+            Error::<Test>::KittyInResaleCooldown
+        );
+        assert_noop!(
+            KittiesModule::set_reference_price(Origin::signed(200), 0, 60),
+            Error::<Test>::KittyInResaleCooldown
+        );
+        // Transfers are not affected
+        assert_ok!(KittiesModule::transfer_kitty(Origin::signed(200), 300, 0));
+        assert_noop!(
+            KittiesModule::set_price(Origin::signed(300), 0, Some(60)),
+            Error::<Test>::KittyInResaleCooldown
+        );
+
+        System::set_block_number(6);
+        assert_ok!(KittiesModule::set_price(Origin::signed(300), 0, Some(60)));
+        assert_eq!(KittiesModule::resale_locked_until(0), None);
+    });
+}
+
+#[test]
+fn force_origin_can_clear_resale_cooldown() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_and_list_kitty(
+            Origin::signed(100),
+            50
+        ));
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(200), 0, None));
+
+        assert_noop!(
+            KittiesModule::clear_resale_cooldown(Origin::signed(200), 0),
+            sp_runtime::DispatchError::BadOrigin
+        );
+        assert_ok!(KittiesModule::clear_resale_cooldown(Origin::root(), 0));
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::ResaleCooldownCleared { kitty: 0 })
+        );
+        assert_ok!(KittiesModule::set_price(Origin::signed(200), 0, Some(60)));
+    });
+}
+
+#[test]
+fn reversed_trades_are_flagged_as_wash_trading() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::set_wash_trade_threshold(
+            Origin::root(),
+            Some(1)
+        ));
+        assert_ok!(KittiesModule::create_and_list_kitty(
+            Origin::signed(100),
+            50
+        ));
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(200), 0, None));
+        assert_eq!(KittiesModule::pair_trades(200, 100), Some((1, 1)));
+        assert_eq!(KittiesModule::suspicious_trades(100), 0);
+
+        System::set_block_number(6);
+        assert_ok!(KittiesModule::set_price(Origin::signed(200), 0, Some(60)));
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(100), 0, None));
+        assert_eq!(KittiesModule::suspicious_trades(100), 1);
+        assert_eq!(KittiesModule::suspicious_trades(200), 1);
+        assert!(System::events().iter().any(|record| record.event
+            == Event::pallet_kitties(crate::Event::<Test>::SuspiciousTrade {
+                kitty: 0,
+                seller: 200,
+                buyer: 100
+            })));
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::TradingRestricted { account: 100 })
+        );
+
+        assert_noop!(
+            KittiesModule::create_and_list_kitty(Origin::signed(200), 50),
+            Error::<Test>::AccountRestricted
         );
-        assert_eq!(KittiesModule::kitties(100, 1).is_some(), false);
-        assert_eq!(KittiesModule::kitties(200, 1).is_some(), true);
+
+        assert_noop!(
+            KittiesModule::lift_trading_restriction(Origin::signed(200), 200),
+            sp_runtime::DispatchError::BadOrigin
+        );
+        assert_ok!(KittiesModule::lift_trading_restriction(Origin::root(), 200));
+        assert_eq!(KittiesModule::suspicious_trades(200), 0);
+        assert_ok!(KittiesModule::create_and_list_kitty(
+            Origin::signed(200),
+            50
+        ));
+    });
+}
+
+#[test]
+fn can_export_and_import_kitty() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_and_list_kitty(
+            Origin::signed(100),
+            50
+        ));
+        let kitty = KittiesModule::kitties(&100, 0).unwrap();
+
+        assert_noop!(
+            KittiesModule::export_kitty(Origin::signed(200), 0),
+            Error::<Test>::KittyNotFound
+        );
+        assert_ok!(KittiesModule::export_kitty(Origin::signed(100), 0));
+        let export = KittyExport {
+            owner: 100,
+            dna: kitty.clone(),
+            parents: None,
+            generation: 0,
+            nonce: 0,
+        };
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::KittyExported {
+                kitty: 0,
+                export: export.clone()
+            })
+        );
+        assert_eq!(KittiesModule::kitty_of(0), None);
+        assert_eq!(KittiesModule::kitty_exchange(0), None);
+        assert!(KittiesModule::kitties_with_trait(0, kitty.dna()[0] / 16, 10).is_empty());
+        assert_eq!(KittiesModule::export_nonce(), 1);
+
+        let signature = TestSignature(7, export.encode());
+        assert_noop!(
+            KittiesModule::import_kitty(Origin::signed(200), export.clone(), signature.clone()),
+            Error::<Test>::ImportsDisabled
+        );
+        assert_ok!(KittiesModule::set_import_authority(Origin::root(), Some(7)));
+        assert_noop!(
+            KittiesModule::import_kitty(
+                Origin::signed(200),
+                export.clone(),
+                TestSignature(8, export.encode())
+            ),
+            Error::<Test>::InvalidExportSignature
+        );
+        assert_noop!(
+            KittiesModule::import_kitty(
+                Origin::signed(200),
+                KittyExport {
+                    owner: 200,
+                    ..export.clone()
+                },
+                signature.clone()
+            ),
+            Error::<Test>::InvalidExportSignature
+        );
+
+        assert_ok!(KittiesModule::import_kitty(
+            Origin::signed(200),
+            export.clone(),
+            signature.clone()
+        ));
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::KittyImported {
+                dna: kitty.clone(),
+                kitty: 1,
+                owner: 100
+            })
+        );
+        assert_eq!(KittiesModule::kitty_of(1), Some((100, kitty)));
+        assert_noop!(
+            KittiesModule::import_kitty(Origin::signed(200), export, signature),
+            Error::<Test>::ExportAlreadyImported
+        );
+    });
+}
+
+#[test]
+fn tracks_last_minted_kitty_per_account() {
+    new_test_ext().execute_with(|| {
+        assert_eq!(KittiesModule::last_minted_by(100), None);
+
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_eq!(KittiesModule::last_minted_by(100), Some(0));
+
+        System::set_extrinsic_index(3);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::create_and_list_kitty(
+            Origin::signed(200),
+            50
+        ));
+        assert_eq!(KittiesModule::last_minted_by(100), Some(1));
+        assert_eq!(KittiesModule::last_minted_by(200), Some(2));
+
+        assert_ok!(KittiesModule::breed_kitty(Origin::signed(100), 0, 1));
+        assert_eq!(KittiesModule::last_minted_by(100), Some(3));
+    });
+}
+
+#[test]
+fn dna_length_follows_config() {
+    new_test_ext().execute_with(|| {
+        DnaLength::set(40);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        let kitty = KittiesModule::kitties(&100, 0).unwrap();
+        assert_eq!(kitty.0.version, crate::GENOME_VERSION);
+        assert_eq!(kitty.dna().len(), 40);
+        assert_eq!(
+            KittiesModule::kitties_with_trait(39, kitty.dna()[39] / 16, 10),
+            vec![0]
+        );
+
+        // Longer genomes than the runtime generates are not imported
+        assert_ok!(KittiesModule::export_kitty(Origin::signed(100), 0));
+        let export = KittyExport {
+            owner: 100,
+            dna: kitty,
+            parents: None,
+            generation: 0,
+            nonce: 0,
+        };
+        assert_ok!(KittiesModule::set_import_authority(Origin::root(), Some(7)));
+        DnaLength::set(16);
+        assert_noop!(
+            KittiesModule::import_kitty(
+                Origin::signed(100),
+                export.clone(),
+                TestSignature(7, export.encode())
+            ),
+            Error::<Test>::UnsupportedGenome
+        );
+    });
+}
+
+#[test]
+fn migrates_raw_dna_to_genomes() {
+    use frame_support::{storage::unhashed, traits::OnRuntimeUpgrade};
+
+    new_test_ext().execute_with(|| {
+        let dna = [3u8; 16];
+        let key = orml_nft::Tokens::<Test>::hashed_key_for(KittiesModule::class_id(), 0);
+        unhashed::put(&key, &(Vec::<u8>::new(), 100u64, dna));
+        crate::StorageVersion::<Test>::kill();
+
+        AllModules::on_runtime_upgrade();
+
+        assert_eq!(KittiesModule::kitty_of(0), Some((100, Kitty::from(dna))));
+        assert_eq!(
+            crate::DnaIndex::<Test>::get(sp_io::hashing::blake2_256(&dna)),
+            Some(0)
+        );
+        assert_eq!(crate::StorageVersion::<Test>::get(), crate::Releases::V9);
+    });
+}
+
+#[test]
+fn breeding_can_produce_twins() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        System::set_extrinsic_index(3);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+
+        TwinProbability::set(Permill::one());
+        assert_ok!(KittiesModule::breed_kitty(Origin::signed(100), 0, 1));
+        assert_eq!(next_kitty_id(), 4);
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::KittyTwinsBorn {
+                kitty: 2,
+                twin: 3,
+                owner: 100
+            })
+        );
+        let (_, kitty) = KittiesModule::kitty_of(2).unwrap();
+        let (_, twin) = KittiesModule::kitty_of(3).unwrap();
+        assert_ne!(kitty, twin);
+        assert_eq!(KittiesModule::kitty_parents(3), Some((0, 1)));
+        assert_eq!(KittiesModule::kitty_generation(3), 1);
+        // Twins are charged a single fee
+        assert_eq!(Balances::free_balance(KittiesModule::account_id()), 1 + 10);
+        // and are siblings
+        assert_noop!(
+            KittiesModule::breed_kitty(Origin::signed(100), 2, 3),
+            Error::<Test>::IncestuousBreeding
+        );
+
+        TwinProbability::set(Permill::zero());
+        assert_ok!(KittiesModule::breed_kitty(Origin::signed(100), 0, 1));
+        assert_eq!(next_kitty_id(), 5);
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::KittyBred {
+                dna: KittiesModule::kitty_of(4).unwrap().1,
+                kitty: 4,
+                owner: 100,
+                seed_hash: KittiesModule::dna_seed(4).unwrap()
+            })
+        );
+    });
+}
+
+#[test]
+fn milestone_kitties_reward_their_minter() {
+    new_test_ext().execute_with(|| {
+        MilestoneInterval::set(Some(2));
+        let pot = KittiesModule::account_id();
+        assert_ok!(Balances::transfer(Origin::signed(300), pot, 59));
+
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_eq!(KittiesModule::total_minted(), 1);
+        assert_eq!(KittiesModule::milestone_kitty(0), None);
+
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(200)));
+        assert_eq!(KittiesModule::milestone_kitty(1), Some(2));
+        assert!(System::events().iter().any(|record| record.event
+            == Event::pallet_kitties(crate::Event::<Test>::MilestoneKitty {
+                kitty: 1,
+                milestone: 2,
+                minter: 200,
+                reward: 50
+            })));
+        assert_eq!(Balances::free_balance(200), 1_050);
+        assert_eq!(Balances::free_balance(pot), 10);
+
+        // An empty pot still badges the kitty
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_eq!(KittiesModule::milestone_kitty(3), Some(4));
+        assert!(System::events().iter().any(|record| record.event
+            == Event::pallet_kitties(crate::Event::<Test>::MilestoneKitty {
+                kitty: 3,
+                milestone: 4,
+                minter: 100,
+                reward: 0
+            })));
+        assert_eq!(Balances::free_balance(100), 1_000);
+    });
+}
+
+#[test]
+fn can_describe_errors_by_code() {
+    new_test_ext().execute_with(|| {
+        let code = match sp_runtime::DispatchError::from(Error::<Test>::KittyNotFound) {
+            sp_runtime::DispatchError::Module { error, .. } => error,
+            _ => unreachable!(),
+        };
+        assert_eq!(code, 3);
+        assert_eq!(
+            KittiesModule::describe_error(code),
+            b"KittyNotFound: Kitty not found".to_vec()
+        );
+        assert_eq!(
+            KittiesModule::describe_error(12),
+            b"TradingPaused: The marketplace is closed for maintenance".to_vec()
+        );
+        assert!(KittiesModule::describe_error(u8::MAX).is_empty());
+    });
+}
+
+#[test]
+fn finalize_summarizes_block_activity() {
+    use frame_support::traits::OnFinalize;
+
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::create_and_list_kitty(
+            Origin::signed(100),
+            50
+        ));
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(200), 1, None));
+
+        KittiesModule::on_finalize(1);
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::BlockMarketDigest {
+                mints: 2,
+                sales: 1,
+                volume: 50
+            })
+        );
+
+        // Quiet blocks deposit nothing
+        System::reset_events();
+        System::set_block_number(2);
+        KittiesModule::on_finalize(2);
+        assert!(System::events().is_empty());
+    });
+}
+
+#[test]
+fn can_complete_and_claim_quests() {
+    new_test_ext().execute_with(|| {
+        assert_noop!(
+            KittiesModule::create_quest(Origin::signed(100), QuestGoal::Breed, 1, 30, 10),
+            sp_runtime::DispatchError::BadOrigin
+        );
+        assert_noop!(
+            KittiesModule::create_quest(Origin::root(), QuestGoal::Breed, 0, 30, 10),
+            Error::<Test>::InvalidQuest
+        );
+        assert_ok!(KittiesModule::create_quest(
+            Origin::root(),
+            QuestGoal::Breed,
+            1,
+            30,
+            10
+        ));
+        assert_ok!(KittiesModule::create_quest(
+            Origin::root(),
+            QuestGoal::Create,
+            3,
+            0,
+            2
+        ));
+        assert_noop!(
+            KittiesModule::create_quest(Origin::root(), QuestGoal::Buy, 1, 0, 10),
+            Error::<Test>::TooManyQuests
+        );
+        assert_ok!(Balances::transfer(
+            Origin::signed(300),
+            KittiesModule::account_id(),
+            100
+        ));
+
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        System::set_extrinsic_index(3);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_eq!(KittiesModule::quest_progress(1, 100), (2, false));
+        System::set_extrinsic_index(4);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(200)));
+        assert_noop!(
+            KittiesModule::claim_quest_reward(Origin::signed(100), 0),
+            Error::<Test>::QuestNotCompleted
+        );
+
+        assert_ok!(KittiesModule::breed_kitty(Origin::signed(100), 0, 1));
+        assert_ok!(KittiesModule::claim_quest_reward(Origin::signed(100), 0));
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::QuestRewardClaimed {
+                quest: 0,
+                account: 100,
+                reward: 30
+            })
+        );
+        // Paid the breeding fee of 10, got the reward of 30
+        assert_eq!(Balances::free_balance(100), 1_020);
+        assert_eq!(KittiesModule::quest_badge(100, 0), Some(()));
+        assert_noop!(
+            KittiesModule::claim_quest_reward(Origin::signed(100), 0),
+            Error::<Test>::QuestAlreadyClaimed
+        );
+
+        // Actions after a quest has ended do not count
+        System::set_block_number(2);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_eq!(KittiesModule::quest_progress(1, 100), (2, false));
+
+        // Progress is removed one account per call here
+        assert_ok!(KittiesModule::remove_quest(Origin::root(), 1));
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::QuestRemoved {
+                quest: 1,
+                cleared: false
+            })
+        );
+        assert_noop!(
+            KittiesModule::claim_quest_reward(Origin::signed(100), 1),
+            Error::<Test>::QuestNotFound
+        );
+        assert_ok!(KittiesModule::remove_quest(Origin::root(), 1));
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::QuestRemoved {
+                quest: 1,
+                cleared: true
+            })
+        );
+        assert_eq!(KittiesModule::quest_progress(1, 100), (0, false));
+        assert_eq!(KittiesModule::quest_progress(1, 200), (0, false));
+        assert_noop!(
+            KittiesModule::remove_quest(Origin::root(), 1),
+            Error::<Test>::QuestNotFound
+        );
+    });
+}
+
+#[test]
+fn purchases_respect_spending_limit() {
+    new_test_ext().execute_with(|| {
+        for _ in 0..3 {
+            assert_ok!(KittiesModule::create_and_list_kitty(
+                Origin::signed(100),
+                40
+            ));
+        }
+
+        assert_ok!(KittiesModule::set_spending_limit(
+            Origin::signed(200),
+            Some(100)
+        ));
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::SpendingLimitSet {
+                account: 200,
+                limit: Some(100)
+            })
+        );
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(200), 0, None));
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(200), 1, None));
+        assert_noop!(
+            KittiesModule::buy_kitty(Origin::signed(200), 2, None),
+            Error::<Test>::SpendingLimitExceeded
+        );
+        assert_eq!(KittiesModule::spent(200), (0, 80));
+
+        // The allowance resets with the next period
+        System::set_block_number(10);
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(200), 2, None));
+        assert_eq!(KittiesModule::spent(200), (1, 40));
+
+        assert_ok!(KittiesModule::set_spending_limit(Origin::signed(200), None));
+        assert_eq!(KittiesModule::spending_limit(200), None);
+    });
+}
+
+#[cfg(feature = "render")]
+#[test]
+fn can_render_kitty() {
+    new_test_ext().execute_with(|| {
+        assert!(KittiesModule::render_kitty(0).is_empty());
+
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        let svg = KittiesModule::render_kitty(0);
+        assert!(svg.starts_with(b"<svg "));
+        assert!(svg.ends_with(b"</svg>"));
+        assert_eq!(svg, KittiesModule::render_kitty(0));
+
+        let kitty = Kitty::from([
+            0, 0, 255, 16, 32, 48, 255, 255, 255, 4, 2, 9, 0, 0, 0, 0,
+        ]);
+        assert_eq!(
+            String::from_utf8(crate::render::render_svg(&kitty)).unwrap(),
+            concat!(
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 100 100\">",
+                "<rect width=\"100\" height=\"100\" fill=\"#8080ff\"/>",
+                "<polygon points=\"22,40 30,9 45,28 55,28 70,9 78,40\" fill=\"#102030\"/>",
+                "<circle cx=\"50\" cy=\"55\" r=\"30\" fill=\"#102030\"/>",
+                "<circle cx=\"38\" cy=\"50\" r=\"5\" fill=\"#ffffff\"/>",
+                "<circle cx=\"62\" cy=\"50\" r=\"5\" fill=\"#ffffff\"/>",
+                "<path d=\"M42 66 Q50 75 58 66\" stroke=\"#000\" stroke-width=\"2\" fill=\"none\"/>",
+                "</svg>"
+            )
+        );
+    });
+}
+
+#[cfg(feature = "render")]
+#[test]
+fn can_export_token_metadata() {
+    new_test_ext().execute_with(|| {
+        assert!(KittiesModule::token_metadata_json(0).is_empty());
+
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        let json = KittiesModule::token_metadata_json(0);
+        assert!(json.starts_with(b"{\"name\":\"Kitty #0\",\"description\":\"Generation 0 kitty"));
+        assert!(json.ends_with(b"}]}"));
+
+        let kitty = Kitty::from([
+            0, 0, 255, 16, 32, 48, 255, 255, 255, 4, 2, 9, 0, 0, 0, 0,
+        ]);
+        assert_eq!(
+            String::from_utf8(crate::render::metadata_json(17, &kitty, 2)).unwrap(),
+            concat!(
+                "{\"name\":\"Kitty #17\",",
+                "\"description\":\"Generation 2 kitty, minted on-chain with its traits read from its dna.\",",
+                "\"image\":\"data:image/svg+xml;base64,",
+                "PHN2ZyB4bWxucz0iaHR0cDovL3d3dy53My5vcmcvMjAwMC9zdmciIHZpZXdCb3g9IjAgMCAxMDAgMTAwIj48cmVjdCB3aWR0aD0i",
+                "MTAwIiBoZWlnaHQ9IjEwMCIgZmlsbD0iIzgwODBmZiIvPjxwb2x5Z29uIHBvaW50cz0iMjIsNDAgMzAsOSA0NSwyOCA1NSwyOCA3",
+                "MCw5IDc4LDQwIiBmaWxsPSIjMTAyMDMwIi8+PGNpcmNsZSBjeD0iNTAiIGN5PSI1NSIgcj0iMzAiIGZpbGw9IiMxMDIwMzAiLz48",
+                "Y2lyY2xlIGN4PSIzOCIgY3k9IjUwIiByPSI1IiBmaWxsPSIjZmZmZmZmIi8+PGNpcmNsZSBjeD0iNjIiIGN5PSI1MCIgcj0iNSIg",
+                "ZmlsbD0iI2ZmZmZmZiIvPjxwYXRoIGQ9Ik00MiA2NiBRNTAgNzUgNTggNjYiIHN0cm9rZT0iIzAwMCIgc3Ryb2tlLXdpZHRoPSIy",
+                "IiBmaWxsPSJub25lIi8+PC9zdmc+\",",
+                "\"attributes\":[{\"trait_type\":\"Gender\",\"value\":\"Female\"},",
+                "{\"trait_type\":\"Generation\",\"display_type\":\"number\",\"value\":2},",
+                "{\"trait_type\":\"Speed\",\"display_type\":\"number\",\"value\":16},",
+                "{\"trait_type\":\"Trait 0\",\"value\":0},{\"trait_type\":\"Trait 1\",\"value\":0},",
+                "{\"trait_type\":\"Trait 2\",\"value\":15},{\"trait_type\":\"Trait 3\",\"value\":1},",
+                "{\"trait_type\":\"Trait 4\",\"value\":2},{\"trait_type\":\"Trait 5\",\"value\":3},",
+                "{\"trait_type\":\"Trait 6\",\"value\":15},{\"trait_type\":\"Trait 7\",\"value\":15},",
+                "{\"trait_type\":\"Trait 8\",\"value\":15},{\"trait_type\":\"Trait 9\",\"value\":0},",
+                "{\"trait_type\":\"Trait 10\",\"value\":0},{\"trait_type\":\"Trait 11\",\"value\":0},",
+                "{\"trait_type\":\"Trait 12\",\"value\":0},{\"trait_type\":\"Trait 13\",\"value\":0},",
+                "{\"trait_type\":\"Trait 14\",\"value\":0},{\"trait_type\":\"Trait 15\",\"value\":0}]}",
+            )
+        );
+    });
+}
+
+#[test]
+fn sales_are_aggregated_into_price_candles() {
+    new_test_ext().execute_with(|| {
+        let sell = |price| {
+            let kitty_id = next_kitty_id();
+            assert_ok!(KittiesModule::create_and_list_kitty(
+                Origin::signed(100),
+                price
+            ));
+            assert_ok!(KittiesModule::buy_kitty(
+                Origin::signed(200),
+                kitty_id,
+                None
+            ));
+        };
+
+        sell(30);
+        sell(50);
+        sell(20);
+        sell(40);
+        assert_eq!(
+            KittiesModule::price_stats(0),
+            Some(crate::PriceCandle {
+                open: 30,
+                high: 50,
+                low: 20,
+                close: 40,
+                volume: 140,
+                sales: 4,
+            })
+        );
+
+        System::set_block_number(10);
+        sell(60);
+        System::set_block_number(35);
+        sell(70);
+        // Only the last two periods with sales are kept
+        assert_eq!(KittiesModule::stats_periods(), vec![1, 3]);
+        assert_eq!(KittiesModule::price_stats(0), None);
+        assert_eq!(KittiesModule::price_stats(3).unwrap().open, 70);
+    });
+}
+
+#[test]
+fn locked_transfers_hold_kitty_until_unlock() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_and_list_kitty(
+            Origin::signed(100),
+            50
+        ));
+        assert_noop!(
+            KittiesModule::transfer_with_lock(Origin::signed(100), 200, 0, 1),
+            Error::<Test>::InvalidUnlockBlock
+        );
+        assert_ok!(KittiesModule::transfer_with_lock(
+            Origin::signed(100),
+            200,
+            0,
+            5
+        ));
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::KittyTransferLocked {
+                kitty: 0,
+                from: 100,
+                to: 200,
+                unlock_at: 5
+            })
+        );
+        assert!(KittiesModule::kitties(&200, 0).is_some());
+        assert_eq!(KittiesModule::kitty_exchange(0), None);
+
+        assert_noop!(
+            KittiesModule::transfer_kitty(Origin::signed(200), 300, 0),
+            Error::<Test>::KittyTransferLocked
+        );
+        assert_noop!(
+            KittiesModule::set_price(Origin::signed(200), 0, Some(60)),
+            Error::<Test>::KittyTransferLocked
+        );
+        assert_noop!(
+            KittiesModule::export_kitty(Origin::signed(200), 0),
+            Error::<Test>::KittyTransferLocked
+        );
+
+        System::set_block_number(5);
+        assert_ok!(KittiesModule::set_price(Origin::signed(200), 0, Some(60)));
+        assert_eq!(KittiesModule::transfer_lock(0), None);
+        assert_ok!(KittiesModule::transfer_kitty(Origin::signed(200), 300, 0));
+    });
+}
+
+#[test]
+fn can_delist_all_listings_at_once() {
+    new_test_ext().execute_with(|| {
+        for _ in 0..3 {
+            assert_ok!(KittiesModule::create_and_list_kitty(
+                Origin::signed(100),
+                40
+            ));
+        }
+        assert_ok!(KittiesModule::create_and_list_kitty(
+            Origin::signed(200),
+            40
+        ));
+
+        // At most `MaxDelistAll` listings go per call
+        assert_ok!(KittiesModule::delist_all(Origin::signed(100)));
+        let listed = (0..3)
+            .filter(|kitty_id| KittiesModule::kitty_exchange(*kitty_id).is_some())
+            .count();
+        assert_eq!(listed, 1);
+        assert!(matches!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::KittyDelisted { owner: 100, .. })
+        ));
+
+        assert_ok!(KittiesModule::delist_all(Origin::signed(100)));
+        assert!((0..3).all(|kitty_id| KittiesModule::kitty_exchange(kitty_id).is_none()));
+        assert!(KittiesModule::kitty_exchange(3).is_some());
+    });
+}
+
+#[test]
+fn migration_indexes_existing_listings() {
+    use frame_support::{
+        storage::migration::put_storage_value, traits::OnRuntimeUpgrade, Blake2_128Concat,
+        StorageHasher,
+    };
+
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_and_list_kitty(
+            Origin::signed(100),
+            40
+        ));
+        // Listings were keyed by kitty alone before V6
+        let listing = crate::KittyExchange::<Test>::take(KittiesModule::class_id(), 0);
+        put_storage_value(
+            b"KittiesModule",
+            b"KittyExchange",
+            &Blake2_128Concat::hash(&0u32.encode()),
+            listing,
+        );
+        crate::OwnerListings::<Test>::remove(100, 0);
+        crate::PriceIndex::<Test>::remove_all();
+        crate::StorageVersion::<Test>::put(crate::Releases::V2);
+
+        AllModules::on_runtime_upgrade();
+
+        assert_eq!(crate::OwnerListings::<Test>::get(100, 0), Some(()));
+        assert_ok!(KittiesModule::buy_floor(Origin::signed(200), 1, 40));
+        assert_eq!(NFT::tokens(0, 0).unwrap().owner, 200);
+    });
+}
+
+#[test]
+fn can_buy_floor_listings() {
+    new_test_ext().execute_with(|| {
+        for price in [40, 30, 50].iter() {
+            assert_ok!(KittiesModule::create_and_list_kitty(
+                Origin::signed(100),
+                *price
+            ));
+        }
+        assert_ok!(KittiesModule::create_and_list_kitty(
+            Origin::signed(200),
+            10
+        ));
+
+        assert_noop!(
+            KittiesModule::buy_floor(Origin::signed(200), 4, 1_000),
+            Error::<Test>::InvalidFloorCount
+        );
+        assert_noop!(
+            KittiesModule::buy_floor(Origin::signed(100), 2, 1_000),
+            Error::<Test>::NotEnoughListings
+        );
+        assert_noop!(
+            KittiesModule::buy_floor(Origin::signed(200), 2, 69),
+            Error::<Test>::FloorTotalExceeded
+        );
+
+        // The caller's own listing is skipped
+        assert_ok!(KittiesModule::buy_floor(Origin::signed(200), 2, 70));
+        assert_eq!(NFT::tokens(0, 0).unwrap().owner, 200);
+        assert_eq!(NFT::tokens(0, 1).unwrap().owner, 200);
+        assert_eq!(NFT::tokens(0, 2).unwrap().owner, 100);
+        assert_eq!(KittiesModule::kitty_exchange(3).map(|l| l.1), Some(10));
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::FloorSwept {
+                buyer: 200,
+                count: 2,
+                total: 70
+            })
+        );
+
+        // Repricing moves a listing within the index
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 2, Some(5)));
+        assert_ok!(KittiesModule::buy_floor(Origin::signed(200), 1, 5));
+        assert_eq!(NFT::tokens(0, 2).unwrap().owner, 200);
+    });
+}
+
+#[test]
+fn listing_changes_emit_distinct_events() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, Some(50)));
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::KittyListed {
+                kitty: 0,
+                price: 50,
+                owner: 100
+            })
+        );
+
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, Some(60)));
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::KittyRepriced {
+                kitty: 0,
+                old_price: 50,
+                new_price: 60,
+                owner: 100
+            })
+        );
+
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, None));
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::KittyDelisted {
+                kitty: 0,
+                owner: 100
+            })
+        );
+
+        // Delisting a kitty that is not for sale changes nothing
+        let events = System::events().len();
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, None));
+        assert_eq!(System::events().len(), events);
+    });
+}
+
+#[test]
+fn migrates_legacy_template_kitties() {
+    use frame_support::{
+        storage::migration::{get_storage_value, put_storage_value},
+        traits::OnRuntimeUpgrade,
+        Blake2_128Concat, StorageHasher,
+    };
+
+    new_test_ext().execute_with(|| {
+        MilestoneInterval::set(Some(1));
+        let pot = KittiesModule::account_id();
+        assert_ok!(Balances::transfer(Origin::signed(300), pot, 59));
+        let pot_balance = Balances::free_balance(pot);
+        let legacy = [
+            (100u64, 0u32, [1u8; 16]),
+            (200, 1, [2; 16]),
+            (300, 2, [2; 16]),
+        ];
+        for (owner, legacy_id, dna) in legacy.iter() {
+            let key = [
+                Blake2_128Concat::hash(&owner.encode()),
+                Blake2_128Concat::hash(&legacy_id.encode()),
+            ]
+            .concat();
+            put_storage_value(b"KittiesModule", b"Kitties", &key, *dna);
+        }
+        put_storage_value(b"KittiesModule", b"NextKittyId", &[], 3u32);
+        crate::StorageVersion::<Test>::put(crate::Releases::V3);
+
+        AllModules::on_runtime_upgrade();
+
+        // The duplicate dna is remixed rather than dropped, and re-mints earn no rewards
+        assert_eq!(next_kitty_id(), 3);
+        let mut migrated = (0..3)
+            .map(|kitty_id| KittiesModule::kitty_of(kitty_id).unwrap())
+            .collect::<Vec<_>>();
+        migrated.sort_by_key(|(owner, _)| *owner);
+        assert_eq!(migrated[0], (100, Kitty::from([1; 16])));
+        let twins = [&migrated[1], &migrated[2]];
+        assert!(twins
+            .iter()
+            .any(|(_, kitty)| *kitty == Kitty::from([2; 16])));
+        assert_ne!(twins[0].1, twins[1].1);
+        assert_eq!((twins[0].0, twins[1].0), (200, 300));
+        assert!((0..3).all(|kitty_id| KittiesModule::milestone_kitty(kitty_id).is_none()));
+        assert_eq!(Balances::free_balance(pot), pot_balance);
+        assert_eq!(
+            get_storage_value::<u32>(b"KittiesModule", b"NextKittyId", &[]),
+            None
+        );
+        assert_eq!(crate::StorageVersion::<Test>::get(), crate::Releases::V9);
+    });
+}
+
+#[test]
+fn can_sell_into_collection_bids() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        let (_, kitty) = KittiesModule::kitty_of(0).unwrap();
+        let (trait_id, value) = kitty.traits().next().unwrap();
+        let other_value = (0..=u8::MAX)
+            .find(|other| {
+                !crate::TraitIndex::<Test>::contains_key((trait_id, *other), 0)
+                    && !crate::TraitIndex::<Test>::contains_key((trait_id, *other), 1)
+            })
+            .unwrap();
+
+        assert_noop!(
+            KittiesModule::place_collection_bid(Origin::signed(200), 40, None, 0),
+            Error::<Test>::InvalidCollectionBid
+        );
+        assert_ok!(KittiesModule::place_collection_bid(
+            Origin::signed(200),
+            40,
+            Some((trait_id, value)),
+            2
+        ));
+        assert_ok!(KittiesModule::place_collection_bid(
+            Origin::signed(200),
+            40,
+            Some((trait_id, other_value)),
+            1
+        ));
+        assert_eq!(KittiesModule::market_hold(200), 120);
+        assert_eq!(Balances::reserved_balance(200), 120);
+
+        assert_noop!(
+            KittiesModule::fill_collection_bid(Origin::signed(100), 1, 0),
+            Error::<Test>::KittyDoesNotMatchBid
+        );
+        assert_noop!(
+            KittiesModule::fill_collection_bid(Origin::signed(300), 0, 0),
+            Error::<Test>::KittyNotFound
+        );
+
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, Some(10)));
+        assert_ok!(KittiesModule::fill_collection_bid(
+            Origin::signed(100),
+            0,
+            0
+        ));
+        assert_eq!(NFT::tokens(0, 0).unwrap().owner, 200);
+        assert_eq!(KittiesModule::kitty_exchange(0), None);
+        assert_eq!(KittiesModule::market_hold(200), 80);
+        assert_eq!(KittiesModule::collection_bids(0).unwrap().quantity, 1);
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::CollectionBidFilled {
+                bid: 0,
+                kitty: 0,
+                seller: 100
+            })
+        );
+
+        assert_noop!(
+            KittiesModule::cancel_collection_bid(Origin::signed(100), 0),
+            Error::<Test>::NotCollectionBidder
+        );
+        assert_ok!(KittiesModule::cancel_collection_bid(Origin::signed(200), 0));
+        assert_ok!(KittiesModule::cancel_collection_bid(Origin::signed(200), 1));
+        assert_eq!(KittiesModule::market_hold(200), 0);
+        assert!(Balances::locks(200).is_empty());
+        assert_noop!(
+            KittiesModule::fill_collection_bid(Origin::signed(100), 0, 1),
+            Error::<Test>::CollectionBidNotFound
+        );
+    });
+}
+
+#[test]
+fn fee_rebates_vest_after_their_period() {
+    new_test_ext().execute_with(|| {
+        FeeRebate::set(Permill::from_percent(50));
+        System::set_block_number(1);
+        assert_ok!(KittiesModule::create_and_list_kitty(
+            Origin::signed(100),
+            100
+        ));
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(200), 0, None));
+        // The mock's u64 accounts are too short to tell the rebate sub-account from the fee
+        // account, so the rebate is followed through the buyer's books instead
+        assert_eq!(KittiesModule::fee_rebates(200), (0, 5, 0));
+
+        assert_noop!(
+            KittiesModule::claim_fee_rebate(Origin::signed(200)),
+            Error::<Test>::NoRebateToClaim
+        );
+
+        System::set_block_number(10);
+        let balance = Balances::free_balance(200);
+        assert_ok!(KittiesModule::claim_fee_rebate(Origin::signed(200)));
+        assert_eq!(Balances::free_balance(200), balance + 5);
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::FeeRebateClaimed {
+                account: 200,
+                amount: 5
+            })
+        );
+        assert_noop!(
+            KittiesModule::claim_fee_rebate(Origin::signed(200)),
+            Error::<Test>::NoRebateToClaim
+        );
+    });
+}
+
+#[test]
+fn kitties_only_breed_in_season() {
+    use frame_support::traits::OnInitialize;
+
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        System::set_extrinsic_index(3);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+
+        assert_noop!(
+            KittiesModule::set_breeding_seasons(Origin::signed(100), vec![(5, 10)]),
+            sp_runtime::DispatchError::BadOrigin
+        );
+        assert_noop!(
+            KittiesModule::set_breeding_seasons(Origin::root(), vec![(5, 10), (8, 12)]),
+            Error::<Test>::InvalidBreedingSeasons
+        );
+        assert_noop!(
+            KittiesModule::set_breeding_seasons(Origin::root(), vec![(1, 2), (3, 4), (5, 6)]),
+            Error::<Test>::TooManyBreedingSeasons
+        );
+        assert_ok!(KittiesModule::set_breeding_seasons(
+            Origin::root(),
+            vec![(5, 10), (20, 30)]
+        ));
+
+        System::set_block_number(4);
+        assert_noop!(
+            KittiesModule::breed_kitty(Origin::signed(100), 0, 1),
+            Error::<Test>::OutOfSeason
+        );
+
+        System::set_block_number(5);
+        KittiesModule::on_initialize(5);
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::BreedingSeasonOpened { start: 5, end: 10 })
+        );
+        assert_ok!(KittiesModule::breed_kitty(Origin::signed(100), 0, 1));
+
+        System::set_block_number(10);
+        KittiesModule::on_initialize(10);
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::BreedingSeasonClosed { start: 5, end: 10 })
+        );
+        assert_noop!(
+            KittiesModule::breed_kitty(Origin::signed(100), 0, 1),
+            Error::<Test>::OutOfSeason
+        );
+    });
+}
+
+#[test]
+fn creating_kitties_costs_the_mint_price() {
+    new_test_ext().execute_with(|| {
+        assert_noop!(
+            KittiesModule::set_mint_price(Origin::signed(100), Some(10)),
+            sp_runtime::DispatchError::BadOrigin
+        );
+        assert_ok!(KittiesModule::set_mint_price(Origin::root(), Some(10)));
+        assert_eq!(KittiesModule::mint_price(), 10);
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::MintPriceSet { price: 10 })
+        );
+
+        let issuance = Balances::total_issuance();
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::create_and_list_kitty(
+            Origin::signed(100),
+            50
+        ));
+        assert_eq!(Balances::free_balance(100), 1_000 - 20);
+        assert_eq!(Balances::total_issuance(), issuance - 20);
+
+        // Accounts that cannot pay, or would be reaped by paying, cannot create kitties
+        assert_ok!(Balances::transfer(Origin::signed(200), 300, 995));
+        assert_noop!(
+            KittiesModule::create_kitty(Origin::signed(200)),
+            pallet_balances::Error::<Test>::InsufficientBalance
+        );
+        assert_ok!(KittiesModule::set_mint_price(Origin::root(), Some(5)));
+        assert_noop!(
+            KittiesModule::create_kitty(Origin::signed(200)),
+            pallet_balances::Error::<Test>::KeepAlive
+        );
+
+        assert_ok!(KittiesModule::set_mint_price(Origin::root(), None));
+        assert_eq!(KittiesModule::mint_price(), 0);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(200)));
+    });
+}
+
+#[test]
+fn kitties_never_share_dna() {
+    new_test_ext().execute_with(|| {
+        // Same owner, randomness and extrinsic index make the same first dna
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        let (_, first) = KittiesModule::kitty_of(0).unwrap();
+        let (_, second) = KittiesModule::kitty_of(1).unwrap();
+        assert_ne!(first, second);
+
+        // With empty dna every attempt collides
+        DnaLength::set(0);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_noop!(
+            KittiesModule::create_kitty(Origin::signed(100)),
+            Error::<Test>::DuplicateKitty
+        );
+
+        // Burning a kitty frees its dna
+        assert_ok!(KittiesModule::export_kitty(Origin::signed(100), 2));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+    });
+}
+
+#[test]
+fn can_surrender_and_adopt_kitties() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_and_list_kitty(
+            Origin::signed(100),
+            50
+        ));
+        assert_noop!(
+            KittiesModule::surrender_kitty(Origin::signed(200), 0),
+            orml_nft::Error::<Test>::NoPermission
+        );
+        assert_noop!(
+            KittiesModule::adopt_kitty(Origin::signed(200), 0),
+            Error::<Test>::KittyNotInShelter
+        );
+
+        assert_ok!(KittiesModule::surrender_kitty(Origin::signed(100), 0));
+        assert_eq!(
+            NFT::tokens(0, 0).unwrap().owner,
+            KittiesModule::shelter_account_id()
+        );
+        assert_eq!(KittiesModule::kitty_exchange(0), None);
+        assert_eq!(KittiesModule::shelter(0), Some(100));
+        assert_eq!(KittiesModule::sheltered_kitties(10), vec![0]);
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::KittySurrendered {
+                kitty: 0,
+                owner: 100
+            })
+        );
+
+        let issuance = Balances::total_issuance();
+        assert_ok!(KittiesModule::adopt_kitty(Origin::signed(200), 0));
+        assert_eq!(NFT::tokens(0, 0).unwrap().owner, 200);
+        assert_eq!(Balances::free_balance(200), 1_000 - 5);
+        assert_eq!(Balances::total_issuance(), issuance - 5);
+        assert!(KittiesModule::sheltered_kitties(10).is_empty());
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::KittyAdopted {
+                kitty: 0,
+                adopter: 200,
+                fee: 5
+            })
+        );
+    });
+}
+
+#[test]
+fn order_book_lists_the_whole_market() {
+    use crate::{CollectionBid, Denomination};
+
+    new_test_ext().execute_with(|| {
+        for price in [40, 30].iter() {
+            assert_ok!(KittiesModule::create_and_list_kitty(
+                Origin::signed(100),
+                *price
+            ));
+        }
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::set_reference_price(
+            Origin::signed(100),
+            2,
+            7
+        ));
+        assert_ok!(KittiesModule::place_collection_bid(
+            Origin::signed(200),
+            10,
+            None,
+            1
+        ));
+        assert_ok!(KittiesModule::place_collection_bid(
+            Origin::signed(200),
+            20,
+            None,
+            1
+        ));
+
+        let bid = |max_price| CollectionBid {
+            bidder: 200,
+            max_price,
+            trait_filter: None,
+            quantity: 1,
+        };
+        assert_eq!(
+            KittiesModule::order_book(10),
+            (
+                vec![
+                    (1, 100, 30, Denomination::Native),
+                    (0, 100, 40, Denomination::Native),
+                    (2, 100, 7, Denomination::Reference),
+                ],
+                vec![(1, bid(20)), (0, bid(10))]
+            )
+        );
+        assert_eq!(
+            KittiesModule::order_book(1),
+            (vec![(1, 100, 30, Denomination::Native)], vec![(1, bid(20))])
+        );
+    });
+}
+
+#[test]
+fn buyers_can_be_allowed_to_spend_their_whole_balance() {
+    use frame_support::traits::ExistenceRequirement;
+
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_and_list_kitty(
+            Origin::signed(100),
+            1_000
+        ));
+
+        assert_err!(
+            KittiesModule::buy_kitty(Origin::signed(200), 0, None),
+            pallet_balances::Error::<Test>::KeepAlive
+        );
+
+        BuyerExistenceRequirement::set(ExistenceRequirement::AllowDeath);
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(200), 0, None));
+        assert_eq!(NFT::tokens(0, 0).unwrap().owner, 200);
+        assert_eq!(Balances::free_balance(200), 0);
+        assert!(!System::account_exists(&200));
+    });
+}
+
+#[test]
+fn sales_cannot_leave_the_seller_below_existential_deposit() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::transfer_kitty(Origin::signed(100), 400, 0));
+        assert_ok!(KittiesModule::set_price(Origin::signed(400), 0, Some(9)));
+
+        ExistentialDeposit::set(10);
+        assert_err!(
+            KittiesModule::buy_kitty(Origin::signed(200), 0, None),
+            pallet_balances::Error::<Test>::ExistentialDeposit
+        );
+    });
+}
+
+#[test]
+fn can_certify_pedigree() {
+    use sp_io::hashing::blake2_256;
+
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        System::set_extrinsic_index(3);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::breed_kitty(Origin::signed(100), 0, 1));
+
+        assert_noop!(
+            KittiesModule::mint_pedigree_certificate(Origin::signed(200), 2),
+            Error::<Test>::KittyNotFound
+        );
+        assert_ok!(KittiesModule::mint_pedigree_certificate(
+            Origin::signed(100),
+            2
+        ));
+
+        let founder = |kitty_id| {
+            (
+                kitty_id,
+                KittiesModule::kitty_of(kitty_id).map(|(_, kitty)| kitty),
+            )
+                .using_encoded(blake2_256)
+        };
+        let (_, kitty) = KittiesModule::kitty_of(2).unwrap();
+        let lineage = (2u32, Some(kitty), founder(0), founder(1)).using_encoded(blake2_256);
+        let certificate = KittiesModule::pedigree_certificates(0).unwrap();
+        assert_eq!(certificate.holder, 100);
+        assert_eq!(certificate.kitty, 2);
+        assert_eq!(certificate.lineage, lineage);
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::PedigreeCertified {
+                certificate: 0,
+                kitty: 2,
+                holder: 100,
+                lineage
+            })
+        );
+
+        // Certificates stay with their holder
+        assert_ok!(KittiesModule::transfer_kitty(Origin::signed(100), 200, 2));
+        assert_eq!(KittiesModule::pedigree_certificates(0).unwrap().holder, 100);
+    });
+}
+
+#[test]
+fn can_clone_kitties() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        let original = KittiesModule::kitty_of(0).unwrap().1.dna().to_vec();
+        let last = original.len() - 1;
+
+        assert_noop!(
+            KittiesModule::clone_kitty(Origin::signed(200), 0),
+            Error::<Test>::KittyNotFound
+        );
+
+        assert_ok!(KittiesModule::clone_kitty(Origin::signed(100), 0));
+        assert_eq!(Balances::free_balance(100), 900);
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::KittyCloned {
+                original: 0,
+                clone: 1,
+                owner: 100,
+                fee: 100
+            })
+        );
+        let mut expected = original.clone();
+        expected[0] = 0;
+        expected[last] = original[last].wrapping_add(1);
+        assert_eq!(KittiesModule::kitty_of(1).unwrap().1.dna(), &expected[..]);
+
+        // Every clone of the same original gets its own counter
+        assert_ok!(KittiesModule::clone_kitty(Origin::signed(100), 0));
+        expected[last] = original[last].wrapping_add(2);
+        assert_eq!(KittiesModule::kitty_of(2).unwrap().1.dna(), &expected[..]);
+
+        // Clones of clones cost more and lose another trait
+        assert_ok!(KittiesModule::clone_kitty(Origin::signed(100), 1));
+        assert_eq!(Balances::free_balance(100), 600);
+        expected[1] = 0;
+        expected[last] = original[last].wrapping_add(3);
+        assert_eq!(KittiesModule::kitty_of(3).unwrap().1.dna(), &expected[..]);
+        assert_eq!(KittiesModule::clone_depth(3), 2);
+        assert_eq!(KittiesModule::clone_original(3), Some(0));
+        assert_eq!(KittiesModule::clone_count(0), 3);
+    });
+}
+
+#[test]
+fn sales_pay_tax_by_jurisdiction() {
+    new_test_ext().execute_with(|| {
+        assert_noop!(
+            KittiesModule::set_jurisdiction(Origin::signed(200), 200, Some(*b"EU")),
+            sp_runtime::DispatchError::BadOrigin
+        );
+        assert_ok!(KittiesModule::set_jurisdiction(
+            Origin::root(),
+            200,
+            Some(*b"EU")
+        ));
+        assert_eq!(KittiesModule::jurisdiction(200), Some(*b"EU"));
+
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, Some(100)));
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(200), 0, None));
+
+        // The tax comes out of the seller's proceeds
+        assert!(System::events().iter().any(|record| record.event
+            == Event::pallet_kitties(crate::Event::<Test>::SalesTaxPaid {
+                kitty: 0,
+                amount: 10,
+                collector: 300
+            })));
+        assert_eq!(Balances::free_balance(100), 1_080);
+        assert_eq!(Balances::free_balance(200), 900);
+        assert_eq!(Balances::free_balance(300), 1_010);
+        assert_eq!(Balances::free_balance(KittiesModule::account_id()), 1 + 10);
+
+        // Untagged buyers are not taxed
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 1, Some(100)));
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(101), 1, None));
+        assert_eq!(Balances::free_balance(100), 1_170);
+        assert_eq!(Balances::free_balance(300), 1_010);
+    });
+}
+
+#[test]
+fn can_schedule_listings_and_delistings() {
+    use frame_support::traits::OnInitialize;
+
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+
+        assert_noop!(
+            KittiesModule::schedule_listing(Origin::signed(200), 0, 50, 5),
+            Error::<Test>::KittyNotFound
+        );
+        assert_noop!(
+            KittiesModule::schedule_listing(Origin::signed(100), 0, 50, 1),
+            Error::<Test>::ScheduleInPast
+        );
+        assert_ok!(KittiesModule::schedule_listing(
+            Origin::signed(100),
+            0,
+            50,
+            5
+        ));
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::PriceChangeScheduled {
+                kitty: 0,
+                owner: 100,
+                price: Some(50),
+                block: 5
+            })
+        );
+        assert_ok!(KittiesModule::schedule_listing(
+            Origin::signed(100),
+            1,
+            60,
+            5
+        ));
+        assert_noop!(
+            KittiesModule::schedule_delist(Origin::signed(100), 0, 5),
+            Error::<Test>::TooManyScheduled
+        );
+        assert_ok!(KittiesModule::schedule_delist(Origin::signed(100), 0, 8));
+
+        // Kitties that changed hands in the meantime are not listed
+        assert_ok!(KittiesModule::transfer_kitty(Origin::signed(100), 200, 1));
+
+        System::set_block_number(5);
+        KittiesModule::on_initialize(5);
+        assert!(KittiesModule::kitty_exchange(0).is_some());
+        assert_eq!(KittiesModule::kitty_exchange(1), None);
+        // Events lose their error messages when they are stored
+        assert!(matches!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::ScheduledPriceChangeFailed {
+                kitty: 1,
+                owner: 100,
+                error: sp_runtime::DispatchError::Module { error: 3, .. }
+            })
+        ));
+        assert!(KittiesModule::scheduled_price_changes(5).is_empty());
+
+        System::set_block_number(8);
+        KittiesModule::on_initialize(8);
+        assert_eq!(KittiesModule::kitty_exchange(0), None);
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::KittyDelisted {
+                kitty: 0,
+                owner: 100
+            })
+        );
+    });
+}
+
+#[test]
+fn high_value_listings_need_reputation() {
+    use crate::Reputation;
+
+    new_test_ext().execute_with(|| {
+        assert_noop!(
+            KittiesModule::set_reputation_gate(Origin::signed(100), Some((50, 1))),
+            sp_runtime::DispatchError::BadOrigin
+        );
+        assert_ok!(KittiesModule::set_reputation_gate(
+            Origin::root(),
+            Some((50, 1))
+        ));
+
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_noop!(
+            KittiesModule::set_price(Origin::signed(100), 0, Some(100)),
+            Error::<Test>::InsufficientReputation
+        );
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, Some(50)));
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(200), 0, None));
+        assert_eq!(
+            KittiesModule::reputation(100),
+            Reputation {
+                sales: 1,
+                cancelled_bids: 0
+            }
+        );
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 1, Some(100)));
+
+        // Withdrawn bids count against the score
+        assert_ok!(KittiesModule::place_collection_bid(
+            Origin::signed(100),
+            10,
+            None,
+            1
+        ));
+        assert_ok!(KittiesModule::cancel_collection_bid(Origin::signed(100), 0));
+        assert_eq!(KittiesModule::reputation(100).score(), 0);
+        assert_noop!(
+            KittiesModule::set_price(Origin::signed(100), 1, Some(200)),
+            Error::<Test>::InsufficientReputation
+        );
+
+        // Delisting is always allowed
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 1, None));
+    });
+}
+
+#[test]
+fn can_find_similar_kitties() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::clone_kitty(Origin::signed(100), 0));
+        assert_ok!(KittiesModule::clone_kitty(Origin::signed(100), 1));
+
+        let kitty = |kitty_id| KittiesModule::kitty_of(kitty_id).unwrap().1;
+        let to_first = kitty(0).hamming_distance(&kitty(1));
+        let to_second = kitty(0).hamming_distance(&kitty(2));
+        assert!(to_first > 0 && to_first < to_second);
+
+        assert_eq!(
+            KittiesModule::similar_kitties(0, to_second, 10),
+            vec![(1, to_first), (2, to_second)]
+        );
+        assert_eq!(
+            KittiesModule::similar_kitties(0, to_first, 10),
+            vec![(1, to_first)]
+        );
+        assert_eq!(
+            KittiesModule::similar_kitties(0, to_second, 1),
+            vec![(1, to_first)]
+        );
+        assert!(KittiesModule::similar_kitties(9, u32::MAX, 10).is_empty());
+    });
+}
+
+#[test]
+fn keeps_recent_trades_per_account() {
+    use crate::TradeReceipt;
+
+    new_test_ext().execute_with(|| {
+        for kitty_id in 0..3 {
+            assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+            assert_ok!(KittiesModule::set_price(
+                Origin::signed(100),
+                kitty_id,
+                Some(10 * (kitty_id as u64 + 1))
+            ));
+        }
+        for kitty_id in 0..3 {
+            System::set_block_number(kitty_id as u64 + 1);
+            assert_ok!(KittiesModule::buy_kitty(
+                Origin::signed(200),
+                kitty_id,
+                None
+            ));
+        }
+
+        let receipt = |kitty: u32| TradeReceipt {
+            kitty,
+            seller: 100,
+            buyer: 200,
+            price: 10 * (kitty as u64 + 1),
+            block: kitty as u64 + 1,
+        };
+        // Only the last `TradeHistoryLength` trades are kept
+        assert_eq!(
+            KittiesModule::trade_history(100),
+            vec![receipt(1), receipt(2)]
+        );
+        assert_eq!(
+            KittiesModule::trade_history(200),
+            vec![receipt(1), receipt(2)]
+        );
+        assert!(KittiesModule::trade_history(300).is_empty());
+    });
+}
+
+#[test]
+fn migrates_listings_to_class_keys() {
+    use crate::{Denomination, Listing};
+    use frame_support::{
+        storage::migration::{get_storage_value, put_storage_value},
+        traits::OnRuntimeUpgrade,
+        Blake2_128Concat, StorageHasher,
+    };
+
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        let listing = Listing::<Test>(100, 50, Denomination::Native);
+        let key = Blake2_128Concat::hash(&0u32.encode());
+        put_storage_value(
+            b"KittiesModule",
+            b"KittyExchange",
+            &key,
+            Some(listing.clone()),
+        );
+        crate::StorageVersion::<Test>::put(crate::Releases::V5);
+
+        AllModules::on_runtime_upgrade();
+
+        assert_eq!(
+            get_storage_value::<Option<Listing<Test>>>(b"KittiesModule", b"KittyExchange", &key),
+            None
+        );
+        assert_eq!(KittiesModule::kitty_exchange(0), Some(listing));
+        assert_eq!(crate::StorageVersion::<Test>::get(), crate::Releases::V9);
+    });
+}
+
+#[test]
+fn race_winners_split_the_prize_pool() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        assert_noop!(
+            KittiesModule::open_race(Origin::root(), 50, 1, 2),
+            Error::<Test>::InvalidRace
+        );
+        assert_ok!(KittiesModule::open_race(Origin::root(), 50, 10, 2));
+
+        for (kitty_id, owner) in [(0u32, 100u64), (1, 200), (2, 300)].iter() {
+            assert_ok!(KittiesModule::create_kitty(Origin::signed(*owner)));
+            assert_ok!(KittiesModule::enter_race(
+                Origin::signed(*owner),
+                0,
+                *kitty_id
+            ));
+            if *kitty_id == 0 {
+                assert_noop!(
+                    KittiesModule::enter_race(Origin::signed(100), 0, 0),
+                    Error::<Test>::AlreadyEntered
+                );
+                assert_noop!(
+                    KittiesModule::enter_race(Origin::signed(200), 0, 0),
+                    Error::<Test>::KittyNotFound
+                );
+            }
+        }
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(101)));
+        assert_noop!(
+            KittiesModule::enter_race(Origin::signed(101), 0, 3),
+            Error::<Test>::RaceFull
+        );
+        assert_noop!(
+            KittiesModule::finish_race(Origin::signed(101), 0),
+            Error::<Test>::RaceNotClosed
+        );
+
+        System::set_block_number(10);
+        assert_noop!(
+            KittiesModule::enter_race(Origin::signed(101), 0, 3),
+            Error::<Test>::RaceClosed
+        );
+        assert_ok!(KittiesModule::finish_race(Origin::signed(101), 0));
+
+        // The mock randomness gives every kitty the same bonus, so the fastest two win
+        let mut expected = [(0u32, 100u64), (1, 200), (2, 300)]
+            .iter()
+            .map(|(kitty_id, owner)| {
+                let speed = KittiesModule::kitty_of(*kitty_id).unwrap().1.speed();
+                (speed, *kitty_id, *owner)
+            })
+            .collect::<Vec<_>>();
+        expected.sort_by_key(|(speed, ..)| sp_std::cmp::Reverse(*speed));
+        let winners = expected
+            .iter()
+            .take(2)
+            .map(|(_, kitty_id, owner)| (*kitty_id, *owner, 75))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::RaceFinished { race: 0, winners })
+        );
+        assert_eq!(Balances::free_balance(expected[0].2), 1_025);
+        assert_eq!(Balances::free_balance(expected[1].2), 1_025);
+        assert_eq!(Balances::free_balance(expected[2].2), 950);
+
+        assert_noop!(
+            KittiesModule::finish_race(Origin::signed(101), 0),
+            Error::<Test>::RaceNotFound
+        );
+    });
+}
+
+#[test]
+fn high_value_sales_wait_for_seller_confirmation() {
+    use frame_support::traits::OnInitialize;
+
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        assert_ok!(KittiesModule::set_high_value_threshold(
+            Origin::root(),
+            Some(50)
+        ));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, Some(100)));
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 1, Some(200)));
+
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(200), 0, None));
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::SaleAwaitingConfirmation {
+                sale: 0,
+                kitty: 0,
+                seller: 100,
+                buyer: 200,
+                price: 100,
+                expires_at: 6
+            })
+        );
+        assert_eq!(KittiesModule::market_hold(200), 100);
+        assert_eq!(KittiesModule::kitty_exchange(0), None);
+
+        // The kitty is escrowed, so the seller cannot relist it, sell it again or give it away
+        let escrow = KittiesModule::escrow_account_id();
+        assert_eq!(KittiesModule::kitty_of(0).unwrap().0, escrow);
+        assert_noop!(
+            KittiesModule::set_price(Origin::signed(100), 0, Some(60)),
+            Error::<Test>::KittyNotFound
+        );
+        assert_err!(
+            KittiesModule::buy_kitty(Origin::signed(300), 0, None),
+            Error::<Test>::KittyNotForSale
+        );
+        assert_noop!(
+            KittiesModule::transfer_kitty(Origin::signed(100), 300, 0),
+            orml_nft::Error::<Test>::NoPermission
+        );
+
+        assert_noop!(
+            KittiesModule::confirm_high_value_sale(Origin::signed(200), 0),
+            Error::<Test>::NotSeller
+        );
+        assert_ok!(KittiesModule::confirm_high_value_sale(
+            Origin::signed(100),
+            0
+        ));
+        assert!(KittiesModule::kitties(&200, 0).is_some());
+        assert_eq!(KittiesModule::market_hold(200), 0);
+        assert_eq!(Balances::free_balance(100), 1_090);
+        assert_eq!(Balances::free_balance(200), 900);
+
+        // Unconfirmed sales are reverted
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(300), 1, None));
+        assert_eq!(KittiesModule::market_hold(300), 200);
+        assert_eq!(KittiesModule::kitty_of(1).unwrap().0, escrow);
+        KittiesModule::on_initialize(6);
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::PendingSaleExpired { sale: 1, kitty: 1 })
+        );
+        assert_eq!(KittiesModule::market_hold(300), 0);
+        assert_eq!(Balances::free_balance(300), 1_000);
+        assert!(KittiesModule::kitties(&100, 1).is_some());
+        assert_noop!(
+            KittiesModule::confirm_high_value_sale(Origin::signed(100), 1),
+            Error::<Test>::PendingSaleNotFound
+        );
+    });
+}
+
+#[test]
+fn can_check_breeding_without_breeding() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        System::set_extrinsic_index(3);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+
+        assert_ok!(KittiesModule::can_breed(None, 0, 1));
+        assert_ok!(KittiesModule::can_breed(Some(100), 0, 1));
+        assert_eq!(
+            KittiesModule::can_breed(Some(200), 0, 1),
+            Err(Error::<Test>::KittyNotFound.into())
+        );
+        assert_eq!(
+            KittiesModule::can_breed(None, 0, 0),
+            Err(Error::<Test>::KittyPartnerMissing.into())
+        );
+
+        assert_ok!(KittiesModule::set_breeding_seasons(
+            Origin::root(),
+            vec![(5, 10)]
+        ));
+        assert_eq!(
+            KittiesModule::can_breed(None, 0, 1),
+            Err(Error::<Test>::OutOfSeason.into())
+        );
+        assert_eq!(next_kitty_id(), 2);
+    });
+}
+
+#[test]
+fn can_verify_dna_against_its_seed() {
+    use sp_io::hashing::blake2_256;
+
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        System::set_extrinsic_index(3);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::breed_kitty(Origin::signed(100), 0, 1));
+
+        // Created kitties are seeded by owner, randomness and extrinsic index
+        let created = (100u64, H256::default(), Some(0u32)).encode();
+        assert_eq!(KittiesModule::dna_seed(0), Some(blake2_256(&created)));
+        assert!(KittiesModule::verify_dna(0, created.clone()));
+        assert!(!KittiesModule::verify_dna(1, created));
+
+        // Bred kitties by randomness and extrinsic index, next to their parents' dna
+        let bred = (H256::default(), Some(3u32)).encode();
+        assert!(KittiesModule::verify_dna(2, bred.clone()));
+        assert!(!KittiesModule::verify_dna(
+            2,
+            (H256::default(), Some(4u32)).encode()
+        ));
+        assert!(!KittiesModule::verify_dna(3, bred));
+    });
+}
+
+#[test]
+fn frozen_collection_cannot_change() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_noop!(
+            KittiesModule::freeze_collection(Origin::signed(100)),
+            sp_runtime::DispatchError::BadOrigin
+        );
+        assert_ok!(KittiesModule::freeze_collection(Origin::root()));
+        assert!(KittiesModule::collection_frozen());
+
+        assert_noop!(
+            KittiesModule::create_kitty(Origin::signed(100)),
+            Error::<Test>::CollectionFrozen
+        );
+        assert_noop!(
+            KittiesModule::transfer_kitty(Origin::signed(100), 200, 0),
+            Error::<Test>::CollectionFrozen
+        );
+        assert_noop!(
+            KittiesModule::set_price(Origin::signed(100), 0, Some(10)),
+            Error::<Test>::CollectionFrozen
+        );
+        assert_noop!(
+            KittiesModule::buy_kitty(Origin::signed(200), 0, None),
+            Error::<Test>::CollectionFrozen
+        );
+        assert_noop!(
+            KittiesModule::set_accepts_returns(Origin::signed(100), 0, true),
+            Error::<Test>::CollectionFrozen
+        );
+
+        assert_ok!(KittiesModule::thaw_collection(Origin::root()));
+        assert!(!KittiesModule::collection_frozen());
+        assert_ok!(KittiesModule::transfer_kitty(Origin::signed(100), 200, 0));
+    });
+}
+
+#[test]
+fn coupons_discount_one_purchase() {
+    use sp_io::hashing::blake2_256;
+
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        let fee_code = blake2_256(b"HALFFEE");
+        let subsidy_code = blake2_256(b"PROMO");
+        assert_ok!(KittiesModule::issue_coupon(
+            Origin::root(),
+            fee_code,
+            Coupon {
+                kind: CouponKind::FeeDiscount,
+                discount: Permill::from_percent(50),
+            }
+        ));
+        assert_ok!(KittiesModule::issue_coupon(
+            Origin::root(),
+            subsidy_code,
+            Coupon {
+                kind: CouponKind::PriceSubsidy,
+                discount: Permill::from_percent(20),
+            }
+        ));
+        assert_noop!(
+            KittiesModule::issue_coupon(
+                Origin::signed(100),
+                fee_code,
+                Coupon {
+                    kind: CouponKind::FeeDiscount,
+                    discount: Permill::one(),
+                }
+            ),
+            sp_runtime::DispatchError::BadOrigin
+        );
+        assert_ok!(Balances::transfer(
+            Origin::signed(300),
+            KittiesModule::promo_account_id(),
+            100
+        ));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, Some(100)));
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 1, Some(100)));
+
+        assert_noop!(
+            KittiesModule::buy_kitty_with_coupon(Origin::signed(200), 0, b"WRONG".to_vec()),
+            Error::<Test>::InvalidCoupon
+        );
+        assert_ok!(KittiesModule::buy_kitty_with_coupon(
+            Origin::signed(200),
+            0,
+            b"HALFFEE".to_vec()
+        ));
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::CouponRedeemed {
+                code_hash: fee_code,
+                kitty: 0,
+                buyer: 200,
+                discount: 5
+            })
+        );
+        assert_eq!(Balances::free_balance(200), 905);
+        assert_eq!(Balances::free_balance(100), 1_090);
+
+        // Coupons are one-time use
+        assert_noop!(
+            KittiesModule::buy_kitty_with_coupon(Origin::signed(200), 1, b"HALFFEE".to_vec()),
+            Error::<Test>::InvalidCoupon
+        );
+        assert_noop!(
+            KittiesModule::issue_coupon(
+                Origin::root(),
+                fee_code,
+                Coupon {
+                    kind: CouponKind::FeeDiscount,
+                    discount: Permill::one(),
+                }
+            ),
+            Error::<Test>::CouponExists
+        );
+
+        assert_ok!(KittiesModule::buy_kitty_with_coupon(
+            Origin::signed(200),
+            1,
+            b"PROMO".to_vec()
+        ));
+        assert_eq!(Balances::free_balance(200), 825);
+    });
+}
+
+#[test]
+fn listing_prices_stay_within_bounds() {
+    new_test_ext().execute_with(|| {
+        assert_noop!(
+            KittiesModule::set_listing_price_bounds(Origin::root(), Some(500), Some(10)),
+            Error::<Test>::InvalidPriceBounds
+        );
+        assert_ok!(KittiesModule::set_listing_price_bounds(
+            Origin::root(),
+            Some(10),
+            Some(500)
+        ));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+
+        assert_noop!(
+            KittiesModule::set_price(Origin::signed(100), 0, Some(5)),
+            Error::<Test>::PriceOutOfBounds
+        );
+        assert_noop!(
+            KittiesModule::set_price(Origin::signed(100), 0, Some(5_000)),
+            Error::<Test>::PriceOutOfBounds
+        );
+        assert_noop!(
+            KittiesModule::create_and_list_kitty(Origin::signed(100), 1),
+            Error::<Test>::PriceOutOfBounds
+        );
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, Some(500)));
+
+        // Delisting is always allowed
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, None));
+
+        assert_ok!(KittiesModule::set_listing_price_bounds(
+            Origin::root(),
+            None,
+            None
+        ));
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, Some(5)));
+    });
+}
+
+#[test]
+fn counts_purchases_that_lose_the_race() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, Some(100)));
+
+        assert_noop!(
+            KittiesModule::buy_kitty(Origin::signed(100), 0, None),
+            Error::<Test>::CannotBuyOwnKitty
+        );
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(200), 0, None));
+        assert_err!(
+            KittiesModule::buy_kitty(Origin::signed(101), 0, None),
+            Error::<Test>::KittyNotForSale
+        );
+        assert_eq!(KittiesModule::failed_purchases(0), 1);
+
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::set_price(
+            Origin::signed(100),
+            1,
+            Some(5_000)
+        ));
+        assert!(KittiesModule::buy_kitty(Origin::signed(101), 1, None).is_err());
+        assert_eq!(KittiesModule::failed_purchases(1), 1);
+
+        // Unknown kitties are not tracked
+        assert_noop!(
+            KittiesModule::buy_kitty(Origin::signed(101), 9, None),
+            Error::<Test>::KittyNotForSale
+        );
+    });
+}
+
+#[test]
+fn named_event_fields_encode_in_declaration_order() {
+    // `KittySold` is the 31st event, and its fields follow its index in the order declared
+    let event = crate::Event::<Test>::KittySold {
+        kitty: 7,
+        price: 100,
+        seller: 1,
+        buyer: 2,
+    };
+    assert_eq!(event.encode(), (30u8, 7u32, 100u64, 1u64, 2u64).encode());
+}
+
+#[test]
+fn heirs_inherit_kitties_of_inactive_owners() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        assert_noop!(
+            KittiesModule::set_heir(Origin::signed(100), 100, 10),
+            Error::<Test>::InvalidHeir
+        );
+        assert_ok!(KittiesModule::set_heir(Origin::signed(100), 200, 10));
+        for _ in 0..3 {
+            assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        }
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, Some(50)));
+        crate::pallet::TransferLocks::<Test>::insert(1, 20);
+
+        System::set_block_number(10);
+        assert_noop!(
+            KittiesModule::claim_inheritance(Origin::signed(300), 100),
+            Error::<Test>::NotHeir
+        );
+        assert_noop!(
+            KittiesModule::claim_inheritance(Origin::signed(200), 100),
+            Error::<Test>::OwnerStillActive
+        );
+
+        // At most `MaxInheritedKitties` kitties move per claim
+        System::set_block_number(11);
+        assert_ok!(KittiesModule::claim_inheritance(Origin::signed(200), 100));
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::InheritanceClaimed {
+                owner: 100,
+                heir: 200,
+                count: 2
+            })
+        );
+
+        // Locked kitties wait for their lock to end
+        assert_ok!(KittiesModule::claim_inheritance(Origin::signed(200), 100));
+        assert!(KittiesModule::kitties(&100, 1).is_some());
+        assert_eq!(KittiesModule::heir(100), Some((200, 10)));
+        System::set_block_number(20);
+        assert_ok!(KittiesModule::claim_inheritance(Origin::signed(200), 100));
+        assert!((0..3).all(|kitty_id| KittiesModule::kitties(&200, kitty_id).is_some()));
+        assert_eq!(KittiesModule::kitty_exchange(0), None);
+        assert_eq!(KittiesModule::heir(100), None);
+        assert_noop!(
+            KittiesModule::claim_inheritance(Origin::signed(200), 100),
+            Error::<Test>::NotHeir
+        );
+    });
+}
+
+#[test]
+fn kitties_breed_up_to_their_fertility() {
+    new_test_ext().execute_with(|| {
+        let male = Kitty::from([2, 4, 6, 8, 10, 12, 14, 16, 18, 20, 22, 24, 0, 26, 28, 30]);
+        let female = Kitty::from([1, 3, 5, 7, 9, 11, 13, 15, 17, 19, 21, 23, 64, 25, 27, 99]);
+        assert_eq!(male.fertility(), 2);
+        assert_eq!(female.fertility(), 4);
+        assert_ok!(KittiesModule::do_mint(&100, male));
+        assert_ok!(KittiesModule::do_mint(&100, female));
+
+        assert_ok!(KittiesModule::breed_kitty(Origin::signed(100), 0, 1));
+        System::set_extrinsic_index(3);
+        assert_ok!(KittiesModule::breed_kitty(Origin::signed(100), 0, 1));
+        assert_eq!(KittiesModule::offspring_count(0), 2);
+        assert_eq!(KittiesModule::offspring_count(1), 2);
+
+        System::set_extrinsic_index(4);
+        assert_noop!(
+            KittiesModule::breed_kitty(Origin::signed(100), 0, 1),
+            Error::<Test>::KittyInfertile
+        );
+        assert_noop!(
+            KittiesModule::can_breed(None, 1, 0),
+            Error::<Test>::KittyInfertile
+        );
+        assert_eq!(
+            KittiesModule::simulate_breed(1, 0, Default::default()),
+            Err(Error::<Test>::KittyInfertile.into())
+        );
+    });
+}
+
+#[test]
+fn market_events_are_indexed_by_kitty_and_seller() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, Some(100)));
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(200), 0, None));
+
+        let kitty_topic = KittiesModule::kitty_topic(0);
+        let seller_topic = KittiesModule::seller_topic(&100);
+        let events = System::events();
+        let record = events.last().unwrap();
+        assert!(matches!(
+            record.event,
+            Event::pallet_kitties(crate::Event::<Test>::KittySold { .. })
+        ));
+        assert_eq!(record.topics, vec![kitty_topic, seller_topic]);
+
+        // Listed and sold
+        let indexed = System::event_topics(kitty_topic);
+        assert_eq!(indexed.len(), 2);
+        assert_eq!(indexed, System::event_topics(seller_topic));
+        assert!(System::event_topics(KittiesModule::seller_topic(&200)).is_empty());
+    });
+}
+
+#[test]
+fn held_funds_cannot_back_other_locks() {
+    use frame_support::traits::{LockableCurrency, WithdrawReasons};
+
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::place_collection_bid(
+            Origin::signed(200),
+            40,
+            None,
+            1
+        ));
+        // Another pallet locking the bidder's whole balance leaves the bid's funds alone
+        Balances::set_lock(*b"othrlock", &200, 1_000, WithdrawReasons::all());
+
+        assert_ok!(KittiesModule::fill_collection_bid(
+            Origin::signed(100),
+            0,
+            0
+        ));
+        assert!(KittiesModule::kitties(&200, 0).is_some());
+        assert_eq!(Balances::free_balance(100), 1_000 + 36);
+        assert_eq!(Balances::reserved_balance(200), 0);
+        assert_eq!(KittiesModule::market_hold(200), 0);
+    });
+}
+
+#[test]
+fn migrates_reserved_bid_funds_into_market_holds() {
+    use crate::CollectionBid;
+    use frame_support::traits::{OnRuntimeUpgrade, ReservableCurrency};
+
+    new_test_ext().execute_with(|| {
+        assert_ok!(Balances::reserve(&200, 120));
+        crate::CollectionBids::<Test>::insert(
+            0,
+            CollectionBid {
+                bidder: 200,
+                max_price: 40,
+                trait_filter: None,
+                quantity: 3,
+            },
+        );
+        crate::StorageVersion::<Test>::put(crate::Releases::V6);
+
+        AllModules::on_runtime_upgrade();
+
+        assert_eq!(Balances::reserved_balance(200), 120);
+        assert_eq!(KittiesModule::market_hold(200), 120);
+        assert_eq!(crate::StorageVersion::<Test>::get(), crate::Releases::V9);
+    });
+}
+
+#[test]
+fn breeding_licenses_can_be_sold_and_used_up() {
+    new_test_ext().execute_with(|| {
+        let male = Kitty::from([2, 4, 6, 8, 10, 12, 14, 16, 18, 20, 22, 24, 0, 26, 28, 30]);
+        let female = Kitty::from([1, 3, 5, 7, 9, 11, 13, 15, 17, 19, 21, 23, 64, 25, 27, 99]);
+        assert_ok!(KittiesModule::do_mint(&100, male));
+        assert_ok!(KittiesModule::do_mint(&200, female));
+
+        assert_noop!(
+            KittiesModule::mint_breeding_license(Origin::signed(200), 0, 1, None),
+            Error::<Test>::KittyNotFound
+        );
+        assert_ok!(KittiesModule::mint_breeding_license(
+            Origin::signed(100),
+            0,
+            1,
+            Some(50)
+        ));
+        assert_noop!(
+            KittiesModule::breed_with_license(Origin::signed(200), 0, 1),
+            Error::<Test>::NotLicenseHolder
+        );
+
+        let seller_balance = Balances::free_balance(100);
+        assert_ok!(KittiesModule::buy_breeding_license(Origin::signed(200), 0));
+        assert_eq!(Balances::free_balance(100), seller_balance + 50);
+        assert_eq!(KittiesModule::breeding_licenses(0).unwrap().holder, 200);
+        assert_noop!(
+            KittiesModule::buy_breeding_license(Origin::signed(300), 0),
+            Error::<Test>::LicenseNotForSale
+        );
+
+        assert_ok!(KittiesModule::breed_with_license(Origin::signed(200), 0, 1));
+        assert!(KittiesModule::kitties(&200, 2).is_some());
+        assert!(KittiesModule::kitties(&100, 0).is_some());
+        assert_eq!(KittiesModule::breeding_licenses(0), None);
+        assert_noop!(
+            KittiesModule::breed_with_license(Origin::signed(200), 0, 1),
+            Error::<Test>::LicenseNotFound
+        );
+
+        // Licenses are void once the kitty changes hands
+        assert_ok!(KittiesModule::mint_breeding_license(
+            Origin::signed(100),
+            0,
+            1,
+            Some(10)
+        ));
+        assert_ok!(KittiesModule::buy_breeding_license(Origin::signed(200), 1));
+        assert_ok!(KittiesModule::transfer_kitty(Origin::signed(100), 300, 0));
+        assert_noop!(
+            KittiesModule::breed_with_license(Origin::signed(200), 1, 1),
+            Error::<Test>::LicenseRevoked
+        );
+        assert_ok!(KittiesModule::mint_breeding_license(
+            Origin::signed(300),
+            0,
+            1,
+            Some(10)
+        ));
+        assert_ok!(KittiesModule::transfer_kitty(Origin::signed(300), 100, 0));
+        assert_noop!(
+            KittiesModule::buy_breeding_license(Origin::signed(200), 2),
+            Error::<Test>::LicenseRevoked
+        );
+    });
+}
+
+#[test]
+fn migrates_breeding_licenses_to_record_their_grantor() {
+    use frame_support::{
+        storage::migration::put_storage_value, traits::OnRuntimeUpgrade, StorageHasher,
+        Twox64Concat,
+    };
+
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        // `(kitty, holder, uses_left, price)` licenses, the second for a kitty that is gone
+        for (license_id, kitty_id) in [(0u32, 0u32), (1, 7)].iter() {
+            put_storage_value(
+                b"KittiesModule",
+                b"BreedingLicenses",
+                &Twox64Concat::hash(&license_id.encode()),
+                (*kitty_id, 200u64, 3u32, Some(5u64)),
+            );
+        }
+        crate::StorageVersion::<Test>::put(crate::Releases::V7);
+
+        AllModules::on_runtime_upgrade();
+
+        assert_eq!(
+            KittiesModule::breeding_licenses(0),
+            Some(crate::BreedingLicense {
+                kitty: 0,
+                grantor: 100,
+                holder: 200,
+                uses_left: 3,
+                price: Some(5),
+            })
+        );
+        assert_eq!(KittiesModule::breeding_licenses(1), None);
+        assert_eq!(crate::StorageVersion::<Test>::get(), crate::Releases::V9);
+    });
+}
+
+#[test]
+fn migrates_returnable_sales_to_record_what_a_return_undoes() {
+    use frame_support::{
+        storage::migration::put_storage_value, traits::OnRuntimeUpgrade, StorageHasher,
+        Twox64Concat,
+    };
+
+    new_test_ext().execute_with(|| {
+        System::set_block_number(25);
+        crate::CostBasis::<Test>::insert(3, 40);
+        // `(kitty, seller, buyer, refund, until)`
+        put_storage_value(
+            b"KittiesModule",
+            b"ReturnableSales",
+            &Twox64Concat::hash(&0u32.encode()),
+            (3u32, 100u64, 200u64, 30u64, 30u64),
+        );
+        crate::StorageVersion::<Test>::put(crate::Releases::V8);
+
+        AllModules::on_runtime_upgrade();
+
+        assert_eq!(
+            KittiesModule::returnable_sales(0),
+            Some(crate::ReturnableSale {
+                kitty: 3,
+                seller: 100,
+                buyer: 200,
+                refund: 30,
+                until: 30,
+                era: 2,
+                price: 0,
+                proceeds: 0,
+                cost_basis: 40,
+            })
+        );
+        assert_eq!(crate::StorageVersion::<Test>::get(), crate::Releases::V9);
+    });
+}
+
+#[test]
+fn minting_keeps_genders_balanced_when_enabled() {
+    new_test_ext().execute_with(|| {
+        BalanceMintGenders::set(true);
+        for account in 1..=10 {
+            assert_ok!(KittiesModule::create_kitty(Origin::signed(account)));
+            let (males, females) = KittiesModule::recent_mint_genders();
+            assert!(males.max(females) - males.min(females) <= 1);
+        }
+        assert_eq!(KittiesModule::recent_mint_genders(), (5, 5));
+    });
+}
+
+#[test]
+fn pages_through_the_collection() {
+    new_test_ext().execute_with(|| {
+        for account in 1..=3 {
+            assert_ok!(KittiesModule::create_kitty(Origin::signed(account)));
+        }
+
+        let (kitties, next) = KittiesModule::kitties_page(0, 2);
+        let ids = kitties
+            .iter()
+            .map(|(id, owner, _)| (*id, *owner))
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![(0, 1), (1, 2)]);
+        assert_eq!(next, Some(2));
+
+        let (kitties, next) = KittiesModule::kitties_page(2, 2);
+        assert_eq!(kitties.len(), 1);
+        assert_eq!(kitties[0].2, KittiesModule::kitties(&3, 2).unwrap());
+        assert_eq!(next, None);
+        assert_eq!(KittiesModule::kitties_page(5, 2), (vec![], None));
+    });
+}
+
+#[test]
+fn layaways_settle_on_the_last_installment_or_default() {
+    use frame_support::traits::OnInitialize;
+
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, Some(100)));
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 1, Some(100)));
+
+        assert_noop!(
+            KittiesModule::buy_kitty_installments(Origin::signed(200), 0, 5),
+            Error::<Test>::InvalidInstallments
+        );
+        assert_ok!(KittiesModule::buy_kitty_installments(
+            Origin::signed(200),
+            0,
+            3
+        ));
+        assert_eq!(KittiesModule::market_hold(200), 34);
+        assert_eq!(
+            KittiesModule::kitty_of(0).map(|(owner, _)| owner),
+            Some(KittiesModule::escrow_account_id())
+        );
+        assert_eq!(KittiesModule::kitty_exchange(0), None);
+        assert_noop!(
+            KittiesModule::pay_installment(Origin::signed(300), 0),
+            Error::<Test>::NotLayawayBuyer
+        );
+
+        // Paying on time moves the deadline, so the old one passes harmlessly
+        assert_ok!(KittiesModule::pay_installment(Origin::signed(200), 0));
+        assert_eq!(KittiesModule::market_hold(200), 67);
+        KittiesModule::on_initialize(11);
+        assert_eq!(KittiesModule::layaways(0).unwrap().due, 21);
+        assert_ok!(KittiesModule::pay_installment(Origin::signed(200), 0));
+        assert_eq!(KittiesModule::market_hold(200), 0);
+        assert!(KittiesModule::kitties(&200, 0).is_some());
+        assert_eq!(KittiesModule::layaways(0), None);
+
+        // A missed installment returns the kitty and costs the buyer the penalty
+        assert_ok!(KittiesModule::buy_kitty_installments(
+            Origin::signed(300),
+            1,
+            2
+        ));
+        assert_eq!(KittiesModule::market_hold(300), 50);
+        KittiesModule::on_initialize(11);
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::LayawayDefaulted {
+                layaway: 1,
+                kitty: 1,
+                buyer: 300,
+                penalty: 10,
+            })
+        );
+        assert_eq!(KittiesModule::market_hold(300), 0);
+        assert_eq!(Balances::free_balance(300), 990);
+        assert!(KittiesModule::kitties(&100, 1).is_some());
+    });
+}
+
+#[test]
+fn mints_are_rate_limited_per_block() {
+    use frame_support::traits::OnFinalize;
+
+    new_test_ext().execute_with(|| {
+        MaxMintsPerBlock::set(2);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(101)));
+        assert_noop!(
+            KittiesModule::create_kitty(Origin::signed(200)),
+            Error::<Test>::MintRateLimited
+        );
+
+        KittiesModule::on_finalize(1);
+        System::set_block_number(2);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(200)));
+    });
+}
+
+#[test]
+fn approved_grants_mint_kitties_for_the_beneficiary() {
+    new_test_ext().execute_with(|| {
+        assert_noop!(
+            KittiesModule::propose_grant(Origin::signed(200), 300, 4),
+            Error::<Test>::InvalidGrant
+        );
+        assert_ok!(KittiesModule::propose_grant(Origin::signed(200), 300, 2));
+        assert_ok!(KittiesModule::propose_grant(Origin::signed(200), 300, 1));
+        assert_noop!(
+            KittiesModule::approve_grant(Origin::signed(200), 0),
+            sp_runtime::DispatchError::BadOrigin
+        );
+
+        assert_ok!(KittiesModule::approve_grant(Origin::root(), 0));
+        assert!(KittiesModule::kitties(&300, 0).is_some());
+        assert!(KittiesModule::kitties(&300, 1).is_some());
+        assert_eq!(KittiesModule::grant_proposals(0), None);
+
+        assert_ok!(KittiesModule::reject_grant(Origin::root(), 1));
+        assert_eq!(KittiesModule::grant_proposals(1), None);
+        assert_noop!(
+            KittiesModule::approve_grant(Origin::root(), 1),
+            Error::<Test>::GrantNotFound
+        );
+    });
+}
+
+#[test]
+fn merkle_proofs_verify_every_leaf() {
+    use crate::merkle;
+
+    for count in 1..=7usize {
+        let leaves = (0..count as u8).map(|i| [i; 32]).collect::<Vec<_>>();
+        let root = merkle::root(leaves.clone());
+        for index in 0..count {
+            let proof = merkle::proof(leaves.clone(), index);
+            assert!(merkle::verify(root, leaves[index], index, count, &proof));
+            assert!(!merkle::verify(root, [99; 32], index, count, &proof));
+        }
+    }
+    assert_eq!(merkle::root(Vec::new()), [0; 32]);
+}
+
+#[test]
+fn owner_snapshots_prove_ownership() {
+    use crate::merkle;
+    use frame_support::traits::OnInitialize;
+
+    new_test_ext().execute_with(|| {
+        for account in [100, 101, 200].iter() {
+            assert_ok!(KittiesModule::create_kitty(Origin::signed(*account)));
+        }
+        // Two kitties are read per block
+        KittiesModule::on_initialize(10);
+        assert_eq!(KittiesModule::latest_owner_snapshot(), None);
+        assert!(KittiesModule::pending_owner_snapshot().is_some());
+        KittiesModule::on_initialize(11);
+        assert_eq!(KittiesModule::pending_owner_snapshot(), None);
+        assert_eq!(KittiesModule::latest_owner_snapshot(), Some(9));
+        let snapshot = KittiesModule::owner_snapshots(9).unwrap();
+        assert_eq!(snapshot.kitties, 3);
+
+        let proof = KittiesModule::ownership_proof(1).unwrap();
+        assert_eq!(proof.owner, 101);
+        let leaf = KittiesModule::owner_leaf(1, &101);
+        let verify = |leaf, proof: &crate::OwnershipProof<u64>| {
+            merkle::verify(
+                snapshot.root,
+                leaf,
+                proof.index as usize,
+                proof.kitties as usize,
+                &proof.siblings,
+            )
+        };
+        assert!(verify(leaf, &proof));
+        assert!(!verify(KittiesModule::owner_leaf(1, &200), &proof));
+
+        // Proofs are of the snapshotted owner, not a later one
+        assert_ok!(KittiesModule::transfer_kitty(Origin::signed(101), 300, 1));
+        let proof = KittiesModule::ownership_proof(1).unwrap();
+        assert_eq!(proof.owner, 101);
+        assert!(verify(leaf, &proof));
+        assert!(!verify(KittiesModule::owner_leaf(1, &300), &proof));
+
+        // Roots built a leaf at a time match those of the whole tree
+        for n in 0..40u32 {
+            let leaves = (0..n)
+                .map(|i| KittiesModule::owner_leaf(i, &100))
+                .collect::<Vec<_>>();
+            let mut peaks = Vec::new();
+            for leaf in leaves.iter() {
+                merkle::append(&mut peaks, *leaf);
+            }
+            assert_eq!(merkle::fold(&peaks), merkle::root(leaves));
+        }
+    });
+}
+
+#[test]
+fn quick_resales_pay_a_decaying_flip_fee() {
+    new_test_ext().execute_with(|| {
+        FlipFee::set(Permill::from_percent(50));
+        System::set_block_number(1);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_eq!(KittiesModule::marketplace_fee(0, 100), 10);
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, Some(100)));
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(200), 0, None));
+        assert_eq!(KittiesModule::marketplace_fee(0, 100), 50);
+
+        // Half way through the decay, half of the surcharge is left
+        System::set_block_number(6);
+        assert_eq!(KittiesModule::marketplace_fee(0, 100), 30);
+        assert_ok!(KittiesModule::set_price(Origin::signed(200), 0, Some(100)));
+        let seller_balance = Balances::free_balance(200);
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(300), 0, None));
+        assert_eq!(Balances::free_balance(200), seller_balance + 70);
+
+        // Fee discounts apply to the flip fee too
+        System::set_block_number(11);
+        assert_eq!(KittiesModule::marketplace_fee(0, 100), 30);
+        assert_ok!(KittiesModule::issue_coupon(
+            Origin::root(),
+            sp_io::hashing::blake2_256(b"HALFFEE"),
+            Coupon {
+                kind: CouponKind::FeeDiscount,
+                discount: Permill::from_percent(50),
+            }
+        ));
+        assert_ok!(KittiesModule::set_price(Origin::signed(300), 0, Some(100)));
+        let buyer_balance = Balances::free_balance(200);
+        assert_ok!(KittiesModule::buy_kitty_with_coupon(
+            Origin::signed(200),
+            0,
+            b"HALFFEE".to_vec()
+        ));
+        assert_eq!(Balances::free_balance(200), buyer_balance - 100 + 15);
+
+        System::set_block_number(21);
+        assert_eq!(KittiesModule::marketplace_fee(0, 100), 10);
+    });
+}
+
+#[test]
+fn kitties_evolve_with_age() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        let kitty = KittiesModule::kitties(&100, 0).unwrap();
+
+        System::set_block_number(10);
+        assert_noop!(
+            KittiesModule::evolve_kitty(Origin::signed(100), 0),
+            Error::<Test>::KittyTooYoung
+        );
+        System::set_block_number(11);
+        assert_ok!(KittiesModule::evolve_kitty(Origin::signed(100), 0));
+        assert_eq!(KittiesModule::evolution_stage(0), 1);
+        let evolved = KittiesModule::kitties(&100, 0).unwrap();
+        assert_eq!(evolved, kitty.evolved());
+        assert_eq!(evolved.gender(), kitty.gender());
+        assert_eq!(evolved.dna()[..13], kitty.dna()[..13]);
+        assert!(evolved.dna()[13] >= kitty.dna()[13]);
+        let seed = (100u64, H256::default(), Some(0u32)).encode();
+        assert!(KittiesModule::verify_dna(0, seed));
+
+        System::set_block_number(20);
+        assert_noop!(
+            KittiesModule::evolve_kitty(Origin::signed(100), 0),
+            Error::<Test>::KittyTooYoung
+        );
+        for block in [21, 31].iter() {
+            System::set_block_number(*block);
+            assert_ok!(KittiesModule::evolve_kitty(Origin::signed(100), 0));
+        }
+        System::set_block_number(100);
+        assert_noop!(
+            KittiesModule::evolve_kitty(Origin::signed(100), 0),
+            Error::<Test>::KittyFullyEvolved
+        );
+    });
+}
+
+#[test]
+fn sales_conserve_funds() {
+    new_test_ext().execute_with(|| {
+        FeeRebate::set(Permill::from_percent(50));
+        System::set_block_number(1);
+        assert_ok!(KittiesModule::set_jurisdiction(
+            Origin::root(),
+            200,
+            Some(*b"EU")
+        ));
+        assert_ok!(KittiesModule::create_and_list_kitty(
+            Origin::signed(100),
+            100
+        ));
+        // The mock's fee, rebate and escrow accounts are one and the same
+        let pallet = KittiesModule::account_id();
+        let accounts = [100, 200, 101, 300, pallet];
+
+        // Seller, buyer, referrer, tax collector and the pallet are paid what the events say
+        System::reset_events();
+        let deltas = balance_deltas(&accounts, || {
+            assert_ok!(KittiesModule::buy_kitty(Origin::signed(200), 0, Some(101)));
+        });
+        let (mut price, mut referral, mut tax) = (0, 0, 0);
+        for event in kitty_events() {
+            match event {
+                crate::Event::KittySold { price: paid, .. } => price = paid,
+                crate::Event::ReferralPaid { amount, .. } => referral = amount,
+                crate::Event::SalesTaxPaid { amount, .. } => tax = amount,
+                _ => {}
+            }
+        }
+        let fee = KittiesModule::marketplace_fee(0, price);
+        assert_eq!((price, referral, tax), (100, 5, 10));
+        assert_eq!(
+            deltas,
+            vec![
+                (price - fee - tax) as i64,
+                -(price as i64),
+                referral as i64,
+                tax as i64,
+                (fee - referral) as i64,
+            ]
+        );
+
+        // Claimed rebates come out of the pallet's share
+        System::set_block_number(10);
+        System::reset_events();
+        let deltas = balance_deltas(&accounts, || {
+            assert_ok!(KittiesModule::claim_fee_rebate(Origin::signed(200)));
+        });
+        let rebate = match kitty_events().last() {
+            Some(crate::Event::FeeRebateClaimed { amount, .. }) => *amount as i64,
+            event => panic!("unexpected event {:?}", event),
+        };
+        assert_eq!(deltas, vec![0, rebate, 0, 0, -rebate]);
+    });
+}
+
+#[test]
+fn layaways_conserve_funds() {
+    use frame_support::traits::OnInitialize;
+
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, Some(100)));
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 1, Some(100)));
+        let pallet = KittiesModule::account_id();
+        let accounts = [100, 200, 300, pallet];
+
+        // Installments are only held until the last one settles the sale
+        let deltas = balance_deltas(&accounts, || {
+            assert_ok!(KittiesModule::buy_kitty_installments(
+                Origin::signed(200),
+                0,
+                2
+            ));
+        });
+        assert_eq!(deltas, vec![0, 0, 0, 0]);
+        System::reset_events();
+        let deltas = balance_deltas(&accounts, || {
+            assert_ok!(KittiesModule::pay_installment(Origin::signed(200), 0));
+        });
+        let price = kitty_events()
+            .into_iter()
+            .find_map(|event| match event {
+                crate::Event::KittySold { price, .. } => Some(price),
+                _ => None,
+            })
+            .unwrap();
+        let fee = KittiesModule::marketplace_fee(0, price) as i64;
+        let price = price as i64;
+        assert_eq!(deltas, vec![price - fee, -price, 0, fee]);
+
+        // A default refunds the escrowed installments less the penalty paid to the seller
+        assert_ok!(KittiesModule::buy_kitty_installments(
+            Origin::signed(300),
+            1,
+            2
+        ));
+        System::reset_events();
+        let deltas = balance_deltas(&accounts, || {
+            KittiesModule::on_initialize(11);
+        });
+        let penalty = match kitty_events().last() {
+            Some(crate::Event::LayawayDefaulted { penalty, .. }) => *penalty as i64,
+            event => panic!("unexpected event {:?}", event),
+        };
+        assert_eq!(penalty, 10);
+        assert_eq!(deltas, vec![penalty, 0, -penalty, 0]);
+        assert_eq!(KittiesModule::market_hold(300), 0);
+    });
+}
+
+#[test]
+fn game_servers_act_within_their_permissions() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::open_race(Origin::root(), 50, 30, 1));
+
+        assert_noop!(
+            KittiesModule::authorize_game_server(Origin::signed(100), 300, 1 << 7, 20),
+            Error::<Test>::InvalidGameServer
+        );
+        assert_noop!(
+            KittiesModule::authorize_game_server(Origin::signed(100), 300, GAME_ENTER_RACES, 1),
+            Error::<Test>::InvalidGameServer
+        );
+        assert_noop!(
+            KittiesModule::enter_race(Origin::signed(300), 0, 0),
+            Error::<Test>::KittyNotFound
+        );
+
+        assert_ok!(KittiesModule::authorize_game_server(
+            Origin::signed(100),
+            300,
+            GAME_ENTER_RACES,
+            20
+        ));
+        assert_eq!(
+            KittiesModule::game_server(100, 300),
+            Some((GAME_ENTER_RACES, 20))
+        );
+        // Only the permissions given count, and transfers are never allowed
+        System::set_block_number(11);
+        assert_noop!(
+            KittiesModule::evolve_kitty(Origin::signed(300), 0),
+            Error::<Test>::KittyNotFound
+        );
+        assert_noop!(
+            KittiesModule::transfer_kitty(Origin::signed(300), 300, 0),
+            orml_nft::Error::<Test>::NoPermission
+        );
+        assert_ok!(KittiesModule::enter_race(Origin::signed(300), 0, 0));
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::RaceEntered {
+                race: 0,
+                kitty: 0,
+                owner: 100,
+            })
+        );
+        assert_eq!(Balances::free_balance(100), 950);
+        assert_eq!(Balances::free_balance(300), 1_000);
+
+        assert_ok!(KittiesModule::authorize_game_server(
+            Origin::signed(100),
+            300,
+            GAME_EVOLVE,
+            20
+        ));
+        assert_ok!(KittiesModule::evolve_kitty(Origin::signed(300), 0));
+        assert_ok!(KittiesModule::revoke_game_server(Origin::signed(100), 300));
+        assert_eq!(KittiesModule::game_server(100, 300), None);
+        System::set_block_number(21);
+        assert_noop!(
+            KittiesModule::evolve_kitty(Origin::signed(300), 0),
+            Error::<Test>::KittyNotFound
+        );
+
+        // Permissions lapse at their expiry
+        assert_ok!(KittiesModule::authorize_game_server(
+            Origin::signed(100),
+            300,
+            GAME_EVOLVE,
+            25
+        ));
+        System::set_block_number(25);
+        assert_noop!(
+            KittiesModule::evolve_kitty(Origin::signed(300), 0),
+            Error::<Test>::KittyNotFound
+        );
+    });
+}
+
+#[test]
+fn listing_prices_stand_for_a_while() {
+    new_test_ext().execute_with(|| {
+        MinListingStability::set(5);
+        System::set_block_number(1);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, Some(100)));
+        assert_eq!(KittiesModule::listed_at(0), Some(1));
+
+        System::set_block_number(5);
+        assert_noop!(
+            KittiesModule::set_price(Origin::signed(100), 0, Some(50)),
+            Error::<Test>::ListingTooFresh
+        );
+        assert_noop!(
+            KittiesModule::set_reference_price(Origin::signed(100), 0, 50),
+            Error::<Test>::ListingTooFresh
+        );
+        System::set_block_number(6);
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, Some(50)));
+        assert_eq!(KittiesModule::listed_at(0), Some(6));
+
+        // Delisting is always allowed, and relisting starts afresh
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, None));
+        assert_eq!(KittiesModule::listed_at(0), None);
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, Some(80)));
+        assert_noop!(
+            KittiesModule::set_price(Origin::signed(100), 0, Some(90)),
+            Error::<Test>::ListingTooFresh
+        );
+    });
+}
+
+#[test]
+fn admins_set_collection_metadata() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        assert_eq!(KittiesModule::class_metadata(), None);
+        assert_noop!(
+            KittiesModule::set_class_metadata(
+                Origin::signed(100),
+                b"Kitties".to_vec(),
+                vec![],
+                vec![]
+            ),
+            sp_runtime::DispatchError::BadOrigin
+        );
+        assert_noop!(
+            KittiesModule::set_class_metadata(
+                Origin::root(),
+                b"Kitties".to_vec(),
+                b"Far too long a description".to_vec(),
+                vec![]
+            ),
+            Error::<Test>::MetadataTooLong
+        );
+
+        assert_ok!(KittiesModule::set_class_metadata(
+            Origin::root(),
+            b"Kitties".to_vec(),
+            b"Cute cats".to_vec(),
+            b"ipfs://banner".to_vec()
+        ));
+        let metadata = crate::CollectionMetadata {
+            name: b"Kitties".to_vec(),
+            description: b"Cute cats".to_vec(),
+            banner_uri: b"ipfs://banner".to_vec(),
+        };
+        assert_eq!(KittiesModule::class_metadata(), Some(metadata.clone()));
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::ClassMetadataSet { metadata })
+        );
+    });
+}
+
+#[test]
+fn buyers_can_return_kitties_within_the_period() {
+    use frame_support::traits::OnInitialize;
+
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_noop!(
+            KittiesModule::set_accepts_returns(Origin::signed(100), 0, true),
+            Error::<Test>::KittyNotForSale
+        );
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, Some(100)));
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 1, Some(100)));
+        assert_ok!(KittiesModule::set_accepts_returns(
+            Origin::signed(100),
+            0,
+            true
+        ));
+        // Repricing keeps the choice
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, Some(100)));
+        assert_eq!(KittiesModule::accepts_returns(0), Some(100));
+
+        // The price less the restocking fee stays held with the seller for the return period
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(200), 0, None));
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::ReturnWindowOpened {
+                sale: 0,
+                kitty: 0,
+                buyer: 200,
+                refund: 80,
+                until: 11,
+            })
+        );
+        assert_eq!(KittiesModule::market_hold(100), 80);
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(300), 1, None));
+        assert_eq!(KittiesModule::returnable_sales(1), None);
+
+        assert_noop!(
+            KittiesModule::return_kitty(Origin::signed(300), 0),
+            Error::<Test>::NotSaleBuyer
+        );
+        System::set_block_number(5);
+        assert_ok!(KittiesModule::return_kitty(Origin::signed(200), 0));
+        assert!(KittiesModule::kitties(&100, 0).is_some());
+        assert_eq!(KittiesModule::market_hold(100), 0);
+        assert_eq!(Balances::free_balance(100), 1_000 + 90 + 90 - 80);
+        assert_eq!(Balances::free_balance(200), 1_000 - 100 + 80);
+        assert_noop!(
+            KittiesModule::return_kitty(Origin::signed(200), 0),
+            Error::<Test>::ReturnNotFound
+        );
+
+        // The hold is released once the period is over
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, Some(100)));
+        assert_ok!(KittiesModule::set_accepts_returns(
+            Origin::signed(100),
+            0,
+            true
+        ));
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(200), 0, None));
+        assert_eq!(KittiesModule::market_hold(100), 80);
+        KittiesModule::on_initialize(15);
+        assert_eq!(KittiesModule::market_hold(100), 0);
+        assert_noop!(
+            KittiesModule::return_kitty(Origin::signed(200), 1),
+            Error::<Test>::ReturnNotFound
+        );
+
+        // Delisting everything withdraws the offer of returns too
+        System::set_block_number(20);
+        assert_ok!(KittiesModule::set_price(Origin::signed(200), 0, Some(100)));
+        assert_ok!(KittiesModule::set_accepts_returns(
+            Origin::signed(200),
+            0,
+            true
+        ));
+        assert_ok!(KittiesModule::delist_all(Origin::signed(200)));
+        assert_eq!(KittiesModule::accepts_returns(0), None);
+
+        // Refunds come out of the proceeds, whatever else the seller has spent, and the flip
+        // fee leaves less of them than the price less the restocking fee
+        FlipFee::set(Permill::from_percent(50));
+        assert_ok!(KittiesModule::set_price(Origin::signed(300), 1, Some(100)));
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(200), 1, None));
+        System::set_block_number(25);
+        assert_ok!(KittiesModule::set_price(Origin::signed(200), 1, Some(120)));
+        assert_ok!(KittiesModule::set_accepts_returns(
+            Origin::signed(200),
+            1,
+            true
+        ));
+        assert_ok!(Balances::transfer(
+            Origin::signed(200),
+            400,
+            Balances::free_balance(200) - 1
+        ));
+        let reports = || {
+            (
+                KittiesModule::tax_report(&200, 2),
+                KittiesModule::tax_report(&300, 2),
+            )
+        };
+        let before = reports();
+        assert_eq!(KittiesModule::cost_basis(1), 100);
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(300), 1, None));
+        assert_eq!(KittiesModule::market_hold(200), 84);
+        assert_eq!(Balances::free_balance(200), 1);
+        assert_eq!(KittiesModule::cost_basis(1), 120);
+        assert_eq!(KittiesModule::tax_report(&200, 2).proceeds, 84);
+        assert_eq!(KittiesModule::tax_report(&300, 2).purchases, 120);
+
+        // A return takes the sale back out of both tax reports and restores the cost basis
+        assert_ok!(KittiesModule::return_kitty(Origin::signed(300), 2));
+        assert_eq!(reports(), before);
+        assert_eq!(KittiesModule::cost_basis(1), 100);
+    });
+}
+
+#[test]
+fn airdrops_pay_kitty_owners_over_several_blocks() {
+    use frame_support::traits::{Currency, OnInitialize};
+
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        for owner in [100, 100, 200].iter() {
+            assert_ok!(KittiesModule::create_kitty(Origin::signed(*owner)));
+        }
+        let promo = KittiesModule::promo_account_id();
+        Balances::make_free_balance_be(&promo, 1_000);
+
+        assert_noop!(
+            KittiesModule::snapshot_and_drop(Origin::signed(100), 10),
+            sp_runtime::DispatchError::BadOrigin
+        );
+        assert_ok!(KittiesModule::snapshot_and_drop(Origin::root(), 10));
+        assert_noop!(
+            KittiesModule::snapshot_and_drop(Origin::root(), 10),
+            Error::<Test>::AirdropInProgress
+        );
+        // Kitties minted after the snapshot are left out
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(300)));
+
+        KittiesModule::on_initialize(2);
+        assert_eq!(Balances::free_balance(100), 1_020);
+        assert_eq!(Balances::free_balance(200), 1_000);
+        assert_eq!(KittiesModule::active_airdrop().map(|a| a.paid), Some(2));
+
+        KittiesModule::on_initialize(3);
+        assert_eq!(Balances::free_balance(200), 1_010);
+        assert_eq!(Balances::free_balance(300), 1_000);
+        assert_eq!(KittiesModule::active_airdrop(), None);
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::AirdropFinished {
+                amount_per_kitty: 10,
+                paid: 3,
+            })
+        );
+    });
+}
+
+#[test]
+fn transfers_take_kitties_off_the_market() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        for _ in 0..3 {
+            assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        }
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 0, Some(100)));
+        assert_ok!(KittiesModule::set_accepts_returns(
+            Origin::signed(100),
+            0,
+            true
+        ));
+        assert_ok!(KittiesModule::set_reference_price(
+            Origin::signed(100),
+            1,
+            20
+        ));
+        let listed = |kitty_id| KittiesModule::kitty_exchange(kitty_id);
+        let order_book = || {
+            KittiesModule::order_book(10)
+                .0
+                .into_iter()
+                .map(|(kitty, seller, _, _)| (kitty, seller))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(order_book(), vec![(0, 100), (1, 100)]);
+
+        // Transfers to oneself leave every market state as it was
+        for kitty_id in 0..3 {
+            assert_storage_noop!({
+                let _ = KittiesModule::transfer_kitty(Origin::signed(100), 100, kitty_id);
+            });
+        }
+        assert!(listed(0).is_some() && listed(1).is_some() && listed(2).is_none());
+        assert_eq!(KittiesModule::accepts_returns(0), Some(100));
+
+        // Real transfers withdraw native and reference listings along with accepted returns
+        assert_ok!(KittiesModule::transfer_kitty(Origin::signed(100), 200, 0));
+        assert_eq!(listed(0), None);
+        assert_eq!(KittiesModule::listed_at(0), None);
+        assert_eq!(KittiesModule::accepts_returns(0), None);
+        assert_ok!(KittiesModule::transfer_with_lock(
+            Origin::signed(100),
+            200,
+            1,
+            5
+        ));
+        assert_eq!(listed(1), None);
+        assert_ok!(KittiesModule::transfer_kitty(Origin::signed(100), 200, 2));
+        assert_eq!(order_book(), vec![]);
+        assert_ok!(KittiesModule::delist_all(Origin::signed(100)));
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::KittyTransfer {
+                kitty: 2,
+                from: 100,
+                to: 200
+            })
+        );
+
+        // The new owner lists afresh, without accepting returns
+        assert_ok!(KittiesModule::set_price(Origin::signed(200), 0, Some(50)));
+        assert_eq!(KittiesModule::accepts_returns(0), None);
+        assert_eq!(order_book(), vec![(0, 200)]);
+    });
+}
+
+#[test]
+fn admin_actions_are_audited() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        assert_ok!(KittiesModule::freeze_collection(Origin::root()));
+        // Failed and unprivileged calls are not logged
+        assert_noop!(
+            KittiesModule::freeze_collection(Origin::signed(100)),
+            sp_runtime::DispatchError::BadOrigin
+        );
+        let frozen = Event::pallet_kitties(crate::Event::<Test>::CollectionFrozen);
+        assert_eq!(last_event(), frozen);
+        let entry = |event: crate::Event<Test>| {
+            let encoded = event.encode();
+            crate::AuditEntry {
+                block: System::block_number(),
+                action: encoded[0],
+                payload_hash: sp_io::hashing::blake2_256(&encoded),
+            }
+        };
+        let first = entry(crate::Event::<Test>::CollectionFrozen);
+        assert_eq!(KittiesModule::audit_log(), vec![first.clone()]);
+
+        System::set_block_number(2);
+        assert_ok!(KittiesModule::thaw_collection(Origin::root()));
+        assert_ok!(KittiesModule::set_mint_price(Origin::root(), Some(5)));
+        // Only the latest `MaxAuditLogLength` actions are kept
+        let log = KittiesModule::audit_log();
+        assert_eq!(log.len(), 2);
+        assert!(!log.contains(&first));
+        assert_eq!(log[0], entry(crate::Event::<Test>::CollectionThawed));
+        assert_ne!(log[0].action, log[1].action);
+    });
+}
+
+#[test]
+fn kitties_are_appraised_from_comparable_sales() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        assert_ok!(KittiesModule::create_and_list_kitty(
+            Origin::signed(100),
+            80
+        ));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(100)));
+        assert_eq!(KittiesModule::appraise(0), None);
+        assert_eq!(KittiesModule::appraise(5), None);
+
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(200), 0, None));
+        // A sold kitty shares every trait with itself
+        assert_eq!(KittiesModule::appraise(0), Some(80));
+        let kitty = KittiesModule::kitties(&200, 0).unwrap();
+        for trait_key in kitty.traits() {
+            assert_eq!(KittiesModule::trait_price(trait_key), Some((80, 1)));
+        }
+
+        // Later sales move the averages by an eighth of the difference
+        System::set_block_number(1 + ResaleCooldown::get());
+        assert_ok!(KittiesModule::set_price(Origin::signed(200), 0, Some(160)));
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(300), 0, None));
+        assert_eq!(KittiesModule::appraise(0), Some(90));
+
+        // Other kitties are valued by the traits they share, if any
+        let other = KittiesModule::kitties(&100, 1).unwrap();
+        let shared = other
+            .traits()
+            .filter(|t| kitty.traits().any(|k| k == *t))
+            .count();
+        assert_eq!(KittiesModule::appraise(1).is_some(), shared > 0);
+
+        // Only the traits of the first `DnaLength` dna bytes are priced
+        DnaLength::set(2);
+        assert_ok!(KittiesModule::set_price(Origin::signed(100), 1, Some(40)));
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(200), 1, None));
+        for (index, trait_key) in other.traits().enumerate() {
+            if !kitty.traits().any(|k| k == trait_key) {
+                assert_eq!(KittiesModule::trait_price(trait_key).is_some(), index < 2);
+            }
+        }
+    });
+}
+
+#[test]
+fn signed_trades_swap_kitties_and_balance() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        assert_ok!(KittiesModule::create_and_list_kitty(
+            Origin::signed(100),
+            50
+        ));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(200)));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(300)));
+        let terms = crate::TradeTerms {
+            submitter: 100,
+            counterparty: 200,
+            give: vec![0],
+            take: vec![1],
+            submitter_pays: 30,
+            counterparty_pays: 0,
+            expires: 5,
+            nonce: 0,
+        };
+        let signature = |signer, terms: &crate::TradeTerms<u64, u32, u64, u64>| {
+            TestSignature(signer, KittiesModule::trade_payload(terms))
+        };
+
+        assert_noop!(
+            KittiesModule::execute_trade(
+                Origin::signed(300),
+                terms.clone(),
+                signature(200, &terms)
+            ),
+            Error::<Test>::InvalidTrade
+        );
+        assert_noop!(
+            KittiesModule::execute_trade(
+                Origin::signed(100),
+                terms.clone(),
+                signature(300, &terms)
+            ),
+            Error::<Test>::InvalidTradeSignature
+        );
+        let mut changed = terms.clone();
+        changed.counterparty_pays = 30;
+        assert_noop!(
+            KittiesModule::execute_trade(Origin::signed(100), changed, signature(200, &terms)),
+            Error::<Test>::InvalidTradeSignature
+        );
+        // Signatures over the bare terms are not accepted
+        assert_noop!(
+            KittiesModule::execute_trade(
+                Origin::signed(100),
+                terms.clone(),
+                TestSignature(200, terms.encode())
+            ),
+            Error::<Test>::InvalidTradeSignature
+        );
+        // Nothing changes hands if any part of the trade fails
+        let mut unowned = terms.clone();
+        unowned.take = vec![1, 2];
+        assert_noop!(
+            KittiesModule::execute_trade(
+                Origin::signed(100),
+                unowned.clone(),
+                signature(200, &unowned)
+            ),
+            orml_nft::Error::<Test>::NoPermission
+        );
+
+        assert_ok!(KittiesModule::execute_trade(
+            Origin::signed(100),
+            terms.clone(),
+            signature(200, &terms)
+        ));
+        assert!(KittiesModule::kitties(&200, 0).is_some());
+        assert!(KittiesModule::kitties(&100, 1).is_some());
+        assert_eq!(KittiesModule::kitty_exchange(0), None);
+        assert_eq!(Balances::free_balance(100), 970);
+        assert_eq!(Balances::free_balance(200), 1_030);
+        assert_eq!(
+            last_event(),
+            Event::pallet_kitties(crate::Event::<Test>::TradeExecuted {
+                submitter: 100,
+                counterparty: 200,
+                given: vec![0],
+                taken: vec![1],
+            })
+        );
+
+        // The signature cannot be replayed once the kitties are back
+        assert_eq!(KittiesModule::trade_nonce(200), 1);
+        assert_ok!(KittiesModule::transfer_kitty(Origin::signed(200), 100, 0));
+        assert_ok!(KittiesModule::transfer_kitty(Origin::signed(100), 200, 1));
+        assert_noop!(
+            KittiesModule::execute_trade(
+                Origin::signed(100),
+                terms.clone(),
+                signature(200, &terms)
+            ),
+            Error::<Test>::InvalidTrade
+        );
+        let back = crate::TradeTerms {
+            give: vec![1],
+            take: vec![0],
+            ..terms
+        };
+        System::set_block_number(6);
+        let expired = crate::TradeTerms { nonce: 1, ..back };
+        assert_noop!(
+            KittiesModule::execute_trade(
+                Origin::signed(100),
+                expired.clone(),
+                signature(200, &expired)
+            ),
+            Error::<Test>::InvalidTrade
+        );
+    });
+}
+
+#[test]
+fn tax_reports_track_sales_per_era() {
+    use frame_support::traits::OnInitialize;
+
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        assert_ok!(KittiesModule::create_and_list_kitty(
+            Origin::signed(100),
+            100
+        ));
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(200), 0, None));
+
+        // A minted kitty has no cost basis
+        let report = |who, era| KittiesModule::tax_report(&who, era);
+        assert_eq!(
+            report(100, 0),
+            crate::TaxReport {
+                proceeds: 90,
+                cost_basis: 0,
+                purchases: 0
+            }
+        );
+        assert_eq!(report(200, 0).purchases, 100);
+
+        // Reselling in the next era reports what the seller paid as its cost basis
+        System::set_block_number(12);
+        assert_ok!(KittiesModule::set_price(Origin::signed(200), 0, Some(150)));
+        assert_ok!(KittiesModule::buy_kitty(Origin::signed(300), 0, None));
+        assert_eq!(
+            report(200, 1),
+            crate::TaxReport {
+                proceeds: 135,
+                cost_basis: 100,
+                purchases: 0
+            }
+        );
+        assert_eq!(report(300, 1).purchases, 150);
+        assert_eq!(report(200, 0).purchases, 100);
+
+        // Only the last two eras are kept, and two reports are removed per block
+        let old = crate::TaxReport {
+            proceeds: 0,
+            cost_basis: 0,
+            purchases: 5,
+        };
+        crate::TaxReports::<Test>::insert(0, 400, old);
+        System::set_block_number(20);
+        KittiesModule::on_initialize(20);
+        let in_era_zero = [100, 200, 400]
+            .iter()
+            .filter(|who| crate::TaxReports::<Test>::contains_key(0, **who))
+            .count();
+        assert_eq!(in_era_zero, 1);
+        KittiesModule::on_initialize(21);
+        assert_eq!(report(100, 0), Default::default());
+        assert_eq!(report(200, 0), Default::default());
+        assert_eq!(report(400, 0), Default::default());
+        assert_eq!(report(200, 1).proceeds, 135);
     });
 }
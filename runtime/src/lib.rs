@@ -12,12 +12,12 @@ use sp_api::impl_runtime_apis;
 use sp_consensus_aura::sr25519::AuthorityId as AuraId;
 use sp_core::{crypto::KeyTypeId, OpaqueMetadata};
 use sp_runtime::traits::{
-    AccountIdLookup, BlakeTwo256, Block as BlockT, IdentifyAccount, NumberFor, Verify,
+    AccountIdLookup, BlakeTwo256, Block as BlockT, Convert, IdentifyAccount, NumberFor, Verify,
 };
 use sp_runtime::{
     create_runtime_str, generic, impl_opaque_keys,
     transaction_validity::{TransactionSource, TransactionValidity},
-    ApplyExtrinsicResult, MultiSignature,
+    ApplyExtrinsicResult, ModuleId, MultiSignature,
 };
 use sp_std::prelude::*;
 #[cfg(feature = "std")]
@@ -27,7 +27,7 @@ use sp_version::RuntimeVersion;
 // A few exports that help ease life for downstream crates.
 pub use frame_support::{
     construct_runtime, parameter_types,
-    traits::{KeyOwnerProofSystem, Randomness},
+    traits::{ExistenceRequirement, KeyOwnerProofSystem, Randomness},
     weights::{
         constants::{BlockExecutionWeight, ExtrinsicBaseWeight, RocksDbWeight, WEIGHT_PER_SECOND},
         IdentityFee, Weight,
@@ -106,10 +106,12 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
     //   `spec_version`, and `authoring_version` are the same between Wasm and native.
     // This value is set to 100 to notify Polkadot-JS App (https://polkadot.js.org/apps) to use
     //   the compatible custom types.
-    spec_version: 101,
+    // 104 runs every kitties storage migration up to `Releases::V9`.
+    spec_version: 104,
     impl_version: 1,
     apis: RUNTIME_API_VERSIONS,
-    transaction_version: 1,
+    // Kitties call indices and event encodings changed since version 1.
+    transaction_version: 2,
 };
 
 /// This determines the average expected block time that we are targeting.
@@ -273,10 +275,139 @@ impl pallet_template::Config for Runtime {
     type Event = Event;
 }
 
+parameter_types! {
+    pub const KittiesModuleId: ModuleId = ModuleId(*b"py/kitty");
+    pub const MarketplaceFee: Permill = Permill::from_percent(2);
+    pub const FlipFee: Permill = Permill::from_percent(10);
+    pub const FlipFeeDecay: BlockNumber = 7 * DAYS;
+    pub const ReferralShare: Permill = Permill::from_percent(25);
+    pub const BreedingFeeBase: Balance = 1_000_000_000;
+    pub const MaxGeneration: Option<u32> = Some(32);
+    pub const ForbidIncest: bool = true;
+    pub const BalanceMintGenders: bool = true;
+    pub const MaxOracleAge: BlockNumber = 10 * MINUTES;
+    pub const ResaleCooldown: BlockNumber = HOURS;
+    pub const ConfirmationPeriod: BlockNumber = HOURS;
+    pub const InstallmentPeriod: BlockNumber = 7 * DAYS;
+    pub const MaxInstallments: u32 = 12;
+    pub const LayawayPenalty: Permill = Permill::from_percent(10);
+    pub const WashTradeWindow: BlockNumber = DAYS;
+    pub const DnaLength: u32 = 16;
+    pub const TwinProbability: Permill = Permill::from_percent(1);
+    pub const MilestoneInterval: Option<u64> = Some(10_000);
+    pub const MilestoneReward: Balance = 100_000_000_000;
+    pub const MaxQuests: u32 = 16;
+    pub const MaxQuestProgressRemovals: u32 = 500;
+    pub const MaxRaceEntrants: u32 = 64;
+    pub const SpendingPeriod: BlockNumber = DAYS;
+    pub const StatsPeriod: BlockNumber = DAYS;
+    pub const StatsHistory: u32 = 90;
+    pub const TradeHistoryLength: u32 = 20;
+    pub const MaxDelistAll: u32 = 64;
+    pub const MaxMintsPerBlock: u32 = 1_000;
+    pub const MaxGrantKitties: u32 = 20;
+    pub const OwnerSnapshotPeriod: BlockNumber = DAYS;
+    pub const MaxSnapshotKittiesPerBlock: u32 = 1_000;
+    pub const EvolutionInterval: BlockNumber = 30 * DAYS;
+    pub const MinListingStability: BlockNumber = HOURS;
+    pub const MaxMetadataLength: u32 = 512;
+    pub const ReturnPeriod: BlockNumber = 3 * DAYS;
+    pub const RestockingFee: Permill = Permill::from_percent(5);
+    pub const MaxAirdropsPerBlock: u32 = 100;
+    pub const MaxAuditLogLength: u32 = 256;
+    pub const TaxEraLength: BlockNumber = 30 * DAYS;
+    pub const TaxHistory: u32 = 24;
+    pub const MaxTaxPrunesPerBlock: u32 = 500;
+    pub const MaxInheritedKitties: u32 = 64;
+    pub const MaxFloorBuy: u32 = 16;
+    pub const MaxScheduledPerBlock: u32 = 32;
+    pub const CloneFee: Balance = 10_000_000_000;
+    pub const PedigreeDepth: u32 = 8;
+    pub const AdoptionFee: Balance = 10_000_000;
+    pub const MintPrice: Balance = 100_000_000;
+    pub const MaxBreedingSeasons: u32 = 12;
+    pub const BuyerExistenceRequirement: ExistenceRequirement = ExistenceRequirement::KeepAlive;
+    pub const FeeRebate: Permill = Permill::from_percent(20);
+    pub const RebatePeriod: BlockNumber = 7 * DAYS;
+}
+
+/// Breeding costs `BreedingFeeBase` for first-generation parents and doubles with every
+/// generation after that.
+pub struct BreedingFeeCurve;
+
+impl Convert<u32, Balance> for BreedingFeeCurve {
+    fn convert(generation: u32) -> Balance {
+        BreedingFeeBase::get().saturating_mul(2u128.saturating_pow(generation))
+    }
+}
+
 impl pallet_kitties::Config for Runtime {
     type Event = Event;
     type RandomnessSource = RandomnessCollectiveFlip;
+    type DnaProvider = pallet_kitties::Blake2Dna;
     type Currency = Balances;
+    type ModuleId = KittiesModuleId;
+    type MarketplaceFee = MarketplaceFee;
+    type FlipFee = FlipFee;
+    type FlipFeeDecay = FlipFeeDecay;
+    type ReferralShare = ReferralShare;
+    type BreedingFee = BreedingFeeCurve;
+    type CloneFee = CloneFee;
+    type MaxGeneration = MaxGeneration;
+    type ForbidIncest = ForbidIncest;
+    type BalanceMintGenders = BalanceMintGenders;
+    type PriceOracle = ();
+    type MaxOracleAge = MaxOracleAge;
+    type ResaleCooldown = ResaleCooldown;
+    type ConfirmationPeriod = ConfirmationPeriod;
+    type InstallmentPeriod = InstallmentPeriod;
+    type MaxInstallments = MaxInstallments;
+    type LayawayPenalty = LayawayPenalty;
+    type WashTradeWindow = WashTradeWindow;
+    type ImportSignature = Signature;
+    type ImportSigner = <Signature as Verify>::Signer;
+    type TradeSignature = Signature;
+    type TradeSigner = <Signature as Verify>::Signer;
+    type DnaLength = DnaLength;
+    type TwinProbability = TwinProbability;
+    type MilestoneInterval = MilestoneInterval;
+    type MilestoneReward = MilestoneReward;
+    type MaxQuests = MaxQuests;
+    type MaxQuestProgressRemovals = MaxQuestProgressRemovals;
+    type MaxRaceEntrants = MaxRaceEntrants;
+    type SpendingPeriod = SpendingPeriod;
+    type StatsPeriod = StatsPeriod;
+    type StatsHistory = StatsHistory;
+    type TradeHistoryLength = TradeHistoryLength;
+    type MaxDelistAll = MaxDelistAll;
+    type MaxMintsPerBlock = MaxMintsPerBlock;
+    type MaxGrantKitties = MaxGrantKitties;
+    type OwnerSnapshotPeriod = OwnerSnapshotPeriod;
+    type MaxSnapshotKittiesPerBlock = MaxSnapshotKittiesPerBlock;
+    type EvolutionInterval = EvolutionInterval;
+    type MinListingStability = MinListingStability;
+    type MaxMetadataLength = MaxMetadataLength;
+    type ReturnPeriod = ReturnPeriod;
+    type RestockingFee = RestockingFee;
+    type MaxAirdropsPerBlock = MaxAirdropsPerBlock;
+    type MaxAuditLogLength = MaxAuditLogLength;
+    type TaxEraLength = TaxEraLength;
+    type TaxHistory = TaxHistory;
+    type MaxTaxPrunesPerBlock = MaxTaxPrunesPerBlock;
+    type MaxInheritedKitties = MaxInheritedKitties;
+    type MaxFloorBuy = MaxFloorBuy;
+    type MaxScheduledPerBlock = MaxScheduledPerBlock;
+    type MintPrice = MintPrice;
+    type MintPriceDestination = ();
+    type AdoptionFee = AdoptionFee;
+    type AdoptionFeeDestination = ();
+    type MaxBreedingSeasons = MaxBreedingSeasons;
+    type PedigreeDepth = PedigreeDepth;
+    type BuyerExistenceRequirement = BuyerExistenceRequirement;
+    type TaxHandler = ();
+    type FeeRebate = FeeRebate;
+    type RebatePeriod = RebatePeriod;
+    type ForceOrigin = frame_system::EnsureRoot<AccountId>;
 }
 
 impl orml_nft::Config for Runtime {
@@ -303,7 +434,7 @@ construct_runtime!(
         Sudo: pallet_sudo::{Module, Call, Config<T>, Storage, Event<T>},
         // Include the custom logic from the pallet-template in the runtime.
         TemplateModule: pallet_template::{Module, Call, Storage, Event<T>},
-        Kitties: pallet_kitties::{Module, Call, Storage, Event<T>},
+        Kitties: pallet_kitties::{Module, Call, Config, Storage, Event<T>},
         NFT: orml_nft::{Module, Storage},
     }
 );
@@ -471,6 +602,90 @@ impl_runtime_apis! {
         }
     }
 
+    impl pallet_kitties_runtime_api::KittiesApi<Block, AccountId, u32, Balance> for Runtime {
+        fn simulate_breed(
+            first: u32,
+            second: u32,
+            seed: Hash,
+        ) -> Result<pallet_kitties::Kitty, sp_runtime::DispatchError> {
+            Kitties::simulate_breed(first, second, seed)
+        }
+
+        fn can_breed(
+            owner: Option<AccountId>,
+            first: u32,
+            second: u32,
+        ) -> Result<(), sp_runtime::DispatchError> {
+            Kitties::can_breed(owner, first, second)
+        }
+
+        fn verify_dna(kitty_id: u32, seed_preimage: Vec<u8>) -> bool {
+            Kitties::verify_dna(kitty_id, seed_preimage)
+        }
+
+        fn kitty_of(kitty_id: u32) -> Option<(AccountId, pallet_kitties::Kitty)> {
+            Kitties::kitty_of(kitty_id)
+        }
+
+        fn kitties_with_trait(trait_id: u8, value: u8, limit: u32) -> Vec<u32> {
+            Kitties::kitties_with_trait(trait_id, value, limit)
+        }
+
+        fn similar_kitties(kitty_id: u32, max_distance: u32, limit: u32) -> Vec<(u32, u32)> {
+            Kitties::similar_kitties(kitty_id, max_distance, limit)
+        }
+
+        fn appraise(kitty_id: u32) -> Option<Balance> {
+            Kitties::appraise(kitty_id)
+        }
+
+        fn last_minted_by(owner: AccountId) -> Option<u32> {
+            Kitties::last_minted_by(owner)
+        }
+
+        fn describe_error(code: u8) -> Vec<u8> {
+            Kitties::describe_error(code)
+        }
+
+        fn render_kitty(kitty_id: u32) -> Vec<u8> {
+            Kitties::render_kitty(kitty_id)
+        }
+
+        fn token_metadata_json(kitty_id: u32) -> Vec<u8> {
+            Kitties::token_metadata_json(kitty_id)
+        }
+
+        fn sheltered_kitties(limit: u32) -> Vec<u32> {
+            Kitties::sheltered_kitties(limit)
+        }
+
+        fn kitties_page(
+            start: u32,
+            limit: u32,
+        ) -> (Vec<(u32, AccountId, pallet_kitties::Kitty)>, Option<u32>) {
+            Kitties::kitties_page(start, limit)
+        }
+
+        fn ownership_proof(kitty_id: u32) -> Option<pallet_kitties::OwnershipProof<AccountId>> {
+            Kitties::ownership_proof(kitty_id)
+        }
+
+        fn class_metadata() -> Option<pallet_kitties::CollectionMetadata> {
+            Kitties::class_metadata()
+        }
+
+        fn order_book(limit: u32) -> (
+            Vec<(u32, AccountId, Balance, pallet_kitties::Denomination)>,
+            Vec<(u32, pallet_kitties::CollectionBid<AccountId, Balance>)>,
+        ) {
+            Kitties::order_book(limit)
+        }
+
+        fn tax_report(account: AccountId, era: u32) -> pallet_kitties::TaxReport<Balance> {
+            Kitties::tax_report(&account, era)
+        }
+    }
+
     #[cfg(feature = "runtime-benchmarks")]
     impl frame_benchmarking::Benchmark<Block> for Runtime {
         fn dispatch_benchmark(
@@ -499,6 +714,7 @@ impl_runtime_apis! {
 
             add_benchmark!(params, batches, frame_system, SystemBench::<Runtime>);
             add_benchmark!(params, batches, pallet_balances, Balances);
+            add_benchmark!(params, batches, pallet_kitties, Kitties);
             add_benchmark!(params, batches, pallet_timestamp, Timestamp);
             add_benchmark!(params, batches, pallet_template, TemplateModule);
 